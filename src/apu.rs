@@ -0,0 +1,147 @@
+use crate::{
+    error::SaveStateError,
+    region::Region,
+    savestate::{Chunk, ChunkReader},
+    statehash::StateHasher,
+};
+
+const FRAME_COUNTER_FIVE_STEP: u8 = 0b1000_0000;
+const FRAME_COUNTER_IRQ_INHIBIT: u8 = 0b0100_0000;
+const STATUS_FRAME_INTERRUPT: u8 = 0b0100_0000;
+
+/// The APU as seen through its CPU-facing registers at $4000-$4013, $4015
+/// and $4017, plus the frame counter's timing. Sound generation itself is
+/// not modelled yet.
+pub struct APU {
+    pub registers: [u8; 0x14],
+    /// Channel enable bits written to $4015 (DMC, noise, triangle, pulse 2, pulse 1).
+    pub channels_enabled: u8,
+    pub frame_counter: u8,
+    /// Sets the frame counter's step lengths and the noise and DMC tables.
+    pub region: Region,
+    /// CPU cycles into the frame counter's current sequence.
+    pub sequence_cycle: u32,
+    /// Raised at the end of each 4-step sequence unless inhibited; read
+    /// and cleared through $4015.
+    pub frame_interrupt: bool,
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            registers: [0; 0x14],
+            channels_enabled: 0,
+            frame_counter: 0,
+            region: Region::Ntsc,
+            sequence_cycle: 0,
+            frame_interrupt: false,
+        }
+    }
+
+    pub fn power_on(&mut self) {
+        self.registers = [0; 0x14];
+        self.channels_enabled = 0;
+        self.frame_counter = 0;
+        self.sequence_cycle = 0;
+        self.frame_interrupt = false;
+    }
+
+    /// Reset silences every channel as if $00 had been written to $4015;
+    /// the frame counter keeps its mode but restarts its sequence.
+    pub fn reset(&mut self) {
+        self.channels_enabled = 0;
+        self.sequence_cycle = 0;
+        self.frame_interrupt = false;
+    }
+
+    /// Runs the frame counter for `cycles` CPU cycles.
+    pub fn tick(&mut self, cycles: u8) {
+        let steps = self.region.frame_counter_steps();
+        let five_step = self.frame_counter & FRAME_COUNTER_FIVE_STEP != 0;
+        let length = if five_step { steps[4] } else { steps[3] };
+
+        for _ in 0..cycles {
+            self.sequence_cycle += 1;
+            if self.sequence_cycle == steps[3]
+                && !five_step
+                && self.frame_counter & FRAME_COUNTER_IRQ_INHIBIT == 0 {
+                self.frame_interrupt = true;
+            }
+            if self.sequence_cycle > length {
+                self.sequence_cycle = 0;
+            }
+        }
+    }
+
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_interrupt = false;
+        status
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        // length counters are not modelled, so no channel reports as playing
+        if self.frame_interrupt { STATUS_FRAME_INTERRUPT } else { 0 }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4000..=0x4013 => self.registers[(addr - 0x4000) as usize] = data,
+            0x4015 => self.channels_enabled = data & 0b0001_1111,
+            0x4017 => {
+                self.frame_counter = data & 0b1100_0000;
+                self.sequence_cycle = 0;
+                if data & FRAME_COUNTER_IRQ_INHIBIT != 0 {
+                    self.frame_interrupt = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The noise channel's timer period in CPU cycles, as set through $400E.
+    pub fn noise_period(&self) -> u16 {
+        self.region.noise_periods()[(self.registers[0x0E] & 0x0F) as usize]
+    }
+
+    /// CPU cycles per DMC output bit, as set through $4010.
+    pub fn dmc_rate(&self) -> u16 {
+        self.region.dmc_rates()[(self.registers[0x10] & 0x0F) as usize]
+    }
+
+    pub fn save_state(&self, chunk: &mut Chunk) {
+        chunk.write_bytes(&self.registers);
+        chunk.write_u8(self.channels_enabled);
+        chunk.write_u8(self.frame_counter);
+        chunk.write_u32(self.sequence_cycle);
+        chunk.write_bool(self.frame_interrupt);
+    }
+
+    pub fn load_state(&mut self, chunk: &mut ChunkReader) -> Result<(), SaveStateError> {
+        chunk.read_into(&mut self.registers)?;
+        self.channels_enabled = chunk.read_u8()?;
+        self.frame_counter = chunk.read_u8()?;
+
+        // the frame counter's timing joined in version 6
+        if chunk.remaining() == 0 {
+            self.sequence_cycle = 0;
+            self.frame_interrupt = false;
+            return Ok(());
+        }
+        self.sequence_cycle = chunk.read_u32()?;
+        self.frame_interrupt = chunk.read_bool()?;
+        Ok(())
+    }
+
+    pub fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.registers);
+        hasher.write_bytes(&[self.channels_enabled, self.frame_counter, self.frame_interrupt as u8]);
+        hasher.write_u64(self.sequence_cycle as u64);
+    }
+}
@@ -0,0 +1,736 @@
+use std::collections::HashMap;
+use crate::{
+    cpu::AddressingMode,
+    error::AsmError,
+    opcode::CPU_OPCODES,
+};
+
+const DEFAULT_ORIGIN: u16 = 0x8000;
+
+/// A run of assembled bytes starting at `origin`. Every `.org` that moves
+/// the location counter starts a new segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+}
+
+/// Assembles `source` into one contiguous image starting at the first
+/// segment's origin ($8000 unless the source says otherwise). Gaps left by
+/// `.org` are padded with $00; segments must be in ascending order.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let segments = assemble_segments(source)?;
+    let Some(first) = segments.first() else {
+        return Ok(Vec::new());
+    };
+
+    let origin = first.origin as usize;
+    let mut image = Vec::new();
+    for segment in segments.iter() {
+        if (segment.origin as usize) < origin + image.len() {
+            return Err(AsmError::OverlappingSegment { origin: segment.origin });
+        }
+        let start = segment.origin as usize - origin;
+        if image.len() < start {
+            image.resize(start, 0);
+        }
+        image.extend_from_slice(&segment.bytes);
+    }
+    Ok(image)
+}
+
+/// Assembles `source` into the segments it places in memory, e.g. for
+/// patching several places in a ROM at once.
+pub fn assemble_segments(source: &str) -> Result<Vec<Segment>, AsmError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| parse_line(i + 1, text))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut assembler = Assembler::new();
+    assembler.layout(&lines)?;
+    assembler.emit(&lines)
+}
+
+/// Assembles 6502 source given as one string literal per line, panicking on
+/// errors. Meant for tests and quick patches:
+///
+/// ```
+/// let program = nes_emulator::asm!(
+///     "LDA #$C0",
+///     "TAX",
+///     "INX",
+///     "BRK",
+/// );
+/// assert_eq!(program, vec![0xA9, 0xC0, 0xAA, 0xE8, 0x00]);
+/// ```
+#[macro_export]
+macro_rules! asm {
+    ($($line:expr),* $(,)?) => {
+        $crate::asm::assemble(&[$($line),*].join("\n"))
+            .unwrap_or_else(|err| panic!("{}", err))
+    };
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Symbol(String),
+    LocationCounter,
+    Negate(Box<Expr>),
+    LowByte(Box<Expr>),
+    HighByte(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expr),
+    Direct(Expr),
+    IndexedX(Expr),
+    IndexedY(Expr),
+    Indirect(Expr),
+    IndirectX(Expr),
+    IndirectY(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum DataItem {
+    Expr(Expr),
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Empty,
+    Equate(String, Expr),
+    Org(Expr),
+    Byte(Vec<DataItem>),
+    Word(Vec<Expr>),
+    Instruction(String, Operand),
+}
+
+struct Line {
+    number: usize,
+    label: Option<String>,
+    statement: Statement,
+}
+
+struct Assembler {
+    symbols: HashMap<String, Expr>,
+    /// Addressing mode chosen for each line during layout, so that both
+    /// passes agree on instruction sizes.
+    modes: Vec<Option<AddressingMode>>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Assembler {
+            symbols: HashMap::new(),
+            modes: Vec::new(),
+        }
+    }
+
+    /// First pass: assigns addresses to labels and picks addressing modes.
+    fn layout(&mut self, lines: &[Line]) -> Result<(), AsmError> {
+        let mut pc = DEFAULT_ORIGIN;
+
+        for line in lines {
+            if let Some(label) = &line.label {
+                self.define(line.number, label, Expr::Number(pc as i64))?;
+            }
+
+            let mut mode = None;
+            match &line.statement {
+                Statement::Empty => {}
+                Statement::Equate(name, expr) => self.define(line.number, name, expr.clone())?,
+                Statement::Org(expr) => pc = self.org(line.number, expr, pc)?,
+                Statement::Byte(items) => {
+                    let len: usize = items.iter().map(|item| match item {
+                        DataItem::Expr(_) => 1,
+                        DataItem::Text(text) => text.len(),
+                    }).sum();
+                    pc = pc.wrapping_add(len as u16);
+                }
+                Statement::Word(exprs) => pc = pc.wrapping_add(2 * exprs.len() as u16),
+                Statement::Instruction(mnemonic, operand) => {
+                    let chosen = self.choose_mode(line.number, mnemonic, operand, pc)?;
+                    pc = pc.wrapping_add(instruction_len(&chosen));
+                    mode = Some(chosen);
+                }
+            }
+            self.modes.push(mode);
+        }
+        Ok(())
+    }
+
+    /// Second pass: evaluates every expression and produces the bytes.
+    fn emit(&self, lines: &[Line]) -> Result<Vec<Segment>, AsmError> {
+        let mut segments = vec![Segment { origin: DEFAULT_ORIGIN, bytes: Vec::new() }];
+        let mut pc = DEFAULT_ORIGIN;
+
+        for (line, mode) in lines.iter().zip(self.modes.iter()) {
+            let mut bytes = Vec::new();
+            match &line.statement {
+                Statement::Empty | Statement::Equate(..) => {}
+                Statement::Org(expr) => {
+                    pc = self.org(line.number, expr, pc)?;
+                    let current = segments.last().unwrap();
+                    if current.bytes.is_empty() {
+                        segments.pop();
+                    }
+                    segments.push(Segment { origin: pc, bytes: Vec::new() });
+                }
+                Statement::Byte(items) => {
+                    for item in items {
+                        match item {
+                            DataItem::Expr(expr) => {
+                                let value = self.eval_resolved(line.number, expr, pc)?;
+                                bytes.push(to_byte(line.number, value)?);
+                            }
+                            DataItem::Text(text) => bytes.extend(text.bytes()),
+                        }
+                    }
+                }
+                Statement::Word(exprs) => {
+                    for expr in exprs {
+                        let value = self.eval_resolved(line.number, expr, pc)?;
+                        bytes.extend(to_word(line.number, value)?.to_le_bytes());
+                    }
+                }
+                Statement::Instruction(mnemonic, operand) => {
+                    let mode = mode.as_ref().unwrap();
+                    bytes = self.encode(line.number, mnemonic, operand, mode, pc)?;
+                }
+            }
+
+            pc = pc.wrapping_add(bytes.len() as u16);
+            segments.last_mut().unwrap().bytes.extend(bytes);
+        }
+
+        segments.retain(|segment| !segment.bytes.is_empty());
+        Ok(segments)
+    }
+
+    fn define(&mut self, line: usize, name: &str, value: Expr) -> Result<(), AsmError> {
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(AsmError::DuplicateSymbol { line, name: name.to_string() });
+        }
+        Ok(())
+    }
+
+    fn org(&self, line: usize, expr: &Expr, pc: u16) -> Result<u16, AsmError> {
+        to_word(line, self.eval_resolved(line, expr, pc)?)
+    }
+
+    fn choose_mode(
+        &self,
+        line: usize,
+        mnemonic: &str,
+        operand: &Operand,
+        pc: u16,
+    ) -> Result<AddressingMode, AsmError> {
+        let has = |mode: AddressingMode| find_opcode(mnemonic, &mode).is_some();
+        if !CPU_OPCODES.iter().any(|op| op.name == mnemonic) {
+            return Err(AsmError::UnknownInstruction { line, mnemonic: mnemonic.to_string() });
+        }
+
+        // zero page is only picked when the operand is already known to fit,
+        // so forward references always get the absolute form
+        let (zero_page, absolute, expr) = match operand {
+            Operand::Direct(expr) if has(AddressingMode::Relative) =>
+                return Ok(AddressingMode::Relative),
+            Operand::Direct(expr) => (AddressingMode::ZeroPage, AddressingMode::Absolute, expr),
+            Operand::IndexedX(expr) => (AddressingMode::ZeroPageX, AddressingMode::AbsoluteX, expr),
+            Operand::IndexedY(expr) => (AddressingMode::ZeroPageY, AddressingMode::AbsoluteY, expr),
+            Operand::None | Operand::Accumulator => {
+                let mode = [
+                    AddressingMode::Accumulator,
+                    AddressingMode::Implied,
+                    AddressingMode::NoneAddressing,
+                ].into_iter().find(|mode| has(mode.clone()));
+                return mode.ok_or_else(|| invalid_mode(line, mnemonic));
+            }
+            Operand::Immediate(_) => return self.require(line, mnemonic, AddressingMode::Immediate),
+            Operand::Indirect(_) => return self.require(line, mnemonic, AddressingMode::Indirect),
+            Operand::IndirectX(_) => return self.require(line, mnemonic, AddressingMode::IndirectX),
+            Operand::IndirectY(_) => return self.require(line, mnemonic, AddressingMode::IndirectY),
+        };
+
+        let fits_zero_page = matches!(self.eval(line, expr, pc), Ok(Some(0..=0xFF)));
+        if has(zero_page.clone()) && (fits_zero_page || !has(absolute.clone())) {
+            Ok(zero_page)
+        } else if has(absolute.clone()) {
+            Ok(absolute)
+        } else {
+            Err(invalid_mode(line, mnemonic))
+        }
+    }
+
+    fn require(&self, line: usize, mnemonic: &str, mode: AddressingMode) -> Result<AddressingMode, AsmError> {
+        match find_opcode(mnemonic, &mode) {
+            Some(_) => Ok(mode),
+            None => Err(invalid_mode(line, mnemonic)),
+        }
+    }
+
+    fn encode(
+        &self,
+        line: usize,
+        mnemonic: &str,
+        operand: &Operand,
+        mode: &AddressingMode,
+        pc: u16,
+    ) -> Result<Vec<u8>, AsmError> {
+        let opcode = find_opcode(mnemonic, mode).ok_or_else(|| invalid_mode(line, mnemonic))?;
+        let mut bytes = vec![opcode];
+
+        let expr = match operand {
+            Operand::None | Operand::Accumulator => return Ok(bytes),
+            Operand::Immediate(expr)
+                | Operand::Direct(expr)
+                | Operand::IndexedX(expr)
+                | Operand::IndexedY(expr)
+                | Operand::Indirect(expr)
+                | Operand::IndirectX(expr)
+                | Operand::IndirectY(expr) => expr,
+        };
+        let value = self.eval_resolved(line, expr, pc)?;
+
+        match instruction_len(mode) {
+            2 if *mode == AddressingMode::Relative => {
+                let offset = value - (pc as i64 + 2);
+                if !(-128..=127).contains(&offset) {
+                    return Err(AsmError::BranchOutOfRange { line, offset });
+                }
+                bytes.push(offset as i8 as u8);
+            }
+            2 => bytes.push(to_byte(line, value)?),
+            _ => bytes.extend(to_word(line, value)?.to_le_bytes()),
+        }
+        Ok(bytes)
+    }
+
+    /// Evaluates `expr`, returning `None` while it still depends on symbols
+    /// that have not been defined yet.
+    fn eval(&self, line: usize, expr: &Expr, pc: u16) -> Result<Option<i64>, AsmError> {
+        self.eval_depth(line, expr, pc, 0)
+    }
+
+    fn eval_resolved(&self, line: usize, expr: &Expr, pc: u16) -> Result<i64, AsmError> {
+        match self.eval(line, expr, pc)? {
+            Some(value) => Ok(value),
+            None => Err(AsmError::UndefinedSymbol { line, name: first_symbol(expr) }),
+        }
+    }
+
+    fn eval_depth(&self, line: usize, expr: &Expr, pc: u16, depth: usize) -> Result<Option<i64>, AsmError> {
+        if depth > 64 {
+            return Err(AsmError::Syntax { line, message: "symbol defined in terms of itself".to_string() });
+        }
+
+        let value = match expr {
+            Expr::Number(value) => Some(*value),
+            Expr::LocationCounter => Some(pc as i64),
+            Expr::Symbol(name) => match self.symbols.get(name) {
+                Some(expr) => self.eval_depth(line, expr, pc, depth + 1)?,
+                None => None,
+            },
+            Expr::Negate(inner) => self.eval_depth(line, inner, pc, depth + 1)?.map(|v| -v),
+            Expr::LowByte(inner) => self.eval_depth(line, inner, pc, depth + 1)?.map(|v| v & 0xFF),
+            Expr::HighByte(inner) => self.eval_depth(line, inner, pc, depth + 1)?.map(|v| (v >> 8) & 0xFF),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval_depth(line, lhs, pc, depth + 1)?;
+                let rhs = self.eval_depth(line, rhs, pc, depth + 1)?;
+                match (lhs, rhs) {
+                    (Some(lhs), Some(rhs)) => Some(apply(line, *op, lhs, rhs)?),
+                    _ => None,
+                }
+            }
+        };
+        Ok(value)
+    }
+}
+
+fn apply(line: usize, op: char, lhs: i64, rhs: i64) -> Result<i64, AsmError> {
+    Ok(match op {
+        '+' => lhs.wrapping_add(rhs),
+        '-' => lhs.wrapping_sub(rhs),
+        '*' => lhs.wrapping_mul(rhs),
+        '/' if rhs == 0 => return Err(AsmError::Syntax { line, message: "division by zero".to_string() }),
+        '/' => lhs / rhs,
+        '&' => lhs & rhs,
+        '|' => lhs | rhs,
+        '^' => lhs ^ rhs,
+        '<' => lhs.wrapping_shl(rhs as u32),
+        '>' => lhs.wrapping_shr(rhs as u32),
+        _ => unreachable!("unknown operator {}", op),
+    })
+}
+
+fn first_symbol(expr: &Expr) -> String {
+    match expr {
+        Expr::Symbol(name) => name.clone(),
+        Expr::Negate(inner) | Expr::LowByte(inner) | Expr::HighByte(inner) => first_symbol(inner),
+        Expr::Binary(_, lhs, rhs) => {
+            let name = first_symbol(lhs);
+            if name.is_empty() { first_symbol(rhs) } else { name }
+        }
+        Expr::Number(_) | Expr::LocationCounter => String::new(),
+    }
+}
+
+fn find_opcode(mnemonic: &str, mode: &AddressingMode) -> Option<u8> {
+    CPU_OPCODES
+        .iter()
+        .find(|op| op.name == mnemonic && op.addressing_mode == *mode)
+        .map(|op| op.opcode)
+}
+
+fn instruction_len(mode: &AddressingMode) -> u16 {
+    match mode {
+        AddressingMode::Implied
+            | AddressingMode::Accumulator
+            | AddressingMode::NoneAddressing => 1,
+        AddressingMode::Absolute
+            | AddressingMode::AbsoluteX
+            | AddressingMode::AbsoluteY
+            | AddressingMode::Indirect => 3,
+        _ => 2,
+    }
+}
+
+fn invalid_mode(line: usize, mnemonic: &str) -> AsmError {
+    AsmError::InvalidAddressingMode { line, mnemonic: mnemonic.to_string() }
+}
+
+fn to_byte(line: usize, value: i64) -> Result<u8, AsmError> {
+    match value {
+        -128..=255 => Ok(value as u8),
+        _ => Err(AsmError::ValueOutOfRange { line, value }),
+    }
+}
+
+fn to_word(line: usize, value: i64) -> Result<u16, AsmError> {
+    match value {
+        -32768..=65535 => Ok(value as u16),
+        _ => Err(AsmError::ValueOutOfRange { line, value }),
+    }
+}
+
+fn parse_line(number: usize, text: &str) -> Result<Line, AsmError> {
+    let syntax = |message: &str| AsmError::Syntax { line: number, message: message.to_string() };
+    let mut rest = strip_comment(text).trim();
+
+    let mut label = None;
+    if let Some(colon) = rest.find(':') {
+        let name = rest[..colon].trim();
+        if is_identifier(name) {
+            label = Some(name.to_string());
+            rest = rest[colon + 1..].trim();
+        }
+    }
+
+    if rest.is_empty() {
+        return Ok(Line { number, label, statement: Statement::Empty });
+    }
+
+    if let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        if is_identifier(name) {
+            let expr = parse_expr(number, &rest[eq + 1..])?;
+            return Ok(Line { number, label, statement: Statement::Equate(name.to_string(), expr) });
+        }
+    }
+
+    let (word, args) = match rest.find(char::is_whitespace) {
+        Some(split) => (&rest[..split], rest[split..].trim()),
+        None => (rest, ""),
+    };
+
+    let statement = match word.to_ascii_lowercase().as_str() {
+        ".org" => Statement::Org(parse_expr(number, args)?),
+        ".byte" | ".db" => {
+            let mut items = Vec::new();
+            for arg in split_args(args) {
+                if let Some(text) = arg.strip_prefix('"') {
+                    let text = text.strip_suffix('"').ok_or_else(|| syntax("unterminated string"))?;
+                    items.push(DataItem::Text(text.to_string()));
+                } else {
+                    items.push(DataItem::Expr(parse_expr(number, &arg)?));
+                }
+            }
+            Statement::Byte(items)
+        }
+        ".word" | ".dw" => Statement::Word(
+            split_args(args).iter().map(|arg| parse_expr(number, arg)).collect::<Result<_, _>>()?,
+        ),
+        directive if directive.starts_with('.') =>
+            return Err(syntax(&format!("unknown directive {}", word))),
+        _ => Statement::Instruction(word.to_ascii_uppercase(), parse_operand(number, args)?),
+    };
+
+    Ok(Line { number, label, statement })
+}
+
+fn parse_operand(line: usize, text: &str) -> Result<Operand, AsmError> {
+    let text = text.trim();
+    let upper = text.to_ascii_uppercase().replace(' ', "");
+
+    if text.is_empty() {
+        return Ok(Operand::None);
+    }
+    if upper == "A" {
+        return Ok(Operand::Accumulator);
+    }
+    if let Some(expr) = text.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_expr(line, expr)?));
+    }
+    if upper.starts_with('(') {
+        let missing = || AsmError::Syntax { line, message: format!("malformed operand {}", text) };
+        if upper.ends_with(",X)") {
+            let inner = indexed_indirect(text, false).ok_or_else(missing)?;
+            return Ok(Operand::IndirectX(parse_expr(line, inner)?));
+        }
+        if upper.ends_with("),Y") {
+            let inner = indexed_indirect(text, true).ok_or_else(missing)?;
+            return Ok(Operand::IndirectY(parse_expr(line, inner)?));
+        }
+        if matching_paren(text) == Some(text.len() - 1) {
+            return Ok(Operand::Indirect(parse_expr(line, &text[1..text.len() - 1])?));
+        }
+    }
+    if upper.ends_with(",X") {
+        return Ok(Operand::IndexedX(parse_expr(line, &text[..text.rfind(',').unwrap()])?));
+    }
+    if upper.ends_with(",Y") {
+        return Ok(Operand::IndexedY(parse_expr(line, &text[..text.rfind(',').unwrap()])?));
+    }
+    Ok(Operand::Direct(parse_expr(line, text)?))
+}
+
+/// Returns the pointer expression of `(expr,X)`, or of `(expr),Y` when
+/// `post_indexed` is set.
+fn indexed_indirect(text: &str, post_indexed: bool) -> Option<&str> {
+    let comma = text.rfind(',')?;
+    let inner = &text[1..comma];
+    if post_indexed {
+        inner.trim_end().strip_suffix(')')
+    } else {
+        Some(inner)
+    }
+}
+
+fn matching_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The length in bytes of the character literal, such as `';'`, that
+/// starts at byte `i` of `text`, or 0 if none does.
+fn char_literal_len(text: &str, i: usize) -> usize {
+    let mut chars = text[i..].chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('\''), Some(c), Some('\'')) => c.len_utf8() + 2,
+        _ => 0,
+    }
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut literal_end = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if i < literal_end => {}
+            '"' => in_string = !in_string,
+            '\'' if !in_string => literal_end = i + char_literal_len(text, i),
+            ';' if !in_string => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut literal_end = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if i < literal_end => current.push(c),
+            '"' => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            '\'' if !in_string => {
+                literal_end = i + char_literal_len(text, i);
+                current.push(c);
+            }
+            ',' if !in_string => args.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        args.push(current.trim().to_string());
+    }
+    args
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_expr(line: usize, text: &str) -> Result<Expr, AsmError> {
+    let mut parser = ExprParser { line, chars: text.chars().collect(), pos: 0 };
+    let expr = parser.binary(0)?;
+    parser.skip_whitespace();
+    if parser.pos != parser.chars.len() {
+        return Err(parser.error("unexpected characters after expression"));
+    }
+    Ok(expr)
+}
+
+/// Precedence climbing over `| ^ & << >> + - * /`, loosest first.
+struct ExprParser {
+    line: usize,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+const BINARY_OPERATORS: [&[char]; 6] = [&['|'], &['^'], &['&'], &['<', '>'], &['+', '-'], &['*', '/']];
+
+impl ExprParser {
+    fn error(&self, message: &str) -> AsmError {
+        AsmError::Syntax { line: self.line, message: message.to_string() }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, AsmError> {
+        if level == BINARY_OPERATORS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(c) = self.peek() {
+            if !BINARY_OPERATORS[level].contains(&c) {
+                break;
+            }
+            // shifts are spelled `<<` and `>>`
+            if c == '<' || c == '>' {
+                if self.chars.get(self.pos + 1) != Some(&c) {
+                    break;
+                }
+                self.pos += 1;
+            }
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(c, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, AsmError> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            Some('<') => {
+                self.pos += 1;
+                Ok(Expr::LowByte(Box::new(self.unary()?)))
+            }
+            Some('>') => {
+                self.pos += 1;
+                Ok(Expr::HighByte(Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, AsmError> {
+        let Some(c) = self.peek() else {
+            return Err(self.error("expected an expression"));
+        };
+
+        match c {
+            '(' => {
+                self.pos += 1;
+                let expr = self.binary(0)?;
+                if self.peek() != Some(')') {
+                    return Err(self.error("expected )"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            '*' => {
+                self.pos += 1;
+                Ok(Expr::LocationCounter)
+            }
+            '$' => {
+                self.pos += 1;
+                self.number(16)
+            }
+            '%' => {
+                self.pos += 1;
+                self.number(2)
+            }
+            '\'' => {
+                let value = self.chars.get(self.pos + 1).copied();
+                if self.chars.get(self.pos + 2) != Some(&'\'') {
+                    return Err(self.error("unterminated character literal"));
+                }
+                self.pos += 3;
+                Ok(Expr::Number(value.unwrap() as i64))
+            }
+            c if c.is_ascii_digit() => self.number(10),
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    self.pos += 1;
+                }
+                Ok(Expr::Symbol(self.chars[start..self.pos].iter().collect()))
+            }
+            _ => Err(self.error(&format!("unexpected character {:?}", c))),
+        }
+    }
+
+    fn number(&mut self, radix: u32) -> Result<Expr, AsmError> {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_digit(radix)) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        i64::from_str_radix(&digits, radix)
+            .map(Expr::Number)
+            .map_err(|_| self.error("malformed number"))
+    }
+}
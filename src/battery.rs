@@ -0,0 +1,72 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use crate::cartridge::Cartridge;
+
+/// Keeps a battery-backed cartridge's PRG-RAM in a `.sav` file, the way the
+/// console keeps it alive while switched off.
+pub struct BatterySave {
+    path: PathBuf,
+    /// Frames between periodic flushes, so a crash loses little progress.
+    interval: u64,
+    last_flush: u64,
+}
+
+impl BatterySave {
+    /// The save file for `rom`: the same name with a `.sav` extension, in the
+    /// same directory.
+    pub fn for_rom(rom: impl AsRef<Path>, interval: u64) -> Self {
+        BatterySave {
+            path: rom.as_ref().with_extension("sav"),
+            interval,
+            last_flush: 0,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the save file into the cartridge. Returns `Ok(false)` when the
+    /// cartridge has no battery or nothing has been saved yet.
+    pub fn load(&self, cartridge: &mut Cartridge) -> io::Result<bool> {
+        if !cartridge.battery {
+            return Ok(false);
+        }
+        match fs::read(&self.path) {
+            Ok(data) => {
+                cartridge.load_battery_ram(&data);
+                Ok(true)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes PRG-RAM out if it changed since the last flush. The file is
+    /// replaced atomically so an interrupted write cannot lose the old save.
+    pub fn flush(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        let Some(ram) = cartridge.battery_ram() else {
+            return Ok(());
+        };
+        if !cartridge.prg_ram_dirty {
+            return Ok(());
+        }
+
+        let temp = self.path.with_extension("sav.tmp");
+        fs::write(&temp, ram)?;
+        fs::rename(&temp, &self.path)?;
+        cartridge.prg_ram_dirty = false;
+        Ok(())
+    }
+
+    /// Call once per frame; flushes every `interval` frames.
+    pub fn on_frame(&mut self, cartridge: &mut Cartridge, frame: u64) -> io::Result<()> {
+        if frame >= self.last_flush + self.interval {
+            self.last_flush = frame;
+            self.flush(cartridge)?;
+        }
+        Ok(())
+    }
+}
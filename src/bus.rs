@@ -0,0 +1,515 @@
+use std::fmt;
+use crate::{
+    apu::APU,
+    cartridge::Cartridge,
+    cheat::Cheats,
+    error::SaveStateError,
+    input::{InputLayout, PortDevice},
+    joypad::{Buttons, Joypad},
+    ppu::PPU,
+    region::Region,
+    savestate::{StateReader, StateWriter},
+    statehash::StateHasher,
+    zapper::Zapper,
+};
+
+// $0000-$1FFF  2 KiB work RAM, mirrored every $0800
+// $2000-$3FFF  PPU registers, mirrored every 8 bytes
+// $4000-$401F  APU and I/O registers
+// $4020-$5FFF  expansion ROM
+// $6000-$7FFF  cartridge PRG-RAM
+// $8000-$FFFF  cartridge PRG-ROM
+
+const RAM: u16 = 0x0000;
+const RAM_MIRRORS_END: u16 = 0x1FFF;
+const PPU_REGISTERS: u16 = 0x2000;
+const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+
+/// Contents of work RAM at power-up. Real consoles come up with a mostly
+/// unpredictable pattern, which some games accidentally depend on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RamPattern {
+    Zeros,
+    Ones,
+    Random(u64),
+}
+
+impl RamPattern {
+    pub fn fill(&self, ram: &mut [u8]) {
+        match *self {
+            RamPattern::Zeros => ram.fill(0x00),
+            RamPattern::Ones => ram.fill(0xFF),
+            RamPattern::Random(seed) => {
+                // xorshift64*, so a given seed always yields the same RAM
+                let mut state = if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed };
+                for byte in ram.iter_mut() {
+                    state ^= state >> 12;
+                    state ^= state << 25;
+                    state ^= state >> 27;
+                    *byte = (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Everything the CPU can reach through its address and data lines.
+pub trait Bus {
+    fn mem_read(&mut self, addr: u16) -> u8;
+    fn mem_write(&mut self, addr: u16, data: u8);
+    /// Reads without the side effects a real read would have, for debuggers
+    /// and the disassembler.
+    fn peek(&self, addr: u16) -> u8;
+    /// Reads work RAM or PRG-RAM as stored, before cheats, for memory
+    /// search tools.
+    fn peek_ram(&self, addr: u16) -> u8 {
+        self.peek(addr)
+    }
+    /// Reads the first byte of an instruction. Buses that log execution
+    /// tell it apart from other reads here.
+    fn fetch_opcode(&mut self, addr: u16) -> u8 {
+        self.mem_read(addr)
+    }
+    /// A read the CPU makes only because of how an instruction is
+    /// sequenced, such as the read of the wrong page while an index carries.
+    /// It has the side effects of a real read but its value is discarded,
+    /// so buses that log data reads leave it out here.
+    fn dummy_read(&mut self, addr: u16) -> u8 {
+        self.mem_read(addr)
+    }
+
+    /// Puts every device on the bus into its power-up state.
+    fn power_on(&mut self, pattern: RamPattern);
+    /// Forwards the console's reset line to the devices wired to it.
+    fn reset(&mut self);
+
+    /// Advances the devices that run alongside the CPU by `cycles` CPU cycles.
+    fn tick(&mut self, _cycles: u8) {}
+    /// Whether a device has raised NMI since the last poll.
+    fn poll_nmi(&mut self) -> bool {
+        false
+    }
+    /// The page written to the OAM DMA register since the last call, for
+    /// the CPU to halt and copy.
+    fn take_oam_dma(&mut self) -> Option<u8> {
+        None
+    }
+    /// Frames completed so far, for buses with a video device.
+    fn frame(&self) -> u64 {
+        0
+    }
+    /// What the players are doing with the controllers, for running a
+    /// frame again with `set_input`. Buses without controllers have none.
+    fn input(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    fn set_input(&mut self, _input: &[u8]) {}
+
+    /// Writes the state of every device on the bus as save-state chunks.
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &StateReader) -> Result<(), SaveStateError>;
+    /// Feeds the state that determines future execution into `hasher`.
+    fn hash_state(&self, hasher: &mut StateHasher);
+}
+
+/// A flat 64 KiB of RAM with nothing mapped into it, for running bare 6502
+/// code outside of the NES memory map.
+pub struct FlatMemory {
+    memory: Box<[u8; 0x10000]>,
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlatMemory {
+    pub fn new() -> Self {
+        FlatMemory {
+            memory: Box::new([0; 0x10000]),
+        }
+    }
+}
+
+impl Bus for FlatMemory {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.memory[addr as usize] = data;
+    }
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn power_on(&mut self, pattern: RamPattern) {
+        pattern.fill(&mut self.memory[0x0000..0x0800]);
+    }
+    fn reset(&mut self) {}
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.chunk(*b"MEM ").write_bytes(&self.memory[..]);
+    }
+    fn load_state(&mut self, state: &StateReader) -> Result<(), SaveStateError> {
+        state.chunk(*b"MEM ")?.read_into(&mut self.memory[..])
+    }
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.memory[..]);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusAccess {
+    pub address: u16,
+    pub value: u8,
+    pub kind: AccessKind,
+}
+
+impl fmt::Display for BusAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "read",
+            AccessKind::Write => "write",
+        };
+        write!(f, "{} ${:04X} ${:02X}", kind, self.address, self.value)
+    }
+}
+
+/// 64 KiB of RAM that records every access the CPU makes.
+pub struct TestBus {
+    memory: Box<[u8; 0x10000]>,
+    pub accesses: Vec<BusAccess>,
+}
+
+impl Default for TestBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestBus {
+    pub fn new() -> Self {
+        TestBus {
+            memory: Box::new([0; 0x10000]),
+            accesses: Vec::new(),
+        }
+    }
+
+    /// Sets memory without recording an access.
+    pub fn load(&mut self, addr: u16, data: u8) {
+        self.memory[addr as usize] = data;
+    }
+}
+
+impl Bus for TestBus {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        let value = self.memory[addr as usize];
+        self.accesses.push(BusAccess { address: addr, value, kind: AccessKind::Read });
+        value
+    }
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.accesses.push(BusAccess { address: addr, value: data, kind: AccessKind::Write });
+        self.memory[addr as usize] = data;
+    }
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn power_on(&mut self, pattern: RamPattern) {
+        pattern.fill(&mut self.memory[0x0000..0x0800]);
+    }
+    fn reset(&mut self) {}
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.chunk(*b"MEM ").write_bytes(&self.memory[..]);
+    }
+    fn load_state(&mut self, state: &StateReader) -> Result<(), SaveStateError> {
+        state.chunk(*b"MEM ")?.read_into(&mut self.memory[..])
+    }
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.memory[..]);
+    }
+}
+
+/// The NES memory map: 2 KiB of work RAM, the PPU and APU registers and
+/// the cartridge.
+pub struct NesBus {
+    cpu_vram: [u8; 0x0800],
+    pub cartridge: Cartridge,
+    pub ppu: PPU,
+    pub apu: APU,
+    /// The devices read through $4016 and $4017.
+    pub ports: [PortDevice; 2],
+    pub cheats: Cheats,
+    /// The page of a transfer requested through $4014.
+    oam_dma: Option<u8>,
+    /// The last value on the CPU data bus. Nothing drives the bus on reads
+    /// of unmapped addresses, so they return whatever was left on it.
+    pub open_bus: u8,
+    region: Region,
+    /// Master clock ticks run by the CPU but not yet by the PPU, which
+    /// divides the clock differently.
+    master_clock: u32,
+}
+
+impl NesBus {
+    /// A console of the region the cartridge's header asks for, NTSC if it
+    /// does not say.
+    pub fn new(cartridge: Cartridge) -> Self {
+        let region = cartridge.region.unwrap_or_default();
+        let mut bus = NesBus {
+            cpu_vram: [0; 0x0800],
+            cartridge,
+            ppu: PPU::new(),
+            apu: APU::new(),
+            ports: InputLayout::Standard.devices(),
+            cheats: Cheats::new(),
+            oam_dma: None,
+            open_bus: 0,
+            region,
+            master_clock: 0,
+        };
+        bus.set_region(region);
+        bus
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Switches the console to `region`'s clocks and video timing.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.region = region;
+        self.apu.region = region;
+        self.master_clock = 0;
+    }
+
+    /// The buttons of `player` (0-3). Players 3 and 4 need a four player
+    /// adapter; `None` if nothing is plugged in for them.
+    pub fn buttons_mut(&mut self, player: usize) -> Option<&mut Buttons> {
+        self.ports.get_mut(player % 2)?.buttons_mut(player / 2)
+    }
+
+    /// Sets `player`'s buttons, if they have a controller.
+    pub fn set_buttons(&mut self, player: usize, buttons: Buttons) {
+        if let Some(pressed) = self.buttons_mut(player) {
+            *pressed = buttons;
+        }
+    }
+
+    /// The Zapper, if one is plugged in.
+    pub fn zapper_mut(&mut self) -> Option<&mut Zapper> {
+        self.ports.iter_mut().find_map(|port| match port {
+            PortDevice::Zapper(zapper) => Some(zapper),
+            _ => None,
+        })
+    }
+
+    /// The 2 KiB of work RAM at $0000-$07FF.
+    pub fn ram(&self) -> &[u8] {
+        &self.cpu_vram
+    }
+}
+
+impl Bus for NesBus {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        let data = match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(addr & 0x07FF) as usize],
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END =>
+                self.ppu.read_register(addr, &mut self.cartridge),
+            // $4015 is read inside the CPU package, so it does not reach the
+            // external bus; bit 5 is not driven
+            0x4015 => return self.apu.read_status() | (self.open_bus & 0b0010_0000),
+            // the controller ports only drive the low five bits
+            0x4016 => self.ports[0].read(&self.ppu) | (self.open_bus & 0b1110_0000),
+            0x4017 => self.ports[1].read(&self.ppu) | (self.open_bus & 0b1110_0000),
+            0x6000..=0xFFFF => self.cartridge.fetch_prg(addr),
+            // write-only APU registers and the unused expansion area
+            _ => self.open_bus,
+        };
+        self.open_bus = self.cheats.apply(addr, data);
+        self.open_bus
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+        match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(addr & 0x07FF) as usize] = data,
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END =>
+                self.ppu.write_register(addr, data, &mut self.cartridge),
+            0x4014 => self.oam_dma = Some(data),
+            0x4016 => {
+                // both ports share the strobe line
+                for port in &mut self.ports {
+                    port.write(data);
+                }
+            }
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write_register(addr, data),
+            0x4020..=0xFFFF => self.cartridge.write_prg(addr, data),
+            _ => {}
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        let data = match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(addr & 0x07FF) as usize],
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => self.ppu.peek_register(addr),
+            0x4015 => return self.apu.peek_status() | (self.open_bus & 0b0010_0000),
+            0x4016 => self.ports[0].peek(&self.ppu) | (self.open_bus & 0b1110_0000),
+            0x4017 => self.ports[1].peek(&self.ppu) | (self.open_bus & 0b1110_0000),
+            0x6000..=0xFFFF => self.cartridge.read_prg(addr),
+            _ => self.open_bus,
+        };
+        self.cheats.apply(addr, data)
+    }
+    fn peek_ram(&self, addr: u16) -> u8 {
+        match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(addr & 0x07FF) as usize],
+            0x6000..=0x7FFF => self.cartridge.prg_ram[(addr - 0x6000) as usize],
+            _ => self.peek(addr),
+        }
+    }
+    fn fetch_opcode(&mut self, addr: u16) -> u8 {
+        let code = match addr {
+            0x6000..=0xFFFF => {
+                self.open_bus = self.cheats.apply(addr, self.cartridge.read_prg(addr));
+                self.open_bus
+            }
+            _ => self.mem_read(addr),
+        };
+        self.cartridge.log_opcode(addr, code);
+        code
+    }
+    fn dummy_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0xFFFF => {
+                self.open_bus = self.cheats.apply(addr, self.cartridge.read_prg(addr));
+                self.open_bus
+            }
+            _ => self.mem_read(addr),
+        }
+    }
+
+    fn power_on(&mut self, pattern: RamPattern) {
+        pattern.fill(&mut self.cpu_vram);
+        self.open_bus = 0;
+        self.ppu.power_on();
+        self.apu.power_on();
+    }
+
+    fn reset(&mut self) {
+        self.ppu.reset();
+        self.apu.reset();
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        // both run off the master clock: three PPU dots per CPU cycle on
+        // NTSC and Dendy, 3.2 on PAL
+        self.master_clock += cycles as u32 * self.region.cpu_divider();
+        let dots = self.master_clock / self.region.ppu_divider();
+        self.master_clock %= self.region.ppu_divider();
+        self.ppu.tick(dots, &mut self.cartridge);
+        self.apu.tick(cycles);
+    }
+    fn poll_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.ppu.nmi_pending)
+    }
+    fn take_oam_dma(&mut self) -> Option<u8> {
+        self.oam_dma.take()
+    }
+    fn frame(&self) -> u64 {
+        self.ppu.frame
+    }
+    fn input(&self) -> Vec<u8> {
+        self.ports.iter().flat_map(|port| port.input()).collect()
+    }
+    fn set_input(&mut self, mut input: &[u8]) {
+        for port in &mut self.ports {
+            port.set_input(&mut input);
+        }
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.chunk(*b"RAM ").write_bytes(&self.cpu_vram);
+        self.ppu.save_state(state.chunk(*b"PPU "));
+        self.apu.save_state(state.chunk(*b"APU "));
+        self.cartridge.save_state(state.chunk(*b"CART"));
+
+        let ports = state.chunk(*b"PORT");
+        for port in &self.ports {
+            port.save_state(ports);
+        }
+
+        let bus = state.chunk(*b"BUS ");
+        bus.write_u8(self.open_bus);
+        bus.write_u8(self.region.to_byte());
+        bus.write_u32(self.master_clock);
+    }
+
+    fn load_state(&mut self, state: &StateReader) -> Result<(), SaveStateError> {
+        // check the ROM first, so a state from another game changes nothing
+        self.cartridge.load_state(&mut state.chunk(*b"CART")?)?;
+        state.chunk(*b"RAM ")?.read_into(&mut self.cpu_vram)?;
+        self.ppu.load_state(&mut state.chunk(*b"PPU ")?)?;
+        self.apu.load_state(&mut state.chunk(*b"APU ")?)?;
+
+        // controllers joined the format in version 3, as a standard
+        // controller per port; other devices in version 7
+        if state.has_chunk(*b"PORT") {
+            let mut ports = state.chunk(*b"PORT")?;
+            for (i, port) in self.ports.iter_mut().enumerate() {
+                port.load_state(&mut ports, i)?;
+            }
+        } else {
+            let mut joypads = state.has_chunk(*b"JOYP").then(|| state.chunk(*b"JOYP")).transpose()?;
+            for port in &mut self.ports {
+                let mut joypad = Joypad::new();
+                if let Some(joypads) = &mut joypads {
+                    joypad.load_state(joypads)?;
+                }
+                if let PortDevice::Joypad(plugged) = port {
+                    *plugged = joypad;
+                }
+            }
+        }
+
+        // open bus joined the format in version 5, the region in version 6
+        self.open_bus = 0;
+        let mut region = Region::Ntsc;
+        let mut master_clock = 0;
+        if state.has_chunk(*b"BUS ") {
+            let mut bus = state.chunk(*b"BUS ")?;
+            self.open_bus = bus.read_u8()?;
+            if bus.remaining() > 0 {
+                region = Region::from_byte(bus.read_u8()?)
+                    .ok_or_else(|| SaveStateError::InvalidValue("region".to_string()))?;
+                master_clock = bus.read_u32()?;
+            }
+        }
+        self.set_region(region);
+        self.master_clock = master_clock;
+        Ok(())
+    }
+
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.cpu_vram);
+        hasher.write_bytes(&[self.oam_dma.is_some() as u8, self.oam_dma.unwrap_or(0)]);
+        for port in &self.ports {
+            port.hash_state(hasher);
+        }
+        hasher.write_u8(self.open_bus);
+        hasher.write_u8(self.region.to_byte());
+        hasher.write_u64(self.master_clock as u64);
+        self.ppu.hash_state(hasher);
+        self.apu.hash_state(hasher);
+        self.cartridge.hash_state(hasher);
+    }
+}
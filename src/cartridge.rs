@@ -0,0 +1,208 @@
+use crate::{
+    cdl::CodeDataLogger,
+    error::{RomError, SaveStateError},
+    region::Region,
+    savestate::{self, Chunk, ChunkReader},
+    statehash::StateHasher,
+};
+
+const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const PRG_ROM_PAGE_SIZE: usize = 0x4000;
+const CHR_ROM_PAGE_SIZE: usize = 0x2000;
+const PRG_RAM_SIZE: usize = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+}
+
+/// A cartridge loaded from an iNES (or NES 2.0) image.
+///
+/// Only mapper 0 (NROM) is supported: 16 or 32 KiB of PRG-ROM at $8000,
+/// 8 KiB of CHR-ROM (or CHR-RAM when the image has none) and 8 KiB of
+/// PRG-RAM at $6000.
+pub struct Cartridge {
+    pub prg_rom: Vec<u8>,
+    pub chr: Vec<u8>,
+    pub chr_is_ram: bool,
+    pub prg_ram: Vec<u8>,
+    pub mapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub nes2: bool,
+    /// The console the image was made for, when its NES 2.0 header says.
+    pub region: Option<Region>,
+    /// Set when battery-backed PRG-RAM changes, cleared once it is saved.
+    pub prg_ram_dirty: bool,
+    /// Code/data log of ROM accesses, when enabled.
+    pub cdl: Option<CodeDataLogger>,
+}
+
+impl Cartridge {
+    pub fn new(raw: &[u8]) -> Result<Cartridge, RomError> {
+        if raw.len() < 16 || raw[0..4] != NES_TAG {
+            return Err(RomError::InvalidHeader);
+        }
+
+        let nes2 = raw[7] & 0b0000_1100 == 0b0000_1000;
+        let mapper = (raw[7] & 0b1111_0000) | (raw[6] >> 4);
+        if mapper != 0 {
+            return Err(RomError::UnsupportedMapper(mapper));
+        }
+
+        let four_screen = raw[6] & 0b0000_1000 != 0;
+        let vertical = raw[6] & 0b0000_0001 != 0;
+        let mirroring = match (four_screen, vertical) {
+            (true, _) => Mirroring::FourScreen,
+            (false, true) => Mirroring::Vertical,
+            (false, false) => Mirroring::Horizontal,
+        };
+        let battery = raw[6] & 0b0000_0010 != 0;
+        let region = if nes2 { Region::from_nes2_timing(raw[12]) } else { None };
+        let trainer = raw[6] & 0b0000_0100 != 0;
+
+        let prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
+        let chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
+        let prg_rom_start = 16 + if trainer { 512 } else { 0 };
+        let chr_rom_start = prg_rom_start + prg_rom_size;
+
+        if prg_rom_size == 0 || raw.len() < chr_rom_start + chr_rom_size {
+            return Err(RomError::Truncated);
+        }
+
+        let chr_is_ram = chr_rom_size == 0;
+        let chr = if chr_is_ram {
+            vec![0; CHR_ROM_PAGE_SIZE]
+        } else {
+            raw[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec()
+        };
+
+        Ok(Cartridge {
+            prg_rom: raw[prg_rom_start..(prg_rom_start + prg_rom_size)].to_vec(),
+            chr,
+            chr_is_ram,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            mapper,
+            mirroring,
+            battery,
+            nes2,
+            region,
+            prg_ram_dirty: false,
+            cdl: None,
+        })
+    }
+
+    pub fn read_prg(&self, addr: u16) -> u8 {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF => self.prg_rom[self.prg_rom_offset(addr).unwrap()],
+            _ => 0,
+        }
+    }
+
+    /// Where in PRG-ROM the CPU address `addr` reads from, if anywhere.
+    pub fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        // 16 KiB images are mirrored into $C000-$FFFF
+        (addr >= 0x8000).then(|| (addr - 0x8000) as usize % self.prg_rom.len())
+    }
+
+    /// A CPU data read, recorded in the code/data log.
+    pub fn fetch_prg(&mut self, addr: u16) -> u8 {
+        if let (Some(offset), Some(cdl)) = (self.prg_rom_offset(addr), self.cdl.as_mut()) {
+            cdl.log_read(addr, offset);
+        }
+        self.read_prg(addr)
+    }
+
+    /// Records that the CPU fetched opcode `code` from `addr`, which need
+    /// not be in cartridge space.
+    pub fn log_opcode(&mut self, addr: u16, code: u8) {
+        if self.cdl.is_none() {
+            return;
+        }
+        let offsets = [0, 1, 2].map(|i| self.prg_rom_offset(addr.wrapping_add(i)));
+        if let Some(cdl) = self.cdl.as_mut() {
+            cdl.log_opcode(addr, code, offsets);
+        }
+    }
+
+    /// Starts a fresh code/data log sized for this cartridge's ROMs.
+    pub fn enable_cdl(&mut self) {
+        let chr_len = if self.chr_is_ram { 0 } else { self.chr.len() };
+        self.cdl = Some(CodeDataLogger::new(self.prg_rom.len(), chr_len));
+    }
+    pub fn write_prg(&mut self, addr: u16, data: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            let byte = &mut self.prg_ram[(addr - 0x6000) as usize];
+            self.prg_ram_dirty |= self.battery && *byte != data;
+            *byte = data;
+        }
+    }
+
+    pub fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[addr as usize % self.chr.len()]
+    }
+    /// A PPU pattern read, recorded in the code/data log as rendered or as
+    /// read through $2007.
+    pub fn fetch_chr(&mut self, addr: u16, rendering: bool) -> u8 {
+        let offset = addr as usize % self.chr.len();
+        if let (false, Some(cdl)) = (self.chr_is_ram, self.cdl.as_mut()) {
+            cdl.log_chr(offset, rendering);
+        }
+        self.chr[offset]
+    }
+    pub fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let len = self.chr.len();
+            self.chr[addr as usize % len] = data;
+        }
+    }
+
+    /// The PRG-RAM contents worth persisting, for cartridges with a battery.
+    pub fn battery_ram(&self) -> Option<&[u8]> {
+        self.battery.then_some(&self.prg_ram[..])
+    }
+
+    /// Replaces PRG-RAM with previously saved contents. Files of another
+    /// size are tolerated: a shorter one fills the start of RAM and a longer
+    /// one is cut off.
+    pub fn load_battery_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.prg_ram.len());
+        self.prg_ram[..len].copy_from_slice(&data[..len]);
+        self.prg_ram_dirty = false;
+    }
+
+    /// Saves cartridge RAM along with a checksum of the PRG-ROM, so that a
+    /// state is never restored into a different game. NROM has no mapper
+    /// registers to save.
+    pub fn save_state(&self, chunk: &mut Chunk) {
+        chunk.write_u32(savestate::crc32(&self.prg_rom));
+        chunk.write_bytes(&self.prg_ram);
+        if self.chr_is_ram {
+            chunk.write_bytes(&self.chr);
+        }
+    }
+
+    pub fn load_state(&mut self, chunk: &mut ChunkReader) -> Result<(), SaveStateError> {
+        if chunk.read_u32()? != savestate::crc32(&self.prg_rom) {
+            return Err(SaveStateError::RomMismatch);
+        }
+        chunk.read_into(&mut self.prg_ram)?;
+        self.prg_ram_dirty = self.battery;
+        if self.chr_is_ram {
+            chunk.read_into(&mut self.chr)?;
+        }
+        Ok(())
+    }
+
+    /// Hashes what can change while a game runs. NROM has no mapper
+    /// registers, so that is cartridge RAM.
+    pub fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.prg_ram);
+        if self.chr_is_ram {
+            hasher.write_bytes(&self.chr);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use bitflags::bitflags;
+use crate::{cpu::AddressingMode, opcode};
+
+bitflags! {
+    /// What a PRG-ROM byte has been used for, as stored in FCEUX .cdl files.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct PrgFlags: u8 {
+        /// Fetched as part of an instruction.
+        const CODE          = 0b0000_0001;
+        /// Read as an operand.
+        const DATA          = 0b0000_0010;
+        /// Which 8 KiB CPU window ($8000/$A000/$C000/$E000) the byte was
+        /// mapped into when it was last logged.
+        const BANK          = 0b0000_1100;
+        /// The target of an indirect `JMP`.
+        const INDIRECT_CODE = 0b0001_0000;
+        /// Read through a `(zp,X)` or `(zp),Y` pointer.
+        const INDIRECT_DATA = 0b0010_0000;
+        /// Played as a DMC sample.
+        const PCM_AUDIO     = 0b0100_0000;
+    }
+}
+
+bitflags! {
+    /// What a CHR-ROM byte has been used for, as stored in FCEUX .cdl files.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ChrFlags: u8 {
+        /// Fetched by the PPU to draw a background tile or sprite.
+        const RENDERED = 0b0000_0001;
+        /// Read by the program through $2007.
+        const READ     = 0b0000_0010;
+    }
+}
+
+/// Records how each byte of cartridge ROM gets used while a game runs, so
+/// that a disassembly can tell code from data.
+///
+/// The log is one flag byte per PRG-ROM byte followed by one per CHR-ROM
+/// byte, which is exactly the FCEUX .cdl layout. Cartridges with CHR-RAM
+/// have no CHR part. DMC sample fetches are not emulated yet, so
+/// `PCM_AUDIO` is never set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeDataLogger {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    /// Address and length of the instruction being executed, whose operand
+    /// fetches are code rather than data.
+    instruction: Option<(u16, u16)>,
+    indirect_data: bool,
+    after_indirect_jump: bool,
+}
+
+impl CodeDataLogger {
+    pub fn new(prg_len: usize, chr_len: usize) -> Self {
+        CodeDataLogger {
+            prg: vec![0; prg_len],
+            chr: vec![0; chr_len],
+            instruction: None,
+            indirect_data: false,
+            after_indirect_jump: false,
+        }
+    }
+
+    /// Continues a log saved by `to_bytes` (or by FCEUX). Returns `None` if
+    /// its size does not fit a cartridge with these ROM sizes.
+    pub fn from_bytes(data: &[u8], prg_len: usize, chr_len: usize) -> Option<Self> {
+        if data.len() != prg_len + chr_len {
+            return None;
+        }
+        let mut logger = CodeDataLogger::new(prg_len, chr_len);
+        logger.prg.copy_from_slice(&data[..prg_len]);
+        logger.chr.copy_from_slice(&data[prg_len..]);
+        Some(logger)
+    }
+
+    /// The log in .cdl layout.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.prg[..], &self.chr[..]].concat()
+    }
+
+    pub fn prg_flags(&self, offset: usize) -> PrgFlags {
+        PrgFlags::from_bits_retain(self.prg[offset])
+    }
+
+    pub fn chr_flags(&self, offset: usize) -> ChrFlags {
+        ChrFlags::from_bits_retain(self.chr[offset])
+    }
+
+    pub fn prg_len(&self) -> usize {
+        self.prg.len()
+    }
+
+    pub fn chr_len(&self) -> usize {
+        self.chr.len()
+    }
+
+    /// Forgets everything logged so far.
+    pub fn clear(&mut self) {
+        self.prg.fill(0);
+        self.chr.fill(0);
+        self.instruction = None;
+        self.indirect_data = false;
+        self.after_indirect_jump = false;
+    }
+
+    /// Marks the instruction starting at `addr` as code. `offsets` holds the
+    /// PRG-ROM offset of each of its bytes, or `None` where they are not in
+    /// ROM.
+    pub(crate) fn log_opcode(&mut self, addr: u16, code: u8, offsets: [Option<usize>; 3]) {
+        let opcode = opcode::lookup(code);
+        let len = opcode.map_or(1, |op| op.bytes) as u16;
+
+        for (i, offset) in offsets.into_iter().take(len as usize).enumerate() {
+            let Some(offset) = offset else {
+                continue;
+            };
+            let mut flags = PrgFlags::CODE | bank(addr.wrapping_add(i as u16));
+            if i == 0 && self.after_indirect_jump {
+                flags |= PrgFlags::INDIRECT_CODE;
+            }
+            self.prg[offset] |= flags.bits();
+        }
+
+        let mode = opcode.map(|op| &op.addressing_mode);
+        self.instruction = Some((addr, len));
+        self.indirect_data = matches!(mode, Some(AddressingMode::IndirectX | AddressingMode::IndirectY));
+        self.after_indirect_jump = opcode.is_some_and(|op| op.name == "JMP")
+            && mode == Some(&AddressingMode::Indirect);
+    }
+
+    /// Marks a CPU read of PRG-ROM as data, unless it is an operand fetch of
+    /// the current instruction.
+    pub(crate) fn log_read(&mut self, addr: u16, offset: usize) {
+        if let Some((start, len)) = self.instruction {
+            if addr.wrapping_sub(start) < len {
+                return;
+            }
+        }
+        let mut flags = PrgFlags::DATA | bank(addr);
+        if self.indirect_data {
+            flags |= PrgFlags::INDIRECT_DATA;
+        }
+        self.prg[offset] |= flags.bits();
+    }
+
+    pub(crate) fn log_chr(&mut self, offset: usize, rendering: bool) {
+        let flag = if rendering { ChrFlags::RENDERED } else { ChrFlags::READ };
+        if let Some(byte) = self.chr.get_mut(offset) {
+            *byte |= flag.bits();
+        }
+    }
+}
+
+/// The bank bits for a byte seen at `addr`: the FCEUX log only records
+/// which 8 KiB window it was read through, not the mapper bank number.
+fn bank(addr: u16) -> PrgFlags {
+    PrgFlags::from_bits_retain((((addr >> 13) & 0b11) as u8) << 2)
+}
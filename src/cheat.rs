@@ -0,0 +1,239 @@
+use std::fmt::{self, Write};
+use crate::error::CheatError;
+
+// A cheat file has one cheat per line: `on` or `off`, the code, then an
+// optional description running to the end of the line. Blank lines and
+// lines starting with `#` are ignored.
+//
+//   # Super Mario Bros.
+//   on  SXIOPO   Infinite lives
+//   off 075F:07  Start in world 8
+
+/// Game Genie letters, in the order of the nibble values they stand for.
+const GAME_GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    /// A Game Genie patch: reads of `address` in cartridge space return
+    /// `value` instead, but with a `compare` byte only while the ROM holds
+    /// that byte there, so that bank-switched code is left alone.
+    Patch { address: u16, value: u8, compare: Option<u8> },
+    /// A Pro Action Replay style freeze: reads of the RAM byte at `address`
+    /// always see `value`.
+    Freeze { address: u16, value: u8 },
+}
+
+impl CheatCode {
+    /// Accepts a 6- or 8-letter Game Genie code, or a freeze code written as
+    /// `AAAA:VV` or `AAAAVV` in hex. Six characters that are all Game Genie
+    /// letters are read as a Game Genie code.
+    pub fn parse(text: &str) -> Result<CheatCode, CheatError> {
+        let text = text.trim();
+        let invalid = || CheatError::InvalidCode(text.to_string());
+
+        let upper = text.to_ascii_uppercase();
+        if let Some(nibbles) = game_genie_nibbles(&upper) {
+            return Ok(decode_game_genie(&nibbles));
+        }
+
+        let hex = upper.replace(':', "");
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        Ok(CheatCode::Freeze {
+            address: u16::from_str_radix(&hex[..4], 16).map_err(|_| invalid())?,
+            value: u8::from_str_radix(&hex[4..], 16).map_err(|_| invalid())?,
+        })
+    }
+
+    /// What the CPU sees when it reads `data` from `addr` with this cheat on.
+    fn apply(&self, addr: u16, data: u8) -> u8 {
+        match *self {
+            CheatCode::Patch { address, value, compare } => {
+                if addr == address && compare.is_none_or(|compare| compare == data) {
+                    return value;
+                }
+            }
+            CheatCode::Freeze { address, value } => {
+                // work RAM is mirrored four times below $2000
+                let same = match (address, addr) {
+                    (0x0000..=0x1FFF, 0x0000..=0x1FFF) => address & 0x07FF == addr & 0x07FF,
+                    _ => address == addr,
+                };
+                if same {
+                    return value;
+                }
+            }
+        }
+        data
+    }
+}
+
+impl fmt::Display for CheatCode {
+    /// Patches are written as Game Genie codes, freezes as `AAAA:VV`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CheatCode::Patch { address, value, compare } => {
+                let text: String = encode_game_genie(address, value, compare).iter()
+                    .map(|&n| GAME_GENIE_LETTERS[n as usize] as char)
+                    .collect();
+                f.write_str(&text)
+            }
+            CheatCode::Freeze { address, value } => write!(f, "{:04X}:{:02X}", address, value),
+        }
+    }
+}
+
+fn game_genie_nibbles(text: &str) -> Option<Vec<u8>> {
+    if text.len() != 6 && text.len() != 8 {
+        return None;
+    }
+    text.bytes()
+        .map(|c| GAME_GENIE_LETTERS.iter().position(|&letter| letter == c).map(|n| n as u8))
+        .collect()
+}
+
+// Each letter is a nibble, and the address and value bits are scattered
+// across them. Eight-letter codes move the value's bit 3 to make room for a
+// compare byte; by convention their third letter has bit 3 set.
+
+fn decode_game_genie(n: &[u8]) -> CheatCode {
+    let address = 0x8000
+        | ((n[3] as u16 & 7) << 12)
+        | ((n[5] as u16 & 7) << 8)
+        | ((n[4] as u16 & 8) << 8)
+        | ((n[2] as u16 & 7) << 4)
+        | ((n[1] as u16 & 8) << 4)
+        | (n[4] as u16 & 7)
+        | (n[3] as u16 & 8);
+    let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7);
+
+    if n.len() == 6 {
+        return CheatCode::Patch { address, value: value | (n[5] & 8), compare: None };
+    }
+    let compare = ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8);
+    CheatCode::Patch { address, value: value | (n[7] & 8), compare: Some(compare) }
+}
+
+fn encode_game_genie(address: u16, value: u8, compare: Option<u8>) -> Vec<u8> {
+    let mut n = vec![
+        (value & 7) | ((value >> 4) & 8),
+        ((value >> 4) & 7) | ((address >> 4) as u8 & 8),
+        ((address >> 4) as u8 & 7) | if compare.is_some() { 8 } else { 0 },
+        ((address >> 12) as u8 & 7) | (address as u8 & 8),
+        (address as u8 & 7) | ((address >> 8) as u8 & 8),
+        ((address >> 8) as u8 & 7) | (compare.unwrap_or(value) & 8),
+    ];
+    if let Some(compare) = compare {
+        n.push((compare & 7) | ((compare >> 4) & 8));
+        n.push(((compare >> 4) & 7) | (value & 8));
+    }
+    n
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+    pub code: CheatCode,
+    pub description: String,
+    pub enabled: bool,
+}
+
+/// The cheats applied to CPU reads. Cheats stay in the list when turned
+/// off, so they can be switched back on while the game runs.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an enabled cheat and returns its index.
+    pub fn add(&mut self, code: CheatCode, description: &str) -> usize {
+        self.cheats.push(Cheat { code, description: description.to_string(), enabled: true });
+        self.cheats.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| self.cheats.remove(index))
+    }
+
+    /// Turns a cheat on or off. Returns `false` if there is no such cheat.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn len(&self) -> usize {
+        self.cheats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    /// What the CPU sees when it reads `data` from `addr`.
+    #[inline]
+    pub fn apply(&self, addr: u16, data: u8) -> u8 {
+        if self.cheats.is_empty() {
+            return data;
+        }
+        self.cheats.iter()
+            .filter(|cheat| cheat.enabled)
+            .fold(data, |data, cheat| cheat.code.apply(addr, data))
+    }
+
+    pub fn parse(text: &str) -> Result<Cheats, CheatError> {
+        let mut cheats = Cheats::new();
+        for (i, line) in text.lines().enumerate() {
+            let syntax = |message: String| CheatError::Syntax { line: i + 1, message };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (state, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let enabled = match state {
+                "on" => true,
+                "off" => false,
+                other => return Err(syntax(format!("expected on or off, found {:?}", other))),
+            };
+            let rest = rest.trim_start();
+            if rest.is_empty() {
+                return Err(syntax("missing code".to_string()));
+            }
+            let (code, description) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let code = CheatCode::parse(code).map_err(|err| syntax(err.to_string()))?;
+            let description = description.trim();
+
+            let index = cheats.add(code, description);
+            cheats.set_enabled(index, enabled);
+        }
+        Ok(cheats)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for cheat in &self.cheats {
+            let state = if cheat.enabled { "on " } else { "off" };
+            let line = format!("{} {:<9} {}", state, cheat.code.to_string(), cheat.description);
+            writeln!(out, "{}", line.trim_end()).unwrap();
+        }
+        out
+    }
+}
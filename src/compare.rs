@@ -0,0 +1,42 @@
+/// How two values are compared, as in a breakpoint condition or a RAM
+/// search filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Operators as typed, longest first so that `<=` is not read as `<`.
+pub const OPERATORS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Equal),
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+impl Comparison {
+    pub fn test(self, left: u16, right: u16) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        OPERATORS.iter().find(|&&(_, comparison)| comparison == self).unwrap().0
+    }
+
+    pub fn parse(text: &str) -> Option<Comparison> {
+        OPERATORS.iter().find(|&&(op, _)| op == text).map(|&(_, comparison)| comparison)
+    }
+}
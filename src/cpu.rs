@@ -1,12 +1,31 @@
-use crate::{error::CPUError, opcode::CPU_OPCODES};
-
-pub struct CPU {
+use crate::{
+    bus::{Bus, FlatMemory, RamPattern},
+    error::{CPUError, CPUState},
+    flags::StatusFlags,
+    opcode::{self, OpCode},
+    watch::{WatchKind, Watches},
+};
+
+const STACK: u16 = 0x0100;
+const NMI_VECTOR: u16 = 0xFFFA;
+const RESET_VECTOR: u16 = 0xFFFC;
+const OAM_DATA: u16 = 0x2004;
+
+pub struct CPU<B: Bus = FlatMemory> {
     pub register_a: u8,
     pub register_x: u8,
     pub register_y: u8,
-    pub status: u8,
+    pub status: StatusFlags,
+    pub stack_pointer: u8,
     pub program_counter: u16,
-    memory: [u8; 0xFFFF],
+    pub cycles: u64,
+    pub bus: B,
+    /// Callbacks on reads, writes and opcode fetches made by the CPU.
+    pub watches: Watches,
+    /// An NMI edge seen during the cycle just finished.
+    pub(crate) nmi_detected: bool,
+    /// An NMI edge seen early enough to be taken after this instruction.
+    pub(crate) nmi_pending: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -28,425 +47,671 @@ pub enum AddressingMode {
     NoneAddressing,
 }
 
+/// Why an instruction could not be executed; `run` turns this into a
+/// `CPUError` carrying the state of the machine.
+enum Fault {
+    Unimplemented,
+    InvalidAddressingMode(AddressingMode),
+}
+
+/// How an instruction uses its effective address. Indexed modes always
+/// read from the address before the carry into the high byte is fixed up;
+/// loads only wait for that fix-up, and so skip the extra read, when no
+/// page is crossed.
+#[derive(Clone, Copy, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CPU {
     pub fn new() -> Self {
+        CPU::with_bus(FlatMemory::new())
+    }
+
+    pub fn load(&mut self, program: Vec<u8>) {
+        for (i, &byte) in program.iter().enumerate() {
+            self.mem_write(0x8000 + i as u16, byte);
+        }
+        self.mem_write_u16(RESET_VECTOR, 0x8000);
+        self.program_counter = self.mem_read_u16(RESET_VECTOR);
+    }
+    pub fn load_and_run(&mut self, program: Vec<u8>) -> Result<(), CPUError> {
+        self.load(program);
+        self.run()?;
+        Ok(())
+    }
+}
+
+impl<B: Bus> CPU<B> {
+    pub fn with_bus(bus: B) -> Self {
         CPU {
             register_a: 0,
             register_x: 0,
             register_y: 0,
-            status: 0,
+            status: StatusFlags::empty(),
+            stack_pointer: 0,
             program_counter: 0,
-            memory: [0; 0xFFFF]
+            cycles: 0,
+            bus,
+            watches: Watches::default(),
+            nmi_detected: false,
+            nmi_pending: false,
         }
     }
 
-    fn get_operand_address(&mut self, mode: &AddressingMode) -> Option<u16> {
+    /// Fetches the operand for `mode` and works out the effective address,
+    /// making every read the 6502 makes along the way.
+    fn get_operand_address(&mut self, mode: &AddressingMode, access: Access) -> Result<u16, Fault> {
         match mode {
-            AddressingMode::Immediate => Some(self.program_counter),
-            AddressingMode::ZeroPage  => Some(self.mem_read(self.program_counter) as u16),
-            AddressingMode::Absolute => Some(self.mem_read_u16(self.program_counter)),
+            AddressingMode::Immediate => {
+                let addr = self.program_counter;
+                self.program_counter = self.program_counter.wrapping_add(1);
+                Ok(addr)
+            }
+            AddressingMode::ZeroPage => Ok(self.fetch() as u16),
+            AddressingMode::Absolute => Ok(self.fetch_u16()),
             AddressingMode::ZeroPageX => {
-                let pos = self.mem_read(self.program_counter);
-                let addr = pos.wrapping_add(self.register_x) as u16;
-                Some(addr)
+                let pos = self.fetch();
+                self.dummy_read(pos as u16);
+                Ok(pos.wrapping_add(self.register_x) as u16)
             }
             AddressingMode::ZeroPageY => {
-                let pos = self.mem_read(self.program_counter);
-                let addr = pos.wrapping_add(self.register_y) as u16;
-                Some(addr)
+                let pos = self.fetch();
+                self.dummy_read(pos as u16);
+                Ok(pos.wrapping_add(self.register_y) as u16)
             }
             AddressingMode::AbsoluteX => {
-                let base = self.mem_read_u16(self.program_counter);
-                let addr = base.wrapping_add(self.register_x as u16);
-                Some(addr)
+                let base = self.fetch_u16();
+                Ok(self.index(base, self.register_x, access))
             }
             AddressingMode::AbsoluteY => {
-                let base = self.mem_read_u16(self.program_counter);
-                let addr = base.wrapping_add(self.register_y as u16);
-                Some(addr)
+                let base = self.fetch_u16();
+                Ok(self.index(base, self.register_y, access))
             }
             AddressingMode::IndirectX => {
-                let base = self.mem_read(self.program_counter);
+                let base = self.fetch();
+                self.dummy_read(base as u16);
 
                 let ptr: u8 = base.wrapping_add(self.register_x);
-                let lo = self.mem_read(ptr as u16);
-                let hi = self.mem_read(ptr.wrapping_add(1) as u16);
-                Some((hi as u16) << 8 | (lo as u16))
+                let lo = self.read(ptr as u16);
+                let hi = self.read(ptr.wrapping_add(1) as u16);
+                Ok((hi as u16) << 8 | (lo as u16))
             }
             AddressingMode::IndirectY => {
-                let base = self.mem_read(self.program_counter);
+                let base = self.fetch();
 
-                let lo = self.mem_read(base as u16);
-                let hi = self.mem_read(base.wrapping_add(1) as u16);
+                let lo = self.read(base as u16);
+                let hi = self.read(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
-                let deref = deref_base.wrapping_add(self.register_y as u16);
-                Some(deref)
+                Ok(self.index(deref_base, self.register_y, access))
             }
-            AddressingMode::Accumulator 
-                | AddressingMode::Relative 
-                | AddressingMode::Implied 
-                | AddressingMode::Indirect => None,
-            AddressingMode::NoneAddressing => panic!("{}", CPUError::InvalidAddressingMode(mode)),
+            AddressingMode::Accumulator
+                | AddressingMode::Relative
+                | AddressingMode::Implied
+                | AddressingMode::Indirect
+                | AddressingMode::NoneAddressing => Err(Fault::InvalidAddressingMode(mode.clone())),
         }
     }
 
-    fn lda(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value: u8 = self.mem_read(addr);
+    /// Adds an index register to `base`. The low byte is added first, so the
+    /// CPU reads from the uncorrected address while it carries into the high
+    /// byte.
+    fn index(&mut self, base: u16, index: u8, access: Access) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        if access == Access::Write || addr & 0xFF00 != base & 0xFF00 {
+            self.dummy_read((base & 0xFF00) | (addr & 0x00FF));
+        }
+        addr
+    }
 
-        self.register_a = value;
-        self.update_zero_and_negative_flags(self.register_a);
+    /// Reads the operand of a load, compare or arithmetic instruction.
+    fn read_operand(&mut self, mode: &AddressingMode) -> Result<u8, Fault> {
+        let addr = self.get_operand_address(mode, Access::Read)?;
+        Ok(self.read(addr))
     }
-    fn sta(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        self.mem_write(addr, self.register_a);
+
+    /// Read-modify-write: the unmodified value is written back on the cycle
+    /// the ALU works on it, then the result is written on the next.
+    fn modify(&mut self, mode: &AddressingMode, op: fn(&mut Self, u8) -> u8) -> Result<(), Fault> {
+        if *mode == AddressingMode::Accumulator {
+            self.dummy_read(self.program_counter);
+            self.register_a = op(self, self.register_a);
+        } else {
+            let addr = self.get_operand_address(mode, Access::Write)?;
+            let value = self.read(addr);
+            self.write(addr, value);
+            let result = op(self, value);
+            self.write(addr, result);
+        }
+        Ok(())
     }
-    fn tax(&mut self) {
-        self.register_x = self.register_a;
-        self.update_zero_and_negative_flags(self.register_x);
+
+    /// The second cycle of a one-byte instruction, which reads the byte
+    /// after the opcode and throws it away.
+    fn implied(&mut self) {
+        self.dummy_read(self.program_counter);
     }
-    fn inx(&mut self) {
-        self.register_x = self.register_x.wrapping_add(1);
+
+    fn lda(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        self.register_a = self.read_operand(mode)?;
+        self.update_zero_and_negative_flags(self.register_a);
+        Ok(())
+    }
+    fn ldx(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        self.register_x = self.read_operand(mode)?;
         self.update_zero_and_negative_flags(self.register_x);
+        Ok(())
     }
-    fn adc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value: u8 = self.mem_read(addr);
-
+    fn ldy(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        self.register_y = self.read_operand(mode)?;
+        self.update_zero_and_negative_flags(self.register_y);
+        Ok(())
+    }
+    fn store(&mut self, mode: &AddressingMode, value: u8) -> Result<(), Fault> {
+        let addr = self.get_operand_address(mode, Access::Write)?;
+        self.write(addr, value);
+        Ok(())
+    }
+    fn transfer(&mut self, value: u8) -> u8 {
+        self.implied();
+        self.update_zero_and_negative_flags(value);
+        value
+    }
+    fn adc(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        let value = self.read_operand(mode)?;
+        self.add_to_a(value);
+        Ok(())
+    }
+    fn sbc(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        // the NES CPU has no decimal mode, so SBC is ADC of the complement
+        let value = self.read_operand(mode)?;
+        self.add_to_a(!value);
+        Ok(())
+    }
+    fn add_to_a(&mut self, value: u8) {
         let result = self.register_a as u16
             + value as u16
-            + (self.status & 0b0000_0001) as u16;
+            + self.status.contains(StatusFlags::CARRY) as u16;
 
-        if result > 0xFF {
-            self.status |= 0b0000_0001;
-        } else {
-            self.status &= 0b1111_1110;
-        }
+        self.status.set(StatusFlags::CARRY, result > 0xFF);
 
         let a = self.register_a;
         let result8 = (result & 0xFF) as u8;
-        if (a ^ value) & 0x80 == 0 && (a ^ result8) & 0x80 != 0 {
-            self.status |= 0b0100_0000;
-        } else {
-            self.status &= 0b1011_1111;
-        }
+        self.status.set(
+            StatusFlags::OVERFLOW,
+            (a ^ value) & 0x80 == 0 && (a ^ result8) & 0x80 != 0,
+        );
 
         self.register_a = result8;
         self.update_zero_and_negative_flags(self.register_a);
     }
-    fn and(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value = self.mem_read(addr);
-
-        self.register_a &= value;
+    fn and(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        self.register_a &= self.read_operand(mode)?;
         self.update_zero_and_negative_flags(self.register_a);
+        Ok(())
     }
-    fn asl(&mut self, mode: &AddressingMode) {
-        if let Some(addr) = self.get_operand_address(mode) {
-            let mut value = self.mem_read(addr);
-            self.status = (self.status & 0b1111_1110) | ((value >> 7) & 1);
-            value <<= 1;
-            self.mem_write(addr, value);
-            self.update_zero_and_negative_flags(value);
-        } else {
-            self.status = (self.status & 0b1111_1110) | ((self.register_a >> 7) & 1);
-            self.register_a <<= 1;
-            self.update_zero_and_negative_flags(self.register_a);
-        }
+    fn ora(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        self.register_a |= self.read_operand(mode)?;
+        self.update_zero_and_negative_flags(self.register_a);
+        Ok(())
     }
-    fn bcc(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
-
-        if self.status & 0b0000_0001 == 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16)
-        }
+    fn eor(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        self.register_a ^= self.read_operand(mode)?;
+        self.update_zero_and_negative_flags(self.register_a);
+        Ok(())
     }
-    fn bcs(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
-
-        if self.status & 0b0000_0001 != 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16);
-        }
+    fn asl(&mut self, value: u8) -> u8 {
+        self.status.set(StatusFlags::CARRY, value & 0b1000_0000 != 0);
+        let result = value << 1;
+        self.update_zero_and_negative_flags(result);
+        result
     }
-    fn beq(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
-
-        if self.status & 0b0000_0010 != 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16)
-        }
+    fn lsr(&mut self, value: u8) -> u8 {
+        self.status.set(StatusFlags::CARRY, value & 0b0000_0001 != 0);
+        let result = value >> 1;
+        self.update_zero_and_negative_flags(result);
+        result
     }
-    fn bit(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value = self.mem_read(addr);
-
-        let result = self.register_a & value;
-
-        if result == 0 {
-            self.status |= 0b0000_0010;
-        } else {
-            self.status &= 0b1111_1101;
-        }
-    
-        if value & 0b0100_0000 != 0 {
-            self.status |= 0b0100_0000;
-        } else {
-            self.status &= 0b1011_1111;
-        }
-    
-        if value & 0b1000_0000 != 0 {
-            self.status |= 0b1000_0000;
-        } else {
-            self.status &= 0b0111_1111;
-        }
+    fn rol(&mut self, value: u8) -> u8 {
+        let carry = self.status.contains(StatusFlags::CARRY) as u8;
+        self.status.set(StatusFlags::CARRY, value & 0b1000_0000 != 0);
+        let result = (value << 1) | carry;
+        self.update_zero_and_negative_flags(result);
+        result
     }
-    fn bmi(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
-
-        if self.status & 0b1000_0000 != 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16)
-        }
+    fn ror(&mut self, value: u8) -> u8 {
+        let carry = self.status.contains(StatusFlags::CARRY) as u8;
+        self.status.set(StatusFlags::CARRY, value & 0b0000_0001 != 0);
+        let result = (value >> 1) | (carry << 7);
+        self.update_zero_and_negative_flags(result);
+        result
     }
-    fn bne(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
-
-        if self.status & 0b0000_0010 == 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16)
-        }
+    fn inc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.update_zero_and_negative_flags(result);
+        result
     }
-    fn bpl(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
-
-        if self.status & 0b1000_0000 == 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16)
-        }
+    fn dec(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.update_zero_and_negative_flags(result);
+        result
     }
-    fn bvc(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
 
-        if self.status & 0b0100_0000 == 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16)
-        }
-    }
-    fn bvs(&mut self) {
-        let displacement: i8 = self.mem_read(self.program_counter) as i8;
-        self.program_counter += 1;
+    /// Relative branches take a cycle more when taken, and another when the
+    /// target is on a different page; both extra cycles re-read the
+    /// instruction stream.
+    fn branch(&mut self, condition: bool) {
+        let displacement: i8 = self.fetch() as i8;
 
-        if self.status & 0b0100_0000 != 0 {
-            self.program_counter = 
-                self.program_counter.wrapping_add(displacement as u16)
+        if condition {
+            self.dummy_read(self.program_counter);
+            let target = self.program_counter.wrapping_add(displacement as u16);
+            if target & 0xFF00 != self.program_counter & 0xFF00 {
+                self.dummy_read((self.program_counter & 0xFF00) | (target & 0x00FF));
+            }
+            self.program_counter = target;
         }
     }
-    fn cmp(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value = self.mem_read(addr);
-
-        let result = self.register_a.wrapping_sub(value);
+    fn bit(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        let value = self.read_operand(mode)?;
 
-        if self.register_a >= value {
-            self.status |= 0b0000_0001;
-        } else {
-            self.status &= 0b1111_1110;
-        }
+        let result = self.register_a & value;
 
-        self.update_zero_and_negative_flags(result);
+        self.status.set(StatusFlags::ZERO, result == 0);
+        self.status.set(StatusFlags::OVERFLOW, value & 0b0100_0000 != 0);
+        self.status.set(StatusFlags::NEGATIVE, value & 0b1000_0000 != 0);
+        Ok(())
     }
-    fn cpx(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value = self.mem_read(addr);
+    fn compare(&mut self, mode: &AddressingMode, register: u8) -> Result<(), Fault> {
+        let value = self.read_operand(mode)?;
 
-        let result = self.register_x.wrapping_sub(value);
+        let result = register.wrapping_sub(value);
 
-        if self.register_x >= value {
-            self.status |= 0b0000_0001;
-        } else {
-            self.status &= 0b1111_1110;
-        }
+        self.status.set(StatusFlags::CARRY, register >= value);
 
         self.update_zero_and_negative_flags(result);
+        Ok(())
     }
-    fn cpy(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value = self.mem_read(addr);
 
-        let result = self.register_y.wrapping_sub(value);
+    fn jmp(&mut self, mode: &AddressingMode) -> Result<(), Fault> {
+        match mode {
+            AddressingMode::Absolute => {
+                self.program_counter = self.fetch_u16();
+            },
+            AddressingMode::Indirect => {
+                let addr = self.fetch_u16();
+                let lo_byte = self.read(addr);
+                // the pointer's high byte is read without carrying into
+                // the page, so JMP ($10FF) reads $10FF and $1000
+                let hi_byte = self.read((addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF));
 
-        if self.register_y >= value {
-            self.status |= 0b0000_0001;
-        } else {
-            self.status &= 0b1111_1110;
+                let target: u16 = ((hi_byte as u16) << 8) | (lo_byte as u16);
+                self.program_counter = target;
+            }
+            _ => return Err(Fault::InvalidAddressingMode(mode.clone())),
         }
+        Ok(())
+    }
 
-        self.update_zero_and_negative_flags(result);
+    fn jsr(&mut self) {
+        let lo = self.fetch();
+        self.dummy_read(STACK + self.stack_pointer as u16);
+        // the return address pushed is that of the last byte of the JSR
+        self.stack_push_u16(self.program_counter);
+        let hi = self.read(self.program_counter);
+        self.program_counter = (hi as u16) << 8 | lo as u16;
     }
-    fn dec(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value = self.mem_read(addr);
-        let result = value.wrapping_sub(1);
-        self.mem_write(addr, result);
-        self.update_zero_and_negative_flags(result);
+    fn rts(&mut self) {
+        self.implied();
+        self.dummy_read(STACK + self.stack_pointer as u16);
+        self.program_counter = self.stack_pop_u16();
+        self.dummy_read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
     }
-    fn dex(&mut self) {
-        self.register_x = self.register_x.wrapping_sub(1);
-        self.update_zero_and_negative_flags(self.register_x);
+
+    fn rti(&mut self) {
+        self.implied();
+        self.dummy_read(STACK + self.stack_pointer as u16);
+        self.plp_value();
+        self.program_counter = self.stack_pop_u16();
     }
-    fn dey(&mut self) {
-        self.register_y = self.register_y.wrapping_sub(1);
-        self.update_zero_and_negative_flags(self.register_y);
+
+    fn pha(&mut self) {
+        self.implied();
+        self.stack_push(self.register_a);
     }
-    fn eor(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let value = self.mem_read(addr);
-        self.register_a ^= value;
-        self.update_zero_and_negative_flags(self.register_a);
+    fn php(&mut self) {
+        self.implied();
+        // B and the unused bit are set in the pushed copy only
+        let pushed = self.status | StatusFlags::BREAK | StatusFlags::UNUSED;
+        self.stack_push(pushed.bits());
     }
-    fn inc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode).unwrap();
-        let mut value = self.mem_read(addr);
-        value = value.wrapping_add(1);
-        self.mem_write(addr, value);
-        self.update_zero_and_negative_flags(value);
+    fn pla(&mut self) {
+        self.implied();
+        self.dummy_read(STACK + self.stack_pointer as u16);
+        self.register_a = self.stack_pop();
+        self.update_zero_and_negative_flags(self.register_a);
     }
-    fn iny(&mut self) {
-        self.register_y = self.register_y.wrapping_add(1);
-        self.update_zero_and_negative_flags(self.register_y); 
+    fn plp(&mut self) {
+        self.implied();
+        self.dummy_read(STACK + self.stack_pointer as u16);
+        self.plp_value();
+    }
+    fn plp_value(&mut self) {
+        // B and the unused bit do not exist in the register itself
+        let pulled = StatusFlags::from_bits_retain(self.stack_pop());
+        self.status = (pulled - StatusFlags::BREAK) | StatusFlags::UNUSED;
+    }
+
+    /// Hardware interrupt entry: pushes PC and P (with B clear), masks IRQs
+    /// and jumps through `vector`.
+    fn interrupt(&mut self, vector: u16) {
+        self.dummy_read(self.program_counter);
+        self.dummy_read(self.program_counter);
+        self.stack_push_u16(self.program_counter);
+        let pushed = (self.status - StatusFlags::BREAK) | StatusFlags::UNUSED;
+        self.stack_push(pushed.bits());
+        self.status.insert(StatusFlags::INTERRUPT_DISABLE);
+        let lo = self.read(vector);
+        let hi = self.read(vector.wrapping_add(1));
+        self.program_counter = (hi as u16) << 8 | lo as u16;
+    }
+
+    /// OAM DMA halts the CPU on what would have been its next read, waits
+    /// a cycle if it landed on a put cycle, then copies the page to $2004
+    /// one byte every two cycles: 513 or 514 cycles in all.
+    fn oam_dma(&mut self, page: u8) {
+        self.dummy_read(self.program_counter);
+        if !self.cycles.is_multiple_of(2) {
+            self.dummy_read(self.program_counter);
+        }
+
+        let base = (page as u16) << 8;
+        for i in 0..256 {
+            let data = self.bus.mem_read(base + i);
+            self.end_cycle();
+            self.bus.mem_write(OAM_DATA, data);
+            self.end_cycle();
+        }
     }
-    
-    fn jmp<'a>(&'a mut self, mode: &'a AddressingMode) {
-        match mode {
-            AddressingMode::Absolute => {
-                let target = self.get_operand_address(mode).unwrap();
-                self.program_counter = target;
-            },
-            AddressingMode::Indirect => {
-                let addr = self.mem_read_u16(self.program_counter);
-                let lo_byte = self.mem_read(addr);
-                let hi_byte = if addr & 0x00FF == 0xFF {
-                    self.mem_read(addr & 0xFF00)
-                } else {
-                    self.mem_read(addr.wrapping_add(1))
-                };
 
-                let target: u16 = ((hi_byte as u16) << 8) | (lo_byte as u16);
-                self.program_counter = target;
-            }
-            _ => panic!("invalid addressing mode for opcode JMP!")
+    /// Finishes a bus cycle: lets the rest of the console catch up and
+    /// samples the NMI line. An edge seen during one cycle is acted on
+    /// after the instruction that is still running a cycle later, so an NMI
+    /// arriving in an instruction's last cycle waits for the next one.
+    fn end_cycle(&mut self) {
+        self.cycles += 1;
+        self.bus.tick(1);
+        self.nmi_pending |= self.nmi_detected;
+        self.nmi_detected = self.bus.poll_nmi();
+    }
+
+    /// A cycle that reads `addr`.
+    fn read(&mut self, addr: u16) -> u8 {
+        let data = self.mem_read(addr);
+        self.end_cycle();
+        data
+    }
+    /// A cycle whose read only exists because of how the instruction is
+    /// sequenced; the value is thrown away but the read still reaches the
+    /// bus, with all its side effects on I/O registers.
+    fn dummy_read(&mut self, addr: u16) {
+        let data = self.bus.dummy_read(addr);
+        if self.watches.is_active() {
+            self.watches.notify(WatchKind::READ, addr, data, self.cycles);
         }
+        self.end_cycle();
+    }
+    /// A cycle that writes `data` to `addr`.
+    fn write(&mut self, addr: u16, data: u8) {
+        self.mem_write(addr, data);
+        self.end_cycle();
+    }
+    /// Reads the byte at PC and steps past it.
+    fn fetch(&mut self) -> u8 {
+        let data = self.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+        data
+    }
+    fn fetch_u16(&mut self) -> u16 {
+        let lo = self.fetch() as u16;
+        let hi = self.fetch() as u16;
+        (hi << 8) | lo
     }
 
+    fn stack_push(&mut self, data: u8) {
+        self.write(STACK + self.stack_pointer as u16, data);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    }
+    fn stack_pop(&mut self) -> u8 {
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        self.read(STACK + self.stack_pointer as u16)
+    }
+    fn stack_push_u16(&mut self, data: u16) {
+        self.stack_push((data >> 8) as u8);
+        self.stack_push((data & 0xff) as u8);
+    }
+    fn stack_pop_u16(&mut self) -> u16 {
+        let lo = self.stack_pop() as u16;
+        let hi = self.stack_pop() as u16;
+        (hi << 8) | lo
+    }
 
     fn update_zero_and_negative_flags(&mut self, result: u8) {
-        if result == 0 {
-            self.status |= 0b0000_0010;
-        } else {
-            self.status &= 0b1111_1101;
-        }
-
-        if result & 0b1000_0000 != 0 {
-            self.status |= 0b1000_0000;
-        } else {
-            self.status &= 0b0111_1111;
-        }
+        self.status.set(StatusFlags::ZERO, result == 0);
+        self.status.set(StatusFlags::NEGATIVE, result & 0b1000_0000 != 0);
     }
 
-    pub fn mem_read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+    /// Reads `addr` outside of any instruction, taking no time.
+    pub fn mem_read(&mut self, addr: u16) -> u8 {
+        let data = self.bus.mem_read(addr);
+        if self.watches.is_active() {
+            self.watches.notify(WatchKind::READ, addr, data, self.cycles);
+        }
+        data
     }
+    /// Writes `addr` outside of any instruction, taking no time.
     pub fn mem_write(&mut self, addr: u16, data: u8) {
-        self.memory[addr as usize] = data;
+        if self.watches.is_active() {
+            self.watches.notify(WatchKind::WRITE, addr, data, self.cycles);
+        }
+        self.bus.mem_write(addr, data);
     }
     fn mem_read_u16(&mut self, pos: u16) -> u16 {
         let lo = self.mem_read(pos) as u16;
         let hi = self.mem_read(pos.wrapping_add(1)) as u16;
         (hi << 8) | lo
     }
-    /* fn mem_write_u16(&mut self, pos: u16, data: u16) {
+    fn mem_write_u16(&mut self, pos: u16, data: u16) {
         let hi = (data >> 8) as u8;
         let lo = (data & 0xff) as u8;
         self.mem_write(pos, lo);
-        self.mem_write(pos + 1, hi);
-    } */
+        self.mem_write(pos.wrapping_add(1), hi);
+    }
 
-    pub fn reset(&mut self) {
+    /// Cold boot: clears the registers, fills work RAM with `pattern`, puts
+    /// the PPU and APU into their power-up state and then runs the reset
+    /// sequence, leaving SP at $FD and P at $34.
+    pub fn power_on(&mut self, pattern: RamPattern) {
         self.register_a = 0;
         self.register_x = 0;
-        self.status = 0;
+        self.register_y = 0;
+        self.status = StatusFlags::INTERRUPT_DISABLE | StatusFlags::BREAK | StatusFlags::UNUSED;
+        self.stack_pointer = 0x00;
+        self.cycles = 0;
 
-        self.program_counter = self.mem_read_u16(0xFFFC);
+        self.bus.power_on(pattern);
+        self.reset();
     }
-    pub fn load(&mut self, program: Vec<u8>) {
-        self.memory[0x8000 .. (0x8000 + program.len())].copy_from_slice(&program[..]);
-        self.program_counter = 0x8000;
+
+    /// Warm reset, as from the console's reset button. A, X, Y and the other
+    /// flags are left alone; the CPU performs three suppressed stack pushes,
+    /// masks interrupts and jumps through the reset vector at $FFFC.
+    pub fn reset(&mut self) {
+        self.bus.reset();
+        self.nmi_detected = false;
+        self.nmi_pending = false;
+
+        // the interrupt sequence, with the pushes turned into reads
+        self.dummy_read(self.program_counter);
+        self.dummy_read(self.program_counter);
+        for _ in 0..3 {
+            self.dummy_read(STACK + self.stack_pointer as u16);
+            self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+        }
+        self.status.insert(StatusFlags::INTERRUPT_DISABLE);
+        let lo = self.read(RESET_VECTOR);
+        let hi = self.read(RESET_VECTOR + 1);
+        self.program_counter = (hi as u16) << 8 | lo as u16;
     }
-    pub fn load_and_run(&mut self, program: Vec<u8>) -> Result<(), CPUError> {
-        self.load(program);
-        self.run()?;
+
+    pub fn run(&mut self) -> Result<(), CPUError> {
+        while self.step()? {}
         Ok(())
     }
 
-    pub fn run(&mut self) -> Result<(), CPUError> {
-        loop {
-            let code = self.mem_read(self.program_counter);
-            self.program_counter += 1;
-
-            let operation = CPU_OPCODES
-                .iter()
-                .find(|op| op.opcode == code);
-
-            if let Some(opcode) = operation {
-                match opcode.name {
-                    "LDA" => self.lda(&opcode.addressing_mode),
-                    "STA" => self.sta(&opcode.addressing_mode),
-                    "ADC" => self.adc(&opcode.addressing_mode),
-                    "AND" => self.and(&opcode.addressing_mode),
-                    "ASL" => self.asl(&opcode.addressing_mode),
-                    "BCC" => self.bcc(),
-                    "BCS" => self.bcs(),
-                    "BEQ" => self.beq(),
-                    "BIT" => self.bit(&opcode.addressing_mode),
-                    "BMI" => self.bmi(),
-                    "BNE" => self.bne(),
-                    "BPL" => self.bpl(),
-                    "BRK" => return Ok(()),
-                    "BVC" => self.bvc(),
-                    "BVS" => self.bvs(),
-                    "CLC" => self.status &= 0b1111_1110,
-                    "CLD" => self.status &= 0b1111_0111,
-                    "CLI" => self.status &= 0b1111_1011,
-                    "CLV" => self.status &= 0b1011_1111,
-                    "CMP" => self.cmp(&opcode.addressing_mode),
-                    "CPX" => self.cpx(&opcode.addressing_mode),
-                    "CPY" => self.cpy(&opcode.addressing_mode),
-                    "DEC" => self.dec(&opcode.addressing_mode),
-                    "DEX" => self.dex(),
-                    "DEY" => self.dey(),
-                    "EOR" => self.eor(&opcode.addressing_mode),
-                    "INC" => self.inc(&opcode.addressing_mode),
-                    "JMP" => self.jmp(&opcode.addressing_mode),
-                    "TAX" => self.tax(),
-                    "INX" => self.inx(),
-                    "INY" => self.iny(),
-                    _ => return Err(CPUError::UnimplementedInstruction(opcode.name.to_string())),
-                }
-                if opcode.addressing_mode != AddressingMode::Relative && opcode.name != "JMP" {
-                    self.program_counter += (opcode.bytes - 1) as u16;
-                }
-            } else {
-                return Err(CPUError::UnknownOpcode(code));
+    /// Runs until the bus reports that a new frame has begun. Returns
+    /// `Ok(false)` if a BRK stopped the program first. Buses without a video
+    /// device never finish a frame, so this then runs until BRK.
+    pub fn run_frame(&mut self) -> Result<bool, CPUError> {
+        let frame = self.bus.frame();
+        while self.bus.frame() == frame {
+            if !self.step()? {
+                return Ok(false);
             }
         }
+        Ok(true)
+    }
+
+    /// Executes one instruction, or enters an NMI that arrived in time to
+    /// be taken after the previous one. Returns `Ok(false)` once a BRK has
+    /// stopped the program.
+    pub fn step(&mut self) -> Result<bool, CPUError> {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            self.watches.pc = self.program_counter;
+            self.interrupt(NMI_VECTOR);
+            return Ok(true);
+        }
+
+        let start = self.program_counter;
+        let start_cycles = self.cycles;
+        let code = self.bus.fetch_opcode(start);
+        if self.watches.is_active() {
+            self.watches.pc = start;
+            self.watches.notify(WatchKind::EXECUTE, start, code, self.cycles);
+        }
+        self.end_cycle();
+        self.program_counter = self.program_counter.wrapping_add(1);
+
+        let opcode = opcode::lookup(code)
+            .ok_or_else(|| CPUError::UnknownOpcode(self.state(start, code, start_cycles)))?;
+
+        if opcode.name == "BRK" {
+            // BRK stops the emulator instead of entering the IRQ handler;
+            // the cycles it would have spent are still counted
+            for _ in 1..opcode.cycles {
+                self.end_cycle();
+            }
+            return Ok(false);
+        }
+
+        self.execute(opcode).map_err(|fault| match fault {
+            Fault::Unimplemented =>
+                CPUError::UnimplementedInstruction(opcode.name.to_string(), self.state(start, code, start_cycles)),
+            Fault::InvalidAddressingMode(mode) =>
+                CPUError::InvalidAddressingMode(mode, self.state(start, code, start_cycles)),
+        })?;
+
+        // every write to $4014 is the last cycle of its instruction, so the
+        // DMA's halt always falls on the next opcode fetch
+        if let Some(page) = self.bus.take_oam_dma() {
+            self.oam_dma(page);
+        }
+        Ok(true)
+    }
+
+    fn execute(&mut self, opcode: &OpCode) -> Result<(), Fault> {
+        let mode = &opcode.addressing_mode;
+        match opcode.name {
+            "LDA" => self.lda(mode)?,
+            "LDX" => self.ldx(mode)?,
+            "LDY" => self.ldy(mode)?,
+            "STA" => self.store(mode, self.register_a)?,
+            "STX" => self.store(mode, self.register_x)?,
+            "STY" => self.store(mode, self.register_y)?,
+            "ADC" => self.adc(mode)?,
+            "SBC" => self.sbc(mode)?,
+            "AND" => self.and(mode)?,
+            "ORA" => self.ora(mode)?,
+            "EOR" => self.eor(mode)?,
+            "ASL" => self.modify(mode, Self::asl)?,
+            "LSR" => self.modify(mode, Self::lsr)?,
+            "ROL" => self.modify(mode, Self::rol)?,
+            "ROR" => self.modify(mode, Self::ror)?,
+            "INC" => self.modify(mode, Self::inc)?,
+            "DEC" => self.modify(mode, Self::dec)?,
+            "BCC" => self.branch(!self.status.contains(StatusFlags::CARRY)),
+            "BCS" => self.branch(self.status.contains(StatusFlags::CARRY)),
+            "BEQ" => self.branch(self.status.contains(StatusFlags::ZERO)),
+            "BMI" => self.branch(self.status.contains(StatusFlags::NEGATIVE)),
+            "BNE" => self.branch(!self.status.contains(StatusFlags::ZERO)),
+            "BPL" => self.branch(!self.status.contains(StatusFlags::NEGATIVE)),
+            "BVC" => self.branch(!self.status.contains(StatusFlags::OVERFLOW)),
+            "BVS" => self.branch(self.status.contains(StatusFlags::OVERFLOW)),
+            "BIT" => self.bit(mode)?,
+            "CMP" => self.compare(mode, self.register_a)?,
+            "CPX" => self.compare(mode, self.register_x)?,
+            "CPY" => self.compare(mode, self.register_y)?,
+            "CLC" => { self.implied(); self.status.clear(StatusFlags::CARRY) }
+            "CLD" => { self.implied(); self.status.clear(StatusFlags::DECIMAL) }
+            "CLI" => { self.implied(); self.status.clear(StatusFlags::INTERRUPT_DISABLE) }
+            "CLV" => { self.implied(); self.status.clear(StatusFlags::OVERFLOW) }
+            "SEC" => { self.implied(); self.status.insert(StatusFlags::CARRY) }
+            "SED" => { self.implied(); self.status.insert(StatusFlags::DECIMAL) }
+            "SEI" => { self.implied(); self.status.insert(StatusFlags::INTERRUPT_DISABLE) }
+            "DEX" => self.register_x = self.transfer(self.register_x.wrapping_sub(1)),
+            "DEY" => self.register_y = self.transfer(self.register_y.wrapping_sub(1)),
+            "INX" => self.register_x = self.transfer(self.register_x.wrapping_add(1)),
+            "INY" => self.register_y = self.transfer(self.register_y.wrapping_add(1)),
+            "TAX" => self.register_x = self.transfer(self.register_a),
+            "TAY" => self.register_y = self.transfer(self.register_a),
+            "TXA" => self.register_a = self.transfer(self.register_x),
+            "TYA" => self.register_a = self.transfer(self.register_y),
+            "TSX" => self.register_x = self.transfer(self.stack_pointer),
+            // the one transfer that leaves the flags alone
+            "TXS" => { self.implied(); self.stack_pointer = self.register_x }
+            "NOP" => self.implied(),
+            "PHA" => self.pha(),
+            "PHP" => self.php(),
+            "PLA" => self.pla(),
+            "PLP" => self.plp(),
+            "JMP" => self.jmp(mode)?,
+            "JSR" => self.jsr(),
+            "RTS" => self.rts(),
+            "RTI" => self.rti(),
+            _ => return Err(Fault::Unimplemented),
+        }
+        Ok(())
+    }
+
+    fn state(&self, program_counter: u16, opcode: u8, cycles: u64) -> CPUState {
+        CPUState {
+            program_counter,
+            opcode,
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            status: self.status,
+            stack_pointer: self.stack_pointer,
+            cycles,
+        }
     }
 }
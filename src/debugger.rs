@@ -0,0 +1,673 @@
+use std::{fmt, fs};
+use crate::{
+    bus::Bus,
+    compare::{Comparison, OPERATORS},
+    cpu::{AddressingMode, CPU},
+    disasm::{self, Instruction},
+    error::CPUError,
+    flags::StatusFlags,
+    ramsearch::{Filter, Operand, RamSearch},
+};
+
+const HELP: &str = "\
+step|s [n]                        execute n instructions, entering subroutines
+next|n                            execute one instruction, stepping over JSR
+continue|c [n]                    run until a breakpoint, BRK, an error or n instructions
+break|b <addr> [if <cond>]        break when PC reaches addr
+break op <mnemonic|byte> [if ..]  break before an opcode executes
+break if <cond>                   break as soon as a register condition holds
+watch|w r|w|rw <addr>[-<end>]     break before a read and/or write of memory
+breakpoints|bl                    list breakpoints
+delete|d <id>                     remove a breakpoint
+regs|r                            show registers
+set <A|X|Y|SP|PC|P> <value>       change a register
+mem|m <addr> [len]                dump memory
+poke <addr> <byte>...             write memory
+dis|u [addr] [count]              disassemble (around PC by default)
+stack|bt                          show the JSR/RTS call stack
+search new [prg]                  start a RAM search over work RAM (and PRG-RAM)
+search <op> <value|prev[+-n]>     keep addresses whose value now <op> the operand
+search update|list                rebaseline without filtering, or list candidates
+save <file>                       write a save state
+load <file>                       restore a save state
+quit|q                            leave the debugger
+conditions compare a register with a value, e.g. `X == $10 && A >= 3`";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    P,
+}
+
+impl Register {
+    fn parse(text: &str) -> Option<Register> {
+        match text.to_ascii_uppercase().as_str() {
+            "A" => Some(Register::A),
+            "X" => Some(Register::X),
+            "Y" => Some(Register::Y),
+            "SP" | "S" => Some(Register::SP),
+            "PC" => Some(Register::PC),
+            "P" => Some(Register::P),
+            _ => None,
+        }
+    }
+
+    fn read<B: Bus>(&self, cpu: &CPU<B>) -> u16 {
+        match self {
+            Register::A => cpu.register_a as u16,
+            Register::X => cpu.register_x as u16,
+            Register::Y => cpu.register_y as u16,
+            Register::SP => cpu.stack_pointer as u16,
+            Register::PC => cpu.program_counter,
+            Register::P => cpu.status.bits() as u16,
+        }
+    }
+
+    fn write<B: Bus>(&self, cpu: &mut CPU<B>, value: u16) {
+        match self {
+            Register::A => cpu.register_a = value as u8,
+            Register::X => cpu.register_x = value as u8,
+            Register::Y => cpu.register_y = value as u8,
+            Register::SP => cpu.stack_pointer = value as u8,
+            Register::PC => cpu.program_counter = value,
+            Register::P => cpu.status = StatusFlags::from_bits_retain(value as u8),
+        }
+    }
+}
+
+/// A register comparison such as `X == $10`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn holds<B: Bus>(&self, cpu: &CPU<B>) -> bool {
+        self.comparison.test(self.register.read(cpu), self.value)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {} ${:02X}", self.register, self.comparison.symbol(), self.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakpointKind {
+    /// PC reaches an address.
+    Address(u16),
+    /// An opcode byte is about to execute.
+    Opcode(u8),
+    /// Any opcode with this mnemonic is about to execute.
+    Mnemonic(String),
+    /// The next instruction reads from the inclusive range.
+    Read(u16, u16),
+    /// The next instruction writes to the inclusive range.
+    Write(u16, u16),
+    /// Checked before every instruction; only the conditions matter.
+    Always,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub kind: BreakpointKind,
+    /// All of these must hold for the breakpoint to fire.
+    pub conditions: Vec<Condition>,
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} ", self.id)?;
+        match &self.kind {
+            BreakpointKind::Address(addr) => write!(f, "pc ${:04X}", addr)?,
+            BreakpointKind::Opcode(code) => write!(f, "opcode ${:02X}", code)?,
+            BreakpointKind::Mnemonic(name) => write!(f, "opcode {}", name)?,
+            BreakpointKind::Read(start, end) => write!(f, "read ${:04X}-${:04X}", start, end)?,
+            BreakpointKind::Write(start, end) => write!(f, "write ${:04X}-${:04X}", start, end)?,
+            BreakpointKind::Always => write!(f, "always")?,
+        }
+        for (i, condition) in self.conditions.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " if " } else { " && " }, condition)?;
+        }
+        Ok(())
+    }
+}
+
+/// One JSR that has not returned yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub call_site: u16,
+    pub target: u16,
+    pub return_address: u16,
+}
+
+#[derive(Debug)]
+pub enum StopReason {
+    Stepped,
+    Breakpoint(usize),
+    Halted,
+    Limit,
+    Error(CPUError),
+}
+
+pub enum Response {
+    Output(String),
+    Quit,
+}
+
+/// Breakpoints and call-stack tracking on top of `CPU::step`.
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    next_id: usize,
+    call_stack: Vec<Frame>,
+    search: Option<RamSearch>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: Vec::new(),
+            next_id: 1,
+            call_stack: Vec::new(),
+            search: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, kind: BreakpointKind, conditions: Vec<Condition>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint { id, kind, conditions });
+        id
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|bp| bp.id != id);
+        self.breakpoints.len() != before
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Subroutine calls made while stepping under the debugger, innermost last.
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
+    /// The breakpoint that fires before the instruction at PC, if any.
+    pub fn breakpoint_hit<B: Bus>(&self, cpu: &CPU<B>) -> Option<usize> {
+        let instruction = disasm::decode(cpu.program_counter, |addr| cpu.bus.peek(addr));
+        let access = memory_access(cpu, &instruction);
+
+        self.breakpoints.iter().find(|bp| {
+            let triggered = match &bp.kind {
+                BreakpointKind::Address(addr) => cpu.program_counter == *addr,
+                BreakpointKind::Opcode(code) => instruction.bytes[0] == *code,
+                BreakpointKind::Mnemonic(name) => instruction.mnemonic() == name,
+                BreakpointKind::Read(start, end) =>
+                    matches!(access, Some((addr, true, _)) if (*start..=*end).contains(&addr)),
+                BreakpointKind::Write(start, end) =>
+                    matches!(access, Some((addr, _, true)) if (*start..=*end).contains(&addr)),
+                BreakpointKind::Always => true,
+            };
+            triggered && bp.conditions.iter().all(|condition| condition.holds(cpu))
+        }).map(|bp| bp.id)
+    }
+
+    /// Executes one instruction, keeping the call stack up to date.
+    pub fn step<B: Bus>(&mut self, cpu: &mut CPU<B>) -> Result<bool, CPUError> {
+        let instruction = disasm::decode(cpu.program_counter, |addr| cpu.bus.peek(addr));
+        let running = cpu.step()?;
+
+        match instruction.mnemonic() {
+            "JSR" => self.call_stack.push(Frame {
+                call_site: instruction.address,
+                target: cpu.program_counter,
+                return_address: instruction.address.wrapping_add(instruction.len()),
+            }),
+            "RTS" => {
+                self.call_stack.pop();
+            }
+            _ => {}
+        }
+        Ok(running)
+    }
+
+    /// Runs until a breakpoint fires, the program halts or fails, or `limit`
+    /// instructions have executed. A breakpoint at the current PC does not
+    /// stop the first instruction, so that continuing from it makes progress.
+    pub fn resume<B: Bus>(&mut self, cpu: &mut CPU<B>, limit: Option<u64>) -> StopReason {
+        self.run_until(cpu, limit, |_, _| false)
+    }
+
+    /// Executes one instruction, or a whole subroutine if it is a JSR.
+    pub fn next<B: Bus>(&mut self, cpu: &mut CPU<B>) -> StopReason {
+        let instruction = disasm::decode(cpu.program_counter, |addr| cpu.bus.peek(addr));
+        if instruction.mnemonic() != "JSR" {
+            return match self.step(cpu) {
+                Ok(true) => StopReason::Stepped,
+                Ok(false) => StopReason::Halted,
+                Err(err) => StopReason::Error(err),
+            };
+        }
+
+        let depth = self.call_stack.len();
+        let return_address = instruction.address.wrapping_add(instruction.len());
+        self.run_until(cpu, None, |debugger, cpu| {
+            debugger.call_stack.len() == depth && cpu.program_counter == return_address
+        })
+    }
+
+    fn run_until<B: Bus>(
+        &mut self,
+        cpu: &mut CPU<B>,
+        limit: Option<u64>,
+        done: impl Fn(&Debugger, &CPU<B>) -> bool,
+    ) -> StopReason {
+        let mut executed = 0;
+        loop {
+            if executed > 0 {
+                if done(self, cpu) {
+                    return StopReason::Stepped;
+                }
+                if let Some(id) = self.breakpoint_hit(cpu) {
+                    return StopReason::Breakpoint(id);
+                }
+            }
+            if limit.is_some_and(|limit| executed >= limit) {
+                return StopReason::Limit;
+            }
+
+            match self.step(cpu) {
+                Ok(true) => executed += 1,
+                Ok(false) => return StopReason::Halted,
+                Err(err) => return StopReason::Error(err),
+            }
+        }
+    }
+
+    /// Runs one line of debugger command input.
+    pub fn execute<B: Bus>(&mut self, cpu: &mut CPU<B>, line: &str) -> Response {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return Response::Output(String::new());
+        };
+
+        let output = match command {
+            "quit" | "q" => return Response::Quit,
+            "help" | "h" | "?" => Ok(HELP.to_string()),
+            "step" | "s" => self.command_step(cpu, args),
+            "next" | "n" => {
+                let reason = self.next(cpu);
+                Ok(self.describe_stop(cpu, &reason))
+            }
+            "continue" | "c" => self.command_continue(cpu, args),
+            "break" | "b" => self.command_break(args),
+            "watch" | "w" => self.command_watch(args),
+            "breakpoints" | "bl" => Ok(self.breakpoints.iter()
+                .map(|bp| bp.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "delete" | "d" => match args.first().and_then(|id| id.parse().ok()) {
+                Some(id) if self.remove_breakpoint(id) => Ok(format!("deleted #{}", id)),
+                _ => Err("usage: delete <id>".to_string()),
+            },
+            "regs" | "r" => Ok(registers(cpu)),
+            "set" => command_set(cpu, args),
+            "mem" | "m" => command_mem(cpu, args),
+            "poke" => command_poke(cpu, args),
+            "dis" | "u" => command_dis(cpu, args),
+            "stack" | "bt" => Ok(self.format_call_stack(cpu)),
+            "search" => self.command_search(cpu, args),
+            "save" => match args.first() {
+                Some(path) => fs::write(path, cpu.save_state())
+                    .map(|_| format!("state saved to {}", path))
+                    .map_err(|err| err.to_string()),
+                None => Err("usage: save <file>".to_string()),
+            },
+            "load" => match args.first() {
+                Some(path) => fs::read(path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|state| cpu.load_state(&state).map_err(|err| err.to_string()))
+                    .map(|_| {
+                        // the shadow call stack belongs to the abandoned timeline
+                        self.call_stack.clear();
+                        registers(cpu)
+                    }),
+                None => Err("usage: load <file>".to_string()),
+            },
+            _ => Err(format!("unknown command {:?}, try `help`", command)),
+        };
+
+        Response::Output(output.unwrap_or_else(|err| format!("error: {}", err)))
+    }
+
+    fn command_step<B: Bus>(&mut self, cpu: &mut CPU<B>, args: &[&str]) -> Result<String, String> {
+        let count = match args.first() {
+            Some(count) => parse_number(count)? as u64,
+            None => 1,
+        };
+
+        // like `continue`, a breakpoint at the starting PC does not stop the
+        // first step, but one reached on the way does
+        let mut reason = StopReason::Stepped;
+        for i in 0..count {
+            if i > 0 {
+                if let Some(id) = self.breakpoint_hit(cpu) {
+                    reason = StopReason::Breakpoint(id);
+                    break;
+                }
+            }
+            reason = match self.step(cpu) {
+                Ok(true) => StopReason::Stepped,
+                Ok(false) => StopReason::Halted,
+                Err(err) => StopReason::Error(err),
+            };
+            if !matches!(reason, StopReason::Stepped) {
+                break;
+            }
+        }
+        Ok(self.describe_stop(cpu, &reason))
+    }
+
+    fn command_continue<B: Bus>(&mut self, cpu: &mut CPU<B>, args: &[&str]) -> Result<String, String> {
+        let limit = match args.first() {
+            Some(limit) => Some(parse_number(limit)? as u64),
+            None => None,
+        };
+        let reason = self.resume(cpu, limit);
+        Ok(self.describe_stop(cpu, &reason))
+    }
+
+    fn command_break(&mut self, args: &[&str]) -> Result<String, String> {
+        let (target, conditions) = split_conditions(args)?;
+        let kind = match target.as_slice() {
+            [] if !conditions.is_empty() => BreakpointKind::Always,
+            ["op", code] => match parse_number(code) {
+                Ok(code) => BreakpointKind::Opcode(code as u8),
+                Err(_) => BreakpointKind::Mnemonic(code.to_ascii_uppercase()),
+            },
+            [addr] => BreakpointKind::Address(parse_number(addr)?),
+            _ => return Err("usage: break <addr> | break op <opcode> | break if <cond>".to_string()),
+        };
+
+        let id = self.add_breakpoint(kind, conditions);
+        Ok(format!("breakpoint #{} set", id))
+    }
+
+    fn command_watch(&mut self, args: &[&str]) -> Result<String, String> {
+        let usage = || "usage: watch r|w|rw <addr>[-<end>] [if <cond>]".to_string();
+        let (target, conditions) = split_conditions(args)?;
+        let [access, range] = target.as_slice() else {
+            return Err(usage());
+        };
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_number(start)?, parse_number(end)?),
+            None => (parse_number(range)?, parse_number(range)?),
+        };
+
+        let kinds = match *access {
+            "r" => vec![BreakpointKind::Read(start, end)],
+            "w" => vec![BreakpointKind::Write(start, end)],
+            "rw" => vec![BreakpointKind::Read(start, end), BreakpointKind::Write(start, end)],
+            _ => return Err(usage()),
+        };
+
+        let ids: Vec<String> = kinds.into_iter()
+            .map(|kind| format!("#{}", self.add_breakpoint(kind, conditions.clone())))
+            .collect();
+        Ok(format!("watchpoint {} set", ids.join(", ")))
+    }
+
+    fn command_search<B: Bus>(&mut self, cpu: &CPU<B>, args: &[&str]) -> Result<String, String> {
+        const USAGE: &str = "usage: search new [prg] | search <op> <value|prev[+-n]> | search update | search list";
+        const LISTED: usize = 16;
+
+        match args {
+            ["new"] | ["new", "prg"] => {
+                let search = RamSearch::new(&cpu.bus, args.len() == 2);
+                let count = search.len();
+                self.search = Some(search);
+                return Ok(format!("{} candidates", count));
+            }
+            [] | ["new", ..] => return Err(USAGE.to_string()),
+            _ => {}
+        }
+
+        let search = self.search.as_mut().ok_or("no search in progress, start one with `search new`")?;
+        match args {
+            ["update"] => search.update(&cpu.bus),
+            ["list"] => {}
+            [op, operand] => {
+                let comparison = Comparison::parse(op).ok_or_else(|| format!("unknown operator {:?}", op))?;
+                search.filter(&cpu.bus, Filter::new(comparison, parse_operand(operand)?));
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+
+        let mut lines = vec![format!("{} candidates", search.len())];
+        lines.extend(search.candidates().iter().take(LISTED)
+            .map(|candidate| format!("  ${:04X}  ${:02X}", candidate.address, candidate.value)));
+        if search.len() > LISTED {
+            lines.push(format!("  ... {} more", search.len() - LISTED));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn describe_stop<B: Bus>(&self, cpu: &CPU<B>, reason: &StopReason) -> String {
+        let prefix = match reason {
+            StopReason::Stepped | StopReason::Limit => String::new(),
+            StopReason::Breakpoint(id) => format!("breakpoint #{} hit\n", id),
+            StopReason::Halted => "BRK: program halted\n".to_string(),
+            StopReason::Error(err) => format!("{}\n", err),
+        };
+        let instruction = disasm::decode(cpu.program_counter, |addr| cpu.bus.peek(addr));
+        format!("{}{:<32}{}", prefix, instruction.listing(), registers(cpu))
+    }
+
+    fn format_call_stack<B: Bus>(&self, cpu: &CPU<B>) -> String {
+        let mut lines = vec![format!("#0  ${:04X}", cpu.program_counter)];
+        for (depth, frame) in self.call_stack.iter().rev().enumerate() {
+            lines.push(format!(
+                "#{}  ${:04X}  JSR ${:04X}, returns to ${:04X}",
+                depth + 1, frame.call_site, frame.target, frame.return_address,
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Disassembles `before` instructions leading up to `pc` and `after` from it.
+/// Code cannot be decoded backwards reliably, so this picks the earliest
+/// starting point whose instruction stream lands exactly on `pc`.
+pub fn disassemble_around<B: Bus>(bus: &B, pc: u16, before: usize, after: usize) -> Vec<Instruction> {
+    let mut leading = Vec::new();
+    for distance in (1..=(before * 3) as u16).rev() {
+        let start = pc.wrapping_sub(distance);
+        let decoded = disasm::disassemble_range(bus, start, pc.wrapping_sub(1));
+        let end = decoded.last().map(|i| i.address.wrapping_add(i.len()));
+        if end == Some(pc) {
+            leading = decoded;
+            break;
+        }
+    }
+
+    let skip = leading.len().saturating_sub(before);
+    let mut instructions: Vec<Instruction> = leading.into_iter().skip(skip).collect();
+    instructions.extend(disasm::disassemble_count(bus, pc, after));
+    instructions
+}
+
+/// The address an instruction is about to touch, with whether it reads
+/// and/or writes it. Indirect pointers are followed with `Bus::peek`.
+fn memory_access<B: Bus>(cpu: &CPU<B>, instruction: &Instruction) -> Option<(u16, bool, bool)> {
+    let (reads, writes) = match instruction.mnemonic() {
+        "LDA" | "LDX" | "LDY" | "ADC" | "SBC" | "AND" | "ORA" | "EOR"
+            | "CMP" | "CPX" | "CPY" | "BIT" => (true, false),
+        "STA" | "STX" | "STY" => (false, true),
+        "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC" => (true, true),
+        _ => return None,
+    };
+
+    let operand = instruction.operand()?;
+    let peek_word = |lo: u16, hi: u16| u16::from_le_bytes([cpu.bus.peek(lo), cpu.bus.peek(hi)]);
+    let addr = match instruction.addressing_mode() {
+        AddressingMode::ZeroPage | AddressingMode::Absolute => operand,
+        AddressingMode::ZeroPageX => (operand as u8).wrapping_add(cpu.register_x) as u16,
+        AddressingMode::ZeroPageY => (operand as u8).wrapping_add(cpu.register_y) as u16,
+        AddressingMode::AbsoluteX => operand.wrapping_add(cpu.register_x as u16),
+        AddressingMode::AbsoluteY => operand.wrapping_add(cpu.register_y as u16),
+        AddressingMode::IndirectX => {
+            let ptr = (operand as u8).wrapping_add(cpu.register_x);
+            peek_word(ptr as u16, ptr.wrapping_add(1) as u16)
+        }
+        AddressingMode::IndirectY => {
+            let ptr = operand as u8;
+            peek_word(ptr as u16, ptr.wrapping_add(1) as u16).wrapping_add(cpu.register_y as u16)
+        }
+        _ => return None,
+    };
+    Some((addr, reads, writes))
+}
+
+fn registers<B: Bus>(cpu: &CPU<B>) -> String {
+    format!(
+        "A:{:02X} X:{:02X} Y:{:02X} P:{} SP:{:02X} PC:{:04X} CYC:{}",
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status,
+        cpu.stack_pointer,
+        cpu.program_counter,
+        cpu.cycles,
+    )
+}
+
+fn command_set<B: Bus>(cpu: &mut CPU<B>, args: &[&str]) -> Result<String, String> {
+    let [register, value] = args else {
+        return Err("usage: set <A|X|Y|SP|PC|P> <value>".to_string());
+    };
+    let register = Register::parse(register).ok_or(format!("unknown register {}", register))?;
+    register.write(cpu, parse_number(value)?);
+    Ok(registers(cpu))
+}
+
+fn command_mem<B: Bus>(cpu: &CPU<B>, args: &[&str]) -> Result<String, String> {
+    let start = parse_number(args.first().ok_or("usage: mem <addr> [len]")?)?;
+    let len = match args.get(1) {
+        Some(len) => parse_number(len)?,
+        None => 64,
+    };
+
+    let mut lines = Vec::new();
+    for row in (0..len as u32).step_by(16) {
+        let addr = start.wrapping_add(row as u16);
+        let count = (len as u32 - row).min(16) as u16;
+        let bytes: Vec<u8> = (0..count).map(|i| cpu.bus.peek(addr.wrapping_add(i))).collect();
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let text: String = bytes.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        lines.push(format!("{:04X}: {:<47}  {}", addr, hex.join(" "), text));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn command_poke<B: Bus>(cpu: &mut CPU<B>, args: &[&str]) -> Result<String, String> {
+    let Some((addr, bytes)) = args.split_first() else {
+        return Err("usage: poke <addr> <byte>...".to_string());
+    };
+    let addr = parse_number(addr)?;
+    for (i, byte) in bytes.iter().enumerate() {
+        cpu.bus.mem_write(addr.wrapping_add(i as u16), parse_number(byte)? as u8);
+    }
+    Ok(format!("wrote {} byte(s) at ${:04X}", bytes.len(), addr))
+}
+
+fn command_dis<B: Bus>(cpu: &CPU<B>, args: &[&str]) -> Result<String, String> {
+    let instructions = match args {
+        [] => disassemble_around(&cpu.bus, cpu.program_counter, 4, 8),
+        [addr] => disasm::disassemble_count(&cpu.bus, parse_number(addr)?, 12),
+        [addr, count, ..] => disasm::disassemble_count(&cpu.bus, parse_number(addr)?, parse_number(count)? as usize),
+    };
+
+    Ok(instructions.iter()
+        .map(|i| {
+            let marker = if i.address == cpu.program_counter { '>' } else { ' ' };
+            format!("{} {}", marker, i.listing())
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Splits `<target...> if <cond> && <cond>` into the target words and the
+/// parsed conditions.
+fn split_conditions<'a>(args: &[&'a str]) -> Result<(Vec<&'a str>, Vec<Condition>), String> {
+    let Some(split) = args.iter().position(|&word| word == "if") else {
+        return Ok((args.to_vec(), Vec::new()));
+    };
+
+    let text = args[split + 1..].join(" ");
+    let conditions = text.split("&&")
+        .map(|condition| parse_condition(condition.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((args[..split].to_vec(), conditions))
+}
+
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    for (op, comparison) in OPERATORS {
+        if let Some((register, value)) = text.split_once(op) {
+            let register = Register::parse(register.trim())
+                .ok_or(format!("unknown register {:?}", register.trim()))?;
+            return Ok(Condition { register, comparison, value: parse_number(value.trim())? });
+        }
+    }
+    Err(format!("cannot parse condition {:?}", text))
+}
+
+/// Parses a RAM search operand: `prev`, `prev+2`, `prev-1` or a number.
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let Some(delta) = text.strip_prefix("prev") else {
+        let value = parse_number(text)?;
+        return u8::try_from(value).map(Operand::Value).map_err(|_| format!("{} is not a byte", text));
+    };
+    let (sign, amount) = match delta.split_at_checked(1) {
+        None => return Ok(Operand::Previous),
+        Some(("+", amount)) => (1, amount),
+        Some(("-", amount)) => (-1, amount),
+        Some(_) => return Err(format!("cannot parse operand {:?}", text)),
+    };
+    let amount = parse_number(amount)?;
+    if amount > 0xFF {
+        return Err(format!("{} is not a byte", amount));
+    }
+    Ok(Operand::Delta(sign * amount as i16))
+}
+
+/// Parses `$1F`, `0x1F`, `%11111` or `31`.
+pub fn parse_number(text: &str) -> Result<u16, String> {
+    let parsed = if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        u16::from_str_radix(hex, 16)
+    } else if let Some(bin) = text.strip_prefix('%') {
+        u16::from_str_radix(bin, 2)
+    } else {
+        text.parse()
+    };
+    parsed.map_err(|_| format!("invalid number {:?}", text))
+}
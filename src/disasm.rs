@@ -0,0 +1,196 @@
+use std::fmt;
+use crate::{
+    bus::Bus,
+    cdl::{CodeDataLogger, PrgFlags},
+    cpu::AddressingMode,
+    opcode::{self, OpCode},
+};
+
+/// One decoded instruction. Bytes that are not an official opcode decode to
+/// a single-byte record with no `opcode`, shown as `.byte $XX`.
+#[derive(Clone)]
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub opcode: Option<&'static OpCode<'static>>,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        self.opcode.map_or(".byte", |op| op.name)
+    }
+
+    pub fn addressing_mode(&self) -> AddressingMode {
+        self.opcode.map_or(AddressingMode::NoneAddressing, |op| op.addressing_mode.clone())
+    }
+
+    pub fn len(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The raw operand: a byte for zero page, immediate and relative modes,
+    /// a little-endian word for absolute and indirect ones.
+    pub fn operand(&self) -> Option<u16> {
+        match self.bytes.len() {
+            2 => Some(self.bytes[1] as u16),
+            3 => Some(u16::from_le_bytes([self.bytes[1], self.bytes[2]])),
+            _ => None,
+        }
+    }
+
+    /// The address control passes to for branches, `JMP abs` and `JSR`.
+    pub fn target(&self) -> Option<u16> {
+        let operand = self.operand()?;
+        match self.addressing_mode() {
+            AddressingMode::Relative => {
+                let next = self.address.wrapping_add(self.len());
+                Some(next.wrapping_add(operand as u8 as i8 as u16))
+            }
+            AddressingMode::Absolute if matches!(self.mnemonic(), "JMP" | "JSR") => Some(operand),
+            _ => None,
+        }
+    }
+
+    /// The operand in standard syntax, e.g. `#$10`, `$10,X` or `($FFFC)`.
+    pub fn operand_text(&self) -> String {
+        let Some(operand) = self.operand() else {
+            return match self.opcode {
+                None => format!("${:02X}", self.bytes[0]),
+                Some(op) if op.addressing_mode == AddressingMode::Accumulator => "A".to_string(),
+                Some(_) => String::new(),
+            };
+        };
+
+        match self.addressing_mode() {
+            AddressingMode::Immediate => format!("#${:02X}", operand),
+            AddressingMode::ZeroPage => format!("${:02X}", operand),
+            AddressingMode::ZeroPageX => format!("${:02X},X", operand),
+            AddressingMode::ZeroPageY => format!("${:02X},Y", operand),
+            AddressingMode::Absolute => format!("${:04X}", operand),
+            AddressingMode::AbsoluteX => format!("${:04X},X", operand),
+            AddressingMode::AbsoluteY => format!("${:04X},Y", operand),
+            AddressingMode::Indirect => format!("(${:04X})", operand),
+            AddressingMode::IndirectX => format!("(${:02X},X)", operand),
+            AddressingMode::IndirectY => format!("(${:02X}),Y", operand),
+            AddressingMode::Relative => format!("${:04X}", self.target().unwrap_or(operand)),
+            AddressingMode::Implied
+                | AddressingMode::Accumulator
+                | AddressingMode::NoneAddressing => String::new(),
+        }
+    }
+
+    /// A listing line with the address and raw bytes, e.g.
+    /// `8000  B5 10     LDA $10,X`.
+    pub fn listing(&self) -> String {
+        let bytes: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        format!("{:04X}  {:<8}  {}", self.address, bytes.join(" "), self)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = self.operand_text();
+        if operand.is_empty() {
+            write!(f, "{}", self.mnemonic())
+        } else {
+            write!(f, "{} {}", self.mnemonic(), operand)
+        }
+    }
+}
+
+impl fmt::Debug for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.listing())
+    }
+}
+
+/// Decodes a single instruction from `fetch`, which returns the byte at an
+/// address. Operand bytes past the end of the address space wrap to $0000.
+pub fn decode(address: u16, mut fetch: impl FnMut(u16) -> u8) -> Instruction {
+    let code = fetch(address);
+    let opcode = opcode::lookup(code);
+    let len = opcode.map_or(1, |op| op.bytes);
+
+    let bytes = (0..len as u16)
+        .map(|i| if i == 0 { code } else { fetch(address.wrapping_add(i)) })
+        .collect();
+
+    Instruction { address, bytes, opcode }
+}
+
+/// Disassembles `bytes` as if they were loaded at `origin`. A trailing
+/// instruction cut short by the end of the slice is emitted as `.byte`s.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let mut instruction = decode(address, |addr| {
+            bytes.get(addr.wrapping_sub(origin) as usize).copied().unwrap_or(0)
+        });
+
+        if offset + instruction.bytes.len() > bytes.len() {
+            instruction = Instruction { address, bytes: vec![bytes[offset]], opcode: None };
+        }
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// Disassembles PRG-ROM loaded at `origin`, decoding only where the
+/// code/data log saw an instruction fetched. Data and bytes the log never
+/// saw are emitted as `.byte`s, so tables are not mistaken for code.
+pub fn disassemble_logged(prg: &[u8], cdl: &CodeDataLogger, origin: u16) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset < prg.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let mut instruction = Instruction { address, bytes: vec![prg[offset]], opcode: None };
+
+        if offset < cdl.prg_len() && cdl.prg_flags(offset).contains(PrgFlags::CODE) {
+            let decoded = decode(address, |addr| {
+                prg.get(addr.wrapping_sub(origin) as usize).copied().unwrap_or(0)
+            });
+            if offset + decoded.bytes.len() <= prg.len() {
+                instruction = decoded;
+            }
+        }
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// Disassembles bus memory from `start` up to and including `end`, reading
+/// with `Bus::peek` so that I/O registers are left untouched.
+pub fn disassemble_range<B: Bus>(bus: &B, start: u16, end: u16) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut address = start as u32;
+
+    while address <= end as u32 {
+        let instruction = decode(address as u16, |addr| bus.peek(addr));
+        address += instruction.len() as u32;
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// Disassembles `count` instructions starting at `start`.
+pub fn disassemble_count<B: Bus>(bus: &B, start: u16, count: usize) -> Vec<Instruction> {
+    let mut instructions = Vec::with_capacity(count);
+    let mut address = start;
+
+    for _ in 0..count {
+        let instruction = decode(address, |addr| bus.peek(addr));
+        address = address.wrapping_add(instruction.len());
+        instructions.push(instruction);
+    }
+    instructions
+}
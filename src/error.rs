@@ -1,22 +1,280 @@
 use std::fmt;
-use crate::cpu::AddressingMode;
+use crate::{cpu::AddressingMode, flags::StatusFlags};
 
-#[derive(Debug)]
-pub enum CPUError<'a> {
-    UnknownOpcode(u8),
-    UnimplementedInstruction(String),
-    InvalidAddressingMode(&'a AddressingMode),
+/// Snapshot of the machine taken at the instruction that failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CPUState {
+    pub program_counter: u16,
+    pub opcode: u8,
+    pub register_a: u8,
+    pub register_x: u8,
+    pub register_y: u8,
+    pub status: StatusFlags,
+    pub stack_pointer: u8,
+    pub cycles: u64,
 }
 
-impl fmt::Display for CPUError<'_> {
+impl fmt::Display for CPUState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "${:04X}  A:{:02X} X:{:02X} Y:{:02X} P:{} SP:{:02X} CYC:{}",
+            self.program_counter,
+            self.register_a,
+            self.register_x,
+            self.register_y,
+            self.status,
+            self.stack_pointer,
+            self.cycles,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CPUError {
+    UnknownOpcode(CPUState),
+    UnimplementedInstruction(String, CPUState),
+    InvalidAddressingMode(AddressingMode, CPUState),
+}
+
+impl CPUError {
+    pub fn state(&self) -> &CPUState {
+        match self {
+            CPUError::UnknownOpcode(state)
+                | CPUError::UnimplementedInstruction(_, state)
+                | CPUError::InvalidAddressingMode(_, state) => state,
+        }
+    }
+}
+
+impl fmt::Display for CPUError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CPUError::UnknownOpcode(state) => 
+                write!(f, "unknown opcode: 0x{:02X} at {}", state.opcode, state),
+            CPUError::UnimplementedInstruction(name, state) => 
+                write!(f, "CPU instruction {} not implemented at {}", name, state),
+            CPUError::InvalidAddressingMode(mode, state) => 
+                write!(f, "addressing mode {:?} is not supported by opcode 0x{:02X} at {}",
+                       mode, state.opcode, state),
+        }
+    }
+}
+
+impl std::error::Error for CPUError {}
+
+#[derive(Debug, PartialEq)]
+pub enum RomError {
+    InvalidHeader,
+    UnsupportedMapper(u8),
+    Truncated,
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::InvalidHeader =>
+                write!(f, "not an iNES image"),
+            RomError::UnsupportedMapper(mapper) =>
+                write!(f, "mapper {} is not supported", mapper),
+            RomError::Truncated =>
+                write!(f, "ROM image is shorter than its header declares"),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmError {
+    Syntax { line: usize, message: String },
+    UnknownInstruction { line: usize, mnemonic: String },
+    InvalidAddressingMode { line: usize, mnemonic: String },
+    UndefinedSymbol { line: usize, name: String },
+    DuplicateSymbol { line: usize, name: String },
+    BranchOutOfRange { line: usize, offset: i64 },
+    ValueOutOfRange { line: usize, value: i64 },
+    OverlappingSegment { origin: u16 },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmError::Syntax { line, message } =>
+                write!(f, "line {}: {}", line, message),
+            AsmError::UnknownInstruction { line, mnemonic } =>
+                write!(f, "line {}: unknown instruction {}", line, mnemonic),
+            AsmError::InvalidAddressingMode { line, mnemonic } =>
+                write!(f, "line {}: addressing mode not supported by {}", line, mnemonic),
+            AsmError::UndefinedSymbol { line, name } =>
+                write!(f, "line {}: undefined symbol {}", line, name),
+            AsmError::DuplicateSymbol { line, name } =>
+                write!(f, "line {}: {} is already defined", line, name),
+            AsmError::BranchOutOfRange { line, offset } =>
+                write!(f, "line {}: branch offset {} does not fit in a byte", line, offset),
+            AsmError::ValueOutOfRange { line, value } =>
+                write!(f, "line {}: value {} is out of range", line, value),
+            AsmError::OverlappingSegment { origin } =>
+                write!(f, ".org ${:04X} overlaps code that was already assembled", origin),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveStateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Truncated,
+    MissingChunk(String),
+    RomMismatch,
+    /// A field holds a value no build writes, such as an unknown region.
+    InvalidValue(String),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::BadMagic =>
+                write!(f, "not a save state"),
+            SaveStateError::UnsupportedVersion(version) =>
+                write!(f, "save state version {} is newer than this emulator", version),
+            SaveStateError::ChecksumMismatch =>
+                write!(f, "save state is corrupt (checksum mismatch)"),
+            SaveStateError::Truncated =>
+                write!(f, "save state is truncated"),
+            SaveStateError::MissingChunk(tag) =>
+                write!(f, "save state has no {:?} chunk", tag),
+            SaveStateError::RomMismatch =>
+                write!(f, "save state was made with a different ROM"),
+            SaveStateError::InvalidValue(field) =>
+                write!(f, "save state has an invalid {}", field),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MovieError {
+    Syntax { line: usize, message: String },
+    UnsupportedVersion(String),
+    /// A port is set to a device other than a standard controller.
+    UnsupportedDevice { port: usize, device: String },
+    /// The movie's `romChecksum` is not that of the loaded ROM.
+    RomMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::Syntax { line, message } =>
+                write!(f, "line {}: {}", line, message),
+            MovieError::UnsupportedVersion(version) =>
+                write!(f, "FM2 version {} is not supported", version),
+            MovieError::UnsupportedDevice { port, device } =>
+                write!(f, "port {} uses unsupported device type {}", port, device),
+            MovieError::RomMismatch { expected, actual } =>
+                write!(f, "movie was recorded on a different ROM (romChecksum {}, this ROM {})", expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheatError {
+    /// Neither a Game Genie code nor an `AAAA:VV` freeze code.
+    InvalidCode(String),
+    Syntax { line: usize, message: String },
+}
+
+impl fmt::Display for CheatError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CPUError::UnknownOpcode(opcode) => 
-                write!(f, "unknown opcode: 0x{:02X}", opcode),
-            CPUError::UnimplementedInstruction(name) => 
-                write!(f, "CPU instruction {} not implemented", name),
-            CPUError::InvalidAddressingMode(mode) => 
-                write!(f, "addressing mode {:?} is not supported", mode),
+            CheatError::InvalidCode(code) =>
+                write!(f, "{:?} is not a Game Genie or RAM freeze code", code),
+            CheatError::Syntax { line, message } =>
+                write!(f, "line {}: {}", line, message),
         }
     }
 }
+
+impl std::error::Error for CheatError {}
+
+#[derive(Debug, PartialEq)]
+pub enum TestRomError {
+    Rom(RomError),
+    Cpu(CPUError),
+    /// The program stopped on BRK before reporting a result.
+    Halted { message: String },
+    /// No result within the frame limit; `message` is whatever text the
+    /// ROM had written so far.
+    TimedOut { frames: u64, message: String },
+}
+
+impl fmt::Display for TestRomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestRomError::Rom(err) => write!(f, "{}", err),
+            TestRomError::Cpu(err) => write!(f, "{}", err),
+            TestRomError::Halted { message } =>
+                write!(f, "halted before reporting a result: {:?}", message),
+            TestRomError::TimedOut { frames, message } =>
+                write!(f, "no result after {} frames: {:?}", frames, message),
+        }
+    }
+}
+
+impl std::error::Error for TestRomError {}
+
+impl From<RomError> for TestRomError {
+    fn from(err: RomError) -> Self {
+        TestRomError::Rom(err)
+    }
+}
+
+impl From<CPUError> for TestRomError {
+    fn from(err: CPUError) -> Self {
+        TestRomError::Cpu(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SingleStepError {
+    Io(String),
+    Json(String),
+    /// Valid JSON that does not follow the test format.
+    Format(String),
+}
+
+impl fmt::Display for SingleStepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SingleStepError::Io(message) => write!(f, "{}", message),
+            SingleStepError::Json(message) => write!(f, "invalid JSON: {}", message),
+            SingleStepError::Format(message) => write!(f, "not a single-step test file: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SingleStepError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteError {
+    /// Neither 64 nor 512 RGB triples.
+    InvalidSize(usize),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteError::InvalidSize(len) =>
+                write!(f, "palette is {} bytes; expected 192 (64 colors) or 1536 (512 colors)", len),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
@@ -0,0 +1,49 @@
+use std::fmt;
+use bitflags::bitflags;
+
+bitflags! {
+    /// The 6502 processor status register (P).
+    ///
+    ///  7 6 5 4 3 2 1 0
+    ///  N V _ B D I Z C
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct StatusFlags: u8 {
+        const CARRY             = 0b0000_0001;
+        const ZERO              = 0b0000_0010;
+        const INTERRUPT_DISABLE = 0b0000_0100;
+        const DECIMAL           = 0b0000_1000;
+        const BREAK             = 0b0001_0000;
+        const UNUSED            = 0b0010_0000;
+        const OVERFLOW          = 0b0100_0000;
+        const NEGATIVE          = 0b1000_0000;
+    }
+}
+
+impl StatusFlags {
+    pub fn clear(&mut self, flags: StatusFlags) {
+        self.remove(flags);
+    }
+}
+
+impl fmt::Display for StatusFlags {
+    /// Formats the register as `NV-BDIZC`, upper case for set flags and
+    /// lower case for clear ones.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [(StatusFlags, char); 8] = [
+            (StatusFlags::NEGATIVE, 'N'),
+            (StatusFlags::OVERFLOW, 'V'),
+            (StatusFlags::UNUSED, '-'),
+            (StatusFlags::BREAK, 'B'),
+            (StatusFlags::DECIMAL, 'D'),
+            (StatusFlags::INTERRUPT_DISABLE, 'I'),
+            (StatusFlags::ZERO, 'Z'),
+            (StatusFlags::CARRY, 'C'),
+        ];
+
+        for (flag, name) in NAMES {
+            let c = if self.contains(flag) { name } else { name.to_ascii_lowercase() };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
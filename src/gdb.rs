@@ -0,0 +1,516 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+use crate::{
+    bus::Bus,
+    cpu::CPU,
+    debugger::{BreakpointKind, Debugger, StopReason},
+    error::CPUError,
+    flags::StatusFlags,
+};
+
+// GDB has no built-in 6502 target, so the register layout is described to
+// it through target.xml. Register numbers follow the order below.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.m6502.core">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="x" bitsize="8" regnum="1"/>
+    <reg name="y" bitsize="8" regnum="2"/>
+    <reg name="p" bitsize="8" regnum="3"/>
+    <reg name="sp" bitsize="8" regnum="4"/>
+    <reg name="pc" bitsize="16" type="code_ptr" regnum="5"/>
+  </feature>
+</target>"#;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+/// Instructions run between checks for a Ctrl-C from the client.
+const INTERRUPT_POLL_INTERVAL: u64 = 10_000;
+
+/// The largest packet we accept or send, as announced in `qSupported`.
+const PACKET_SIZE: usize = 0x1000;
+
+/// A GDB remote serial protocol server for the CPU. Breakpoints and
+/// watchpoints are kept in a `Debugger`, so hardware and software
+/// breakpoints behave the same.
+pub struct GdbStub {
+    debugger: Debugger,
+    /// Debugger breakpoint ids for each `Z` packet, keyed by its type,
+    /// address and length so that the matching `z` packet can remove them.
+    points: BTreeMap<(u8, u16, u16), Vec<usize>>,
+    no_ack: bool,
+    detached: bool,
+}
+
+impl Default for GdbStub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        GdbStub {
+            debugger: Debugger::new(),
+            points: BTreeMap::new(),
+            no_ack: false,
+            detached: false,
+        }
+    }
+
+    /// Waits for one client on `addr` and serves it until it detaches.
+    pub fn listen<B: Bus>(&mut self, cpu: &mut CPU<B>, addr: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        self.serve(cpu, stream)
+    }
+
+    /// Handles packets from a connected client until it detaches, kills the
+    /// session or closes the connection.
+    pub fn serve<B: Bus>(&mut self, cpu: &mut CPU<B>, mut stream: TcpStream) -> io::Result<()> {
+        self.detached = false;
+        stream.set_nodelay(true)?;
+
+        while !self.detached {
+            let Some(packet) = read_packet(&mut stream, self.no_ack)? else {
+                return Ok(());
+            };
+
+            // a client that goes away while the CPU runs stops it too
+            let mut disconnected = false;
+            let mut interrupted = || match poll_client(&stream) {
+                ClientEvent::Nothing => false,
+                ClientEvent::Interrupt => true,
+                ClientEvent::Disconnect => {
+                    disconnected = true;
+                    true
+                }
+            };
+            let reply = match packet {
+                Incoming::Interrupt => Some(format!("S{:02x}", SIGINT)),
+                Incoming::TooLong => Some("E01".to_string()),
+                Incoming::Packet(packet) => self.handle_packet(cpu, &packet, &mut interrupted),
+            };
+            if disconnected {
+                return Ok(());
+            }
+
+            if let Some(reply) = reply {
+                write_packet(&mut stream, &reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers one packet body (without `$` and checksum). `interrupted` is
+    /// polled while continuing so the client can break in. Returns `None`
+    /// when the packet takes no reply.
+    pub fn handle_packet<B: Bus>(
+        &mut self,
+        cpu: &mut CPU<B>,
+        packet: &str,
+        interrupted: &mut dyn FnMut() -> bool,
+    ) -> Option<String> {
+        let reply = match packet.as_bytes().first()? {
+            b'?' => format!("S{:02x}", SIGTRAP),
+            b'g' => read_registers(cpu),
+            b'G' => match write_registers(cpu, &packet[1..]) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            b'p' => match usize::from_str_radix(&packet[1..], 16).ok().and_then(|n| register(cpu, n)) {
+                Some(value) => value,
+                None => "E01".to_string(),
+            },
+            b'P' => match write_register(cpu, &packet[1..]) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            b'm' => match read_memory(cpu, &packet[1..]) {
+                Some(hex) => hex,
+                None => "E01".to_string(),
+            },
+            b'M' => match write_memory(cpu, &packet[1..]) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            b'c' => {
+                set_resume_address(cpu, &packet[1..]);
+                self.resume(cpu, interrupted)
+            }
+            b's' => {
+                set_resume_address(cpu, &packet[1..]);
+                self.single_step(cpu)
+            }
+            b'Z' => match self.insert_point(&packet[1..]) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            b'z' => match self.remove_point(&packet[1..]) {
+                Some(()) => "OK".to_string(),
+                None => "E01".to_string(),
+            },
+            b'H' => "OK".to_string(),
+            b'T' => "OK".to_string(),
+            b'D' => {
+                self.detached = true;
+                "OK".to_string()
+            }
+            b'k' => {
+                self.detached = true;
+                return None;
+            }
+            b'v' => self.handle_v_packet(cpu, packet, interrupted),
+            b'q' | b'Q' => self.handle_query(packet),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    fn handle_v_packet<B: Bus>(
+        &mut self,
+        cpu: &mut CPU<B>,
+        packet: &str,
+        interrupted: &mut dyn FnMut() -> bool,
+    ) -> String {
+        if packet == "vCont?" {
+            return "vCont;c;C;s;S".to_string();
+        }
+        let Some(actions) = packet.strip_prefix("vCont;") else {
+            return String::new();
+        };
+
+        // there is a single thread, so the first action is the one to apply
+        match actions.as_bytes().first() {
+            Some(b'c' | b'C') => self.resume(cpu, interrupted),
+            Some(b's' | b'S') => self.single_step(cpu),
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn handle_query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+", PACKET_SIZE);
+        }
+        if let Some(args) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return read_annex(TARGET_XML, args).unwrap_or_else(|| "E01".to_string());
+        }
+
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn single_step<B: Bus>(&mut self, cpu: &mut CPU<B>) -> String {
+        match self.debugger.step(cpu) {
+            Ok(_) => format!("S{:02x}", SIGTRAP),
+            Err(err) => error_reply(&err),
+        }
+    }
+
+    fn resume<B: Bus>(&mut self, cpu: &mut CPU<B>, interrupted: &mut dyn FnMut() -> bool) -> String {
+        loop {
+            match self.debugger.resume(cpu, Some(INTERRUPT_POLL_INTERVAL)) {
+                StopReason::Limit if interrupted() => return format!("S{:02x}", SIGINT),
+                StopReason::Limit => {}
+                StopReason::Breakpoint(id) => return self.breakpoint_reply(id),
+                StopReason::Stepped | StopReason::Halted => return format!("S{:02x}", SIGTRAP),
+                StopReason::Error(err) => return error_reply(&err),
+            }
+        }
+    }
+
+    fn breakpoint_reply(&self, id: usize) -> String {
+        let Some(&(kind, addr, _)) = self.points.iter()
+            .find(|(_, ids)| ids.contains(&id))
+            .map(|(key, _)| key)
+        else {
+            return format!("S{:02x}", SIGTRAP);
+        };
+
+        match kind {
+            0 => format!("T{:02x}swbreak:;", SIGTRAP),
+            1 => format!("T{:02x}hwbreak:;", SIGTRAP),
+            2 => format!("T{:02x}watch:{:x};", SIGTRAP, addr),
+            3 => format!("T{:02x}rwatch:{:x};", SIGTRAP, addr),
+            _ => format!("T{:02x}awatch:{:x};", SIGTRAP, addr),
+        }
+    }
+
+    /// `Z<type>,<addr>,<kind>`: 0 and 1 are breakpoints, 2 write, 3 read and
+    /// 4 access watchpoints over `kind` bytes.
+    fn insert_point(&mut self, args: &str) -> Option<()> {
+        let (kind, addr, len) = parse_point(args)?;
+        if self.points.contains_key(&(kind, addr, len)) {
+            return Some(());
+        }
+
+        let end = addr.wrapping_add(len.max(1) - 1);
+        let kinds = match kind {
+            0 | 1 => vec![BreakpointKind::Address(addr)],
+            2 => vec![BreakpointKind::Write(addr, end)],
+            3 => vec![BreakpointKind::Read(addr, end)],
+            4 => vec![BreakpointKind::Read(addr, end), BreakpointKind::Write(addr, end)],
+            _ => return None,
+        };
+
+        let ids = kinds.into_iter()
+            .map(|kind| self.debugger.add_breakpoint(kind, Vec::new()))
+            .collect();
+        self.points.insert((kind, addr, len), ids);
+        Some(())
+    }
+
+    fn remove_point(&mut self, args: &str) -> Option<()> {
+        let key = parse_point(args)?;
+        for id in self.points.remove(&key).unwrap_or_default() {
+            self.debugger.remove_breakpoint(id);
+        }
+        Some(())
+    }
+}
+
+fn error_reply(err: &CPUError) -> String {
+    match err {
+        CPUError::UnknownOpcode(_) => format!("S{:02x}", SIGILL),
+        _ => format!("S{:02x}", SIGTRAP),
+    }
+}
+
+fn register<B: Bus>(cpu: &CPU<B>, number: usize) -> Option<String> {
+    match number {
+        0 => Some(format!("{:02x}", cpu.register_a)),
+        1 => Some(format!("{:02x}", cpu.register_x)),
+        2 => Some(format!("{:02x}", cpu.register_y)),
+        3 => Some(format!("{:02x}", cpu.status.bits())),
+        4 => Some(format!("{:02x}", cpu.stack_pointer)),
+        5 => Some(hex_encode(&cpu.program_counter.to_le_bytes())),
+        _ => None,
+    }
+}
+
+fn read_registers<B: Bus>(cpu: &CPU<B>) -> String {
+    (0..6).filter_map(|n| register(cpu, n)).collect()
+}
+
+fn write_registers<B: Bus>(cpu: &mut CPU<B>, hex: &str) -> Option<()> {
+    let bytes = hex_decode(hex)?;
+    let [a, x, y, p, sp, pc_lo, pc_hi] = bytes[..] else {
+        return None;
+    };
+    cpu.register_a = a;
+    cpu.register_x = x;
+    cpu.register_y = y;
+    cpu.status = StatusFlags::from_bits_retain(p);
+    cpu.stack_pointer = sp;
+    cpu.program_counter = u16::from_le_bytes([pc_lo, pc_hi]);
+    Some(())
+}
+
+/// `P<n>=<value>`, with the value in target byte order.
+fn write_register<B: Bus>(cpu: &mut CPU<B>, args: &str) -> Option<()> {
+    let (number, value) = args.split_once('=')?;
+    let bytes = hex_decode(value)?;
+    match (usize::from_str_radix(number, 16).ok()?, &bytes[..]) {
+        (0, [value]) => cpu.register_a = *value,
+        (1, [value]) => cpu.register_x = *value,
+        (2, [value]) => cpu.register_y = *value,
+        (3, [value]) => cpu.status = StatusFlags::from_bits_retain(*value),
+        (4, [value]) => cpu.stack_pointer = *value,
+        (5, [lo, hi]) => cpu.program_counter = u16::from_le_bytes([*lo, *hi]),
+        _ => return None,
+    }
+    Some(())
+}
+
+/// `m<addr>,<len>`. Reads go through `Bus::peek` so that inspecting I/O
+/// registers does not disturb the running program. The reply must fit in
+/// a packet, at two hex digits per byte.
+fn read_memory<B: Bus>(cpu: &CPU<B>, args: &str) -> Option<String> {
+    let (addr, len) = args.split_once(',')?;
+    let addr = u16::from_str_radix(addr, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok().filter(|&len| len <= PACKET_SIZE / 2)?;
+    let bytes: Vec<u8> = (0..len).map(|i| cpu.bus.peek(addr.wrapping_add(i as u16))).collect();
+    Some(hex_encode(&bytes))
+}
+
+/// `M<addr>,<len>:<bytes>`.
+fn write_memory<B: Bus>(cpu: &mut CPU<B>, args: &str) -> Option<()> {
+    let (location, data) = args.split_once(':')?;
+    let (addr, len) = location.split_once(',')?;
+    let addr = u16::from_str_radix(addr, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+    let bytes = hex_decode(data)?;
+    if bytes.len() != len {
+        return None;
+    }
+
+    for (i, &byte) in bytes.iter().enumerate() {
+        cpu.bus.mem_write(addr.wrapping_add(i as u16), byte);
+    }
+    Some(())
+}
+
+/// `c` and `s` may name the address to resume from.
+fn set_resume_address<B: Bus>(cpu: &mut CPU<B>, args: &str) {
+    if let Ok(addr) = u16::from_str_radix(args, 16) {
+        cpu.program_counter = addr;
+    }
+}
+
+fn parse_point(args: &str) -> Option<(u8, u16, u16)> {
+    let mut fields = args.split(';').next()?.split(',');
+    let kind = fields.next()?.parse().ok()?;
+    let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let len = u16::from_str_radix(fields.next()?, 16).ok()?;
+    Some((kind, addr, len))
+}
+
+/// Serves `<offset>,<length>` of a qXfer object, prefixed with `m` when more
+/// data follows and `l` for the last chunk.
+fn read_annex(annex: &str, args: &str) -> Option<String> {
+    let (offset, length) = args.split_once(',')?;
+    let offset = usize::from_str_radix(offset, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+
+    let data = annex.get(offset.min(annex.len())..)?;
+    if data.len() <= length {
+        Some(format!("l{}", data))
+    } else {
+        Some(format!("m{}", &data[..length]))
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+    /// A packet longer than the announced `PacketSize`, whose body was
+    /// dropped.
+    TooLong,
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut byte = [0u8];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Reads the next `$<data>#<checksum>` packet or a bare Ctrl-C, answering
+/// with `+`/`-` unless no-ack mode is on. Returns `None` on disconnect.
+/// A body longer than `PACKET_SIZE` is read past rather than kept.
+fn read_packet(stream: &mut TcpStream, no_ack: bool) -> io::Result<Option<Incoming>> {
+    loop {
+        match read_byte(stream)? {
+            None => return Ok(None),
+            Some(0x03) => return Ok(Some(Incoming::Interrupt)),
+            Some(b'$') => {}
+            // stray acks and line noise between packets
+            Some(_) => continue,
+        }
+
+        let mut data = Vec::new();
+        let mut too_long = false;
+        loop {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(b'#') => break,
+                Some(_) if data.len() == PACKET_SIZE => too_long = true,
+                Some(byte) => data.push(byte),
+            }
+        }
+
+        let mut checksum = [0u8; 2];
+        stream.read_exact(&mut checksum)?;
+        if too_long {
+            // the checksum cannot be checked, but asking for the packet
+            // again would only bring it back
+            if !no_ack {
+                stream.write_all(b"+")?;
+            }
+            return Ok(Some(Incoming::TooLong));
+        }
+        let expected = std::str::from_utf8(&checksum).ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        let actual = data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+
+        if no_ack {
+            return Ok(Some(Incoming::Packet(String::from_utf8_lossy(&data).into_owned())));
+        }
+        if expected == Some(actual) {
+            stream.write_all(b"+")?;
+            return Ok(Some(Incoming::Packet(String::from_utf8_lossy(&data).into_owned())));
+        }
+        stream.write_all(b"-")?;
+    }
+}
+
+fn write_packet(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    write!(stream, "${}#{:02x}", data, checksum)?;
+    stream.flush()
+}
+
+/// What the client has sent while the CPU runs.
+enum ClientEvent {
+    Nothing,
+    Interrupt,
+    Disconnect,
+}
+
+/// Checks, without blocking, whether the client sent a Ctrl-C or closed
+/// the connection. Acks are consumed on the way; anything else is left
+/// for `read_packet`.
+fn poll_client(stream: &TcpStream) -> ClientEvent {
+    if stream.set_nonblocking(true).is_err() {
+        return ClientEvent::Nothing;
+    }
+    let event = loop {
+        let mut byte = [0u8];
+        match stream.peek(&mut byte) {
+            Ok(0) => break ClientEvent::Disconnect,
+            Ok(_) if matches!(byte[0], 0x03 | b'+' | b'-') => {
+                if (&*stream).read(&mut byte).is_err() {
+                    break ClientEvent::Disconnect;
+                }
+                if byte[0] == 0x03 {
+                    break ClientEvent::Interrupt;
+                }
+            }
+            Ok(_) => break ClientEvent::Nothing,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break ClientEvent::Nothing,
+            Err(_) => break ClientEvent::Disconnect,
+        }
+    };
+    let _ = stream.set_nonblocking(false);
+    event
+}
@@ -0,0 +1,318 @@
+use crate::{
+    error::SaveStateError,
+    joypad::{Buttons, Joypad},
+    ppu::PPU,
+    savestate::{Chunk, ChunkReader},
+    statehash::StateHasher,
+    zapper::Zapper,
+};
+
+/// Reads after which a four player adapter has shifted out both
+/// controllers and its signature.
+const MULTITAP_BITS: u8 = 24;
+
+/// What is plugged into one of the controller ports, $4016 or $4017.
+/// Both ports share the strobe line written through $4016, and each
+/// drives only the low five bits of its register.
+#[derive(Debug, Clone)]
+pub enum PortDevice {
+    Empty,
+    Joypad(Joypad),
+    Zapper(Zapper),
+    FourScore(FourScore),
+    Hori(HoriAdapter),
+}
+
+impl PortDevice {
+    pub fn write(&mut self, data: u8) {
+        match self {
+            PortDevice::Empty | PortDevice::Zapper(_) => {}
+            PortDevice::Joypad(joypad) => joypad.write(data),
+            PortDevice::FourScore(four_score) => four_score.shift.write(data),
+            PortDevice::Hori(hori) => hori.shift.write(data),
+        }
+    }
+
+    /// The port's bits for a CPU read. The Zapper senses the picture as the
+    /// PPU has drawn it at that moment.
+    pub fn read(&mut self, ppu: &PPU) -> u8 {
+        match self {
+            PortDevice::Empty => 0,
+            PortDevice::Joypad(joypad) => joypad.read(),
+            PortDevice::Zapper(zapper) => zapper.read(ppu),
+            PortDevice::FourScore(four_score) => four_score.read(),
+            PortDevice::Hori(hori) => hori.read(),
+        }
+    }
+
+    pub fn peek(&self, ppu: &PPU) -> u8 {
+        match self {
+            PortDevice::Empty => 0,
+            PortDevice::Joypad(joypad) => joypad.peek(),
+            PortDevice::Zapper(zapper) => zapper.read(ppu),
+            PortDevice::FourScore(four_score) => four_score.peek(),
+            PortDevice::Hori(hori) => hori.peek(),
+        }
+    }
+
+    /// The buttons of the device's first controller (`slot` 0) or, on a
+    /// four player adapter, of its second (`slot` 1).
+    pub fn buttons_mut(&mut self, slot: usize) -> Option<&mut Buttons> {
+        match self {
+            PortDevice::Joypad(joypad) if slot == 0 => Some(&mut joypad.buttons),
+            PortDevice::FourScore(four_score) => four_score.buttons.get_mut(slot),
+            PortDevice::Hori(hori) => hori.buttons.get_mut(slot),
+            _ => None,
+        }
+    }
+
+    /// What the player is doing with the device: each controller's
+    /// buttons, or the Zapper's trigger and aim.
+    pub fn input(&self) -> Vec<u8> {
+        match self {
+            PortDevice::Empty => Vec::new(),
+            PortDevice::Joypad(joypad) => vec![joypad.buttons.bits()],
+            PortDevice::Zapper(zapper) => {
+                let (x, y) = zapper.input.aim.unwrap_or_default();
+                vec![zapper.input.trigger as u8, zapper.input.aim.is_some() as u8, x as u8, y as u8]
+            }
+            PortDevice::FourScore(FourScore { buttons, .. }) | PortDevice::Hori(HoriAdapter { buttons, .. }) =>
+                buttons.iter().map(|buttons| buttons.bits()).collect(),
+        }
+    }
+
+    /// Sets what `input` recorded, taking the bytes this device uses from
+    /// its front.
+    pub fn set_input(&mut self, input: &mut &[u8]) {
+        let mut next = || input.split_first().map(|(&byte, rest)| {
+            *input = rest;
+            byte
+        });
+        match self {
+            PortDevice::Empty => {}
+            PortDevice::Joypad(joypad) => joypad.buttons = Buttons::from_bits_retain(next().unwrap_or(0)),
+            PortDevice::Zapper(zapper) => {
+                let [trigger, aimed, x, y] = [(); 4].map(|_| next().unwrap_or(0));
+                zapper.input.trigger = trigger != 0;
+                zapper.input.aim = (aimed != 0).then_some((x as usize, y as usize));
+            }
+            PortDevice::FourScore(FourScore { buttons, .. }) | PortDevice::Hori(HoriAdapter { buttons, .. }) => {
+                for buttons in buttons {
+                    *buttons = Buttons::from_bits_retain(next().unwrap_or(0));
+                }
+            }
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            PortDevice::Empty => 0,
+            PortDevice::Joypad(_) => 1,
+            PortDevice::Zapper(_) => 2,
+            PortDevice::FourScore(_) => 3,
+            PortDevice::Hori(_) => 4,
+        }
+    }
+
+    /// The device kind, then its state. The Zapper's aim and trigger come
+    /// from the frontend, so it has none.
+    pub fn save_state(&self, chunk: &mut Chunk) {
+        chunk.write_u8(self.kind());
+        match self {
+            PortDevice::Empty | PortDevice::Zapper(_) => {}
+            PortDevice::Joypad(joypad) => joypad.save_state(chunk),
+            PortDevice::FourScore(four_score) => four_score.shift.save_state(chunk, &four_score.buttons),
+            PortDevice::Hori(hori) => hori.shift.save_state(chunk, &hori.buttons),
+        }
+    }
+
+    /// The same state as `save_state`. The Zapper's aim and trigger are
+    /// left out, as there they are set by the frontend before every frame.
+    pub fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u8(self.kind());
+        match self {
+            PortDevice::Empty | PortDevice::Zapper(_) => {}
+            PortDevice::Joypad(joypad) => joypad.hash_state(hasher),
+            PortDevice::FourScore(four_score) => four_score.shift.hash_state(hasher, &four_score.buttons),
+            PortDevice::Hori(hori) => hori.shift.hash_state(hasher, &hori.buttons),
+        }
+    }
+
+    /// Restores the saved device, replacing the plugged-in one if it is of
+    /// another kind. `port` is 0 for $4016 and 1 for $4017.
+    pub fn load_state(&mut self, chunk: &mut ChunkReader, port: usize) -> Result<(), SaveStateError> {
+        let kind = chunk.read_u8()?;
+        if kind != self.kind() {
+            *self = match kind {
+                0 => PortDevice::Empty,
+                1 => PortDevice::Joypad(Joypad::new()),
+                2 => PortDevice::Zapper(Zapper::new()),
+                3 => PortDevice::FourScore(FourScore::new(port)),
+                4 => PortDevice::Hori(HoriAdapter::new(port)),
+                _ => return Err(SaveStateError::InvalidValue("controller port device".to_string())),
+            };
+        }
+        match self {
+            PortDevice::Empty | PortDevice::Zapper(_) => Ok(()),
+            PortDevice::Joypad(joypad) => joypad.load_state(chunk),
+            PortDevice::FourScore(four_score) => four_score.shift.load_state(chunk, &mut four_score.buttons),
+            PortDevice::Hori(hori) => hori.shift.load_state(chunk, &mut hori.buttons),
+        }
+    }
+}
+
+/// The usual combinations of devices, as chosen on the command line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputLayout {
+    /// A controller in each port.
+    #[default]
+    Standard,
+    /// A controller, and a Zapper in the second port.
+    Zapper,
+    /// The NES Four Score across both ports.
+    FourScore,
+    /// The Famicom's two controllers plus a Hori 4 Players Adapter on the
+    /// expansion port.
+    Hori,
+}
+
+impl InputLayout {
+    pub fn parse(name: &str) -> Option<InputLayout> {
+        match name.to_ascii_lowercase().as_str() {
+            "standard" => Some(InputLayout::Standard),
+            "zapper" => Some(InputLayout::Zapper),
+            "fourscore" => Some(InputLayout::FourScore),
+            "hori" => Some(InputLayout::Hori),
+            _ => None,
+        }
+    }
+
+    /// Fresh devices for $4016 and $4017.
+    pub fn devices(&self) -> [PortDevice; 2] {
+        match self {
+            InputLayout::Standard => [PortDevice::Joypad(Joypad::new()), PortDevice::Joypad(Joypad::new())],
+            InputLayout::Zapper => [PortDevice::Joypad(Joypad::new()), PortDevice::Zapper(Zapper::new())],
+            InputLayout::FourScore => [0, 1].map(|port| PortDevice::FourScore(FourScore::new(port))),
+            InputLayout::Hori => [0, 1].map(|port| PortDevice::Hori(HoriAdapter::new(port))),
+        }
+    }
+}
+
+/// The read counter of a four player adapter's port, which sends two
+/// controllers' worth of bits and then an 8-bit signature.
+#[derive(Debug, Clone)]
+struct MultitapShift {
+    /// Shifted out least significant bit first after the controllers.
+    signature: u8,
+    strobe: bool,
+    index: u8,
+}
+
+impl MultitapShift {
+    fn new(signature: u8) -> Self {
+        MultitapShift { signature, strobe: false, index: 0 }
+    }
+
+    fn write(&mut self, data: u8) {
+        self.strobe = data & 1 != 0;
+        if self.strobe {
+            self.index = 0;
+        }
+    }
+
+    fn advance(&mut self) {
+        if !self.strobe && self.index < MULTITAP_BITS {
+            self.index += 1;
+        }
+    }
+
+    /// `first`'s buttons, then `second`'s (0s if there is none), then the
+    /// signature and 1s after it.
+    fn bit(&self, first: Buttons, second: Option<Buttons>) -> u8 {
+        let buttons = |buttons: Buttons, index: u8| (buttons.bits() >> index) & 1;
+        match self.index {
+            0..=7 => buttons(first, self.index),
+            8..=15 => second.map_or(0, |second| buttons(second, self.index - 8)),
+            16..=23 => (self.signature >> (self.index - 16)) & 1,
+            _ => 1,
+        }
+    }
+
+    fn save_state(&self, chunk: &mut Chunk, buttons: &[Buttons; 2]) {
+        chunk.write_u8(buttons[0].bits());
+        chunk.write_u8(buttons[1].bits());
+        chunk.write_bool(self.strobe);
+        chunk.write_u8(self.index);
+    }
+
+    fn hash_state(&self, hasher: &mut StateHasher, buttons: &[Buttons; 2]) {
+        hasher.write_bytes(&[buttons[0].bits(), buttons[1].bits(), self.strobe as u8, self.index]);
+    }
+
+    fn load_state(&mut self, chunk: &mut ChunkReader, buttons: &mut [Buttons; 2]) -> Result<(), SaveStateError> {
+        buttons[0] = Buttons::from_bits_retain(chunk.read_u8()?);
+        buttons[1] = Buttons::from_bits_retain(chunk.read_u8()?);
+        self.strobe = chunk.read_bool()?;
+        self.index = chunk.read_u8()?;
+        Ok(())
+    }
+}
+
+/// One port's half of the NES Four Score. $4016 shifts out player 1 then
+/// player 3, $4017 player 2 then player 4; both follow with a signature
+/// byte that lets games detect the adapter: the 20th read of $4016 and
+/// the 19th of $4017 return 1.
+#[derive(Debug, Clone)]
+pub struct FourScore {
+    /// The controllers on this port: players 1 and 3, or 2 and 4.
+    pub buttons: [Buttons; 2],
+    shift: MultitapShift,
+}
+
+impl FourScore {
+    pub fn new(port: usize) -> Self {
+        let signature = if port == 0 { 0b0000_1000 } else { 0b0000_0100 };
+        FourScore { buttons: [Buttons::empty(); 2], shift: MultitapShift::new(signature) }
+    }
+
+    fn read(&mut self) -> u8 {
+        let bit = self.peek();
+        self.shift.advance();
+        bit
+    }
+
+    fn peek(&self) -> u8 {
+        self.shift.bit(self.buttons[0], Some(self.buttons[1]))
+    }
+}
+
+/// One port's half of the Hori 4 Players Adapter for the Famicom. The
+/// built-in controller stays on D0; the adapter's controller, player 3 on
+/// $4016 and player 4 on $4017, comes in on D1, followed by eight 0s and
+/// a signature mirroring the Four Score's: the 19th read of $4016 and the
+/// 20th of $4017 return 1.
+#[derive(Debug, Clone)]
+pub struct HoriAdapter {
+    /// The built-in controller, then the one plugged into the adapter.
+    pub buttons: [Buttons; 2],
+    shift: MultitapShift,
+}
+
+impl HoriAdapter {
+    pub fn new(port: usize) -> Self {
+        let signature = if port == 0 { 0b0000_0100 } else { 0b0000_1000 };
+        HoriAdapter { buttons: [Buttons::empty(); 2], shift: MultitapShift::new(signature) }
+    }
+
+    fn read(&mut self) -> u8 {
+        let bit = self.peek();
+        self.shift.advance();
+        bit
+    }
+
+    fn peek(&self) -> u8 {
+        let index = self.shift.index;
+        let built_in = if index < 8 { (self.buttons[0].bits() >> index) & 1 } else { 1 };
+        built_in | self.shift.bit(self.buttons[1], None) << 1
+    }
+}
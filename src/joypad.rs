@@ -0,0 +1,78 @@
+use bitflags::bitflags;
+use crate::{
+    error::SaveStateError,
+    savestate::{Chunk, ChunkReader},
+    statehash::StateHasher,
+};
+
+bitflags! {
+    /// Standard controller buttons, in the order the shift register reports
+    /// them.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Buttons: u8 {
+        const A      = 0b0000_0001;
+        const B      = 0b0000_0010;
+        const SELECT = 0b0000_0100;
+        const START  = 0b0000_1000;
+        const UP     = 0b0001_0000;
+        const DOWN   = 0b0010_0000;
+        const LEFT   = 0b0100_0000;
+        const RIGHT  = 0b1000_0000;
+    }
+}
+
+/// A standard NES controller: a parallel-in, serial-out shift register that
+/// latches the buttons while the strobe bit written to $4016 is high.
+#[derive(Debug, Default, Clone)]
+pub struct Joypad {
+    pub buttons: Buttons,
+    strobe: bool,
+    index: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 1 != 0;
+        if self.strobe {
+            self.index = 0;
+        }
+    }
+
+    /// Shifts out the next button, A first. After all eight have been read
+    /// an official controller returns 1s.
+    pub fn read(&mut self) -> u8 {
+        let bit = self.peek();
+        if !self.strobe && self.index < 8 {
+            self.index += 1;
+        }
+        bit
+    }
+
+    pub fn peek(&self) -> u8 {
+        if self.index > 7 {
+            return 1;
+        }
+        (self.buttons.bits() >> self.index) & 1
+    }
+
+    pub fn save_state(&self, chunk: &mut Chunk) {
+        chunk.write_u8(self.buttons.bits());
+        chunk.write_bool(self.strobe);
+        chunk.write_u8(self.index);
+    }
+
+    pub fn load_state(&mut self, chunk: &mut ChunkReader) -> Result<(), SaveStateError> {
+        self.buttons = Buttons::from_bits_retain(chunk.read_u8()?);
+        self.strobe = chunk.read_bool()?;
+        self.index = chunk.read_u8()?;
+        Ok(())
+    }
+
+    pub fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&[self.buttons.bits(), self.strobe as u8, self.index]);
+    }
+}
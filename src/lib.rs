@@ -1,3 +1,32 @@
 pub mod cpu;
+pub mod flags;
 pub mod opcode;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod bus;
+pub mod cartridge;
+pub mod ppu;
+pub mod apu;
+pub mod joypad;
+pub mod disasm;
+pub mod asm;
+pub mod compare;
+pub mod debugger;
+pub mod gdb;
+pub mod savestate;
+pub mod rewind;
+pub mod battery;
+pub mod movie;
+pub mod statehash;
+pub mod watch;
+pub mod cdl;
+pub mod cheat;
+pub mod ramsearch;
+pub mod testrom;
+#[cfg(feature = "singlestep")]
+pub mod singlestep;
+pub mod region;
+pub mod palette;
+pub mod ntsc;
+pub mod video;
+pub mod zapper;
+pub mod input;
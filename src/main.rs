@@ -1,3 +1,229 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    process,
+};
+use nes_emulator::{
+    battery::BatterySave,
+    bus::{NesBus, RamPattern},
+    cartridge::Cartridge,
+    cdl::CodeDataLogger,
+    cheat::Cheats,
+    cpu::CPU,
+    debugger::{Debugger, Response},
+    gdb::GdbStub,
+    input::InputLayout,
+    ntsc::NtscSetup,
+    palette::Palette,
+    region::Region,
+    video::{Scaler, VideoConfig},
+};
+
+/// About once a minute at 60 frames per second.
+const BATTERY_FLUSH_INTERVAL: u64 = 3600;
+
 fn main() {
+    let mut debug = false;
+    let mut gdb_port = None;
+    let mut cdl_path = None;
+    let mut cheats_path = None;
+    let mut region = None;
+    let mut input = InputLayout::Standard;
+    let mut video = VideoConfig::default();
+    let mut screenshot_path = None;
+    let mut frame_limit = None;
+    let mut rom_path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--debug" | "-d" => debug = true,
+            "--gdb" => {
+                gdb_port = Some(args.next().and_then(|port| port.parse::<u16>().ok()).unwrap_or_else(|| {
+                    eprintln!("--gdb takes a TCP port number");
+                    process::exit(2);
+                }));
+            }
+            "--cdl" => cdl_path = args.next(),
+            "--cheats" => cheats_path = args.next(),
+            "--region" => {
+                let name = args.next().unwrap_or_default();
+                region = match name.as_str() {
+                    "auto" => None,
+                    _ => Some(Region::parse(&name).unwrap_or_else(|| {
+                        eprintln!("unknown region {:?}: expected ntsc, pal, dendy or auto", name);
+                        process::exit(2);
+                    })),
+                };
+            }
+            "--input" => {
+                let name = args.next().unwrap_or_default();
+                input = InputLayout::parse(&name).unwrap_or_else(|| {
+                    eprintln!("unknown input layout {:?}: expected standard, zapper, fourscore or hori", name);
+                    process::exit(2);
+                });
+            }
+            "--palette" => {
+                let path = args.next().unwrap_or_default();
+                let data = fs::read(&path).map_err(|err| err.to_string());
+                match data.and_then(|data| Palette::from_bytes(&data).map_err(|err| err.to_string())) {
+                    Ok(palette) => video.palette = palette,
+                    Err(err) => {
+                        eprintln!("{}: {}", path, err);
+                        process::exit(1);
+                    }
+                }
+            }
+            "--ntsc" => video.ntsc = Some(NtscSetup::default()),
+            "--scale" => {
+                let name = args.next().unwrap_or_default();
+                video.scaler = Scaler::parse(&name).unwrap_or_else(|| {
+                    eprintln!("unknown scaler {:?}: expected none, scale2x, scale3x, hq2x, xbrz2, xbrz3 or xbrz4", name);
+                    process::exit(2);
+                });
+            }
+            "--scanlines" => {
+                video.scanlines = args.next().and_then(|percent| percent.parse().ok())
+                    .filter(|&percent| percent <= 100)
+                    .unwrap_or_else(|| {
+                        eprintln!("--scanlines takes a percentage from 0 to 100");
+                        process::exit(2);
+                    });
+            }
+            "--screenshot" => screenshot_path = args.next(),
+            "--frames" => {
+                frame_limit = Some(args.next().and_then(|frames| frames.parse::<u64>().ok()).unwrap_or_else(|| {
+                    eprintln!("--frames takes a number of frames");
+                    process::exit(2);
+                }));
+            }
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        eprintln!("usage: nes-emulator [--debug | --gdb <port>] [--cdl <file.cdl>] [--cheats <file>] [--region ntsc|pal|dendy|auto] \
+                   [--input standard|zapper|fourscore|hori] \
+                   [--palette <file.pal>] [--ntsc] [--scale <scaler>] [--scanlines <percent>] \
+                   [--frames <count>] [--screenshot <file.ppm>] <rom.nes>");
+        process::exit(2);
+    };
+
+    let rom = fs::read(&rom_path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", rom_path, err);
+        process::exit(1);
+    });
+    let cartridge = Cartridge::new(&rom).unwrap_or_else(|err| {
+        eprintln!("{}: {}", rom_path, err);
+        process::exit(1);
+    });
+
+    let mut cpu = CPU::with_bus(NesBus::new(cartridge));
+    cpu.bus.ports = input.devices();
+    if let Some(region) = region {
+        cpu.bus.set_region(region);
+    }
+    let mut battery = BatterySave::for_rom(&rom_path, BATTERY_FLUSH_INTERVAL);
+    if let Err(err) = battery.load(&mut cpu.bus.cartridge) {
+        eprintln!("{}: {}", battery.path().display(), err);
+    }
+    if let Some(path) = &cheats_path {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string());
+        match text.and_then(|text| Cheats::parse(&text).map_err(|err| err.to_string())) {
+            Ok(cheats) => cpu.bus.cheats = cheats,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &cdl_path {
+        start_code_data_log(&mut cpu.bus.cartridge, path);
+    }
+    cpu.power_on(RamPattern::Zeros);
+
+    let result = if let Some(port) = gdb_port {
+        println!("waiting for gdb on localhost:{}", port);
+        GdbStub::new().listen(&mut cpu, ("127.0.0.1", port))
+            .map_err(|err| format!("gdb: {}", err))
+    } else if debug {
+        run_debugger(&mut cpu);
+        Ok(())
+    } else {
+        run(&mut cpu, &mut battery, frame_limit)
+    };
+
+    if let Err(err) = battery.flush(&mut cpu.bus.cartridge) {
+        eprintln!("{}: {}", battery.path().display(), err);
+    }
+    if let (Some(path), Some(cdl)) = (&cdl_path, &cpu.bus.cartridge.cdl) {
+        if let Err(err) = fs::write(path, cdl.to_bytes()) {
+            eprintln!("{}: {}", path, err);
+        }
+    }
+    if let Some(path) = &screenshot_path {
+        if let Err(err) = fs::write(path, video.render(&cpu.bus.ppu).to_ppm()) {
+            eprintln!("{}: {}", path, err);
+        }
+    }
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+/// Turns on code/data logging, carrying on from `path` if it holds a log
+/// for this ROM.
+fn start_code_data_log(cartridge: &mut Cartridge, path: &str) {
+    cartridge.enable_cdl();
+    let Ok(data) = fs::read(path) else {
+        return;
+    };
+    let fresh = cartridge.cdl.as_ref().unwrap();
+    match CodeDataLogger::from_bytes(&data, fresh.prg_len(), fresh.chr_len()) {
+        Some(cdl) => cartridge.cdl = Some(cdl),
+        None => eprintln!("{}: log does not match the ROM size, starting a new one", path),
+    }
+}
+
+/// Runs until the program halts or, given a limit, that many frames have
+/// been drawn.
+fn run(cpu: &mut CPU<NesBus>, battery: &mut BatterySave, frame_limit: Option<u64>) -> Result<(), String> {
+    while frame_limit.is_none_or(|limit| cpu.bus.ppu.frame < limit)
+        && cpu.run_frame().map_err(|err| err.to_string())? {
+        battery.on_frame(&mut cpu.bus.cartridge, cpu.bus.ppu.frame)
+            .map_err(|err| format!("{}: {}", battery.path().display(), err))?;
+    }
+    Ok(())
+}
+
+fn run_debugger(cpu: &mut CPU<NesBus>) {
+    let mut debugger = Debugger::new();
+    let stdin = io::stdin();
+    let mut last_command = String::from("step");
+
+    println!("{}", match debugger.execute(cpu, "regs") {
+        Response::Output(text) => text,
+        Response::Quit => return,
+    });
+
+    loop {
+        print!("(nes) ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        // an empty line repeats the previous command, like gdb
+        let line = line.trim();
+        if !line.is_empty() {
+            last_command = line.to_string();
+        }
 
-}
\ No newline at end of file
+        match debugger.execute(cpu, &last_command) {
+            Response::Output(text) if !text.is_empty() => println!("{}", text),
+            Response::Output(_) => {}
+            Response::Quit => break,
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use std::{collections::BTreeMap, fmt::Write};
+use bitflags::bitflags;
+use crate::{
+    bus::{NesBus, RamPattern},
+    cartridge::Cartridge,
+    cpu::CPU,
+    error::{CPUError, MovieError},
+    input::{InputLayout, PortDevice},
+    joypad::Buttons,
+    region::Region,
+    savestate::crc32,
+};
+
+bitflags! {
+    /// The command column of an FM2 input line.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct MovieCommands: u8 {
+        const RESET      = 0b0000_0001;
+        const POWER      = 0b0000_0010;
+        const FDS_INSERT = 0b0000_0100;
+        const FDS_SELECT = 0b0000_1000;
+        const VS_COIN    = 0b0001_0000;
+    }
+}
+
+/// FM2 gamepad columns, left to right, and the buttons they stand for.
+const PAD_COLUMNS: [(char, Buttons); 8] = [
+    ('R', Buttons::RIGHT),
+    ('L', Buttons::LEFT),
+    ('D', Buttons::DOWN),
+    ('U', Buttons::UP),
+    ('T', Buttons::START),
+    ('S', Buttons::SELECT),
+    ('B', Buttons::B),
+    ('A', Buttons::A),
+];
+
+/// MD5's per-round shift amounts.
+const MD5_SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+/// MD5's per-step constants, the table `K` of RFC 1321.
+const MD5_K: [u32; 64] = [
+    0xD76A_A478, 0xE8C7_B756, 0x2420_70DB, 0xC1BD_CEEE,
+    0xF57C_0FAF, 0x4787_C62A, 0xA830_4613, 0xFD46_9501,
+    0x6980_98D8, 0x8B44_F7AF, 0xFFFF_5BB1, 0x895C_D7BE,
+    0x6B90_1122, 0xFD98_7193, 0xA679_438E, 0x49B4_0821,
+    0xF61E_2562, 0xC040_B340, 0x265E_5A51, 0xE9B6_C7AA,
+    0xD62F_105D, 0x0244_1453, 0xD8A1_E681, 0xE7D3_FBC8,
+    0x21E1_CDE6, 0xC337_07D6, 0xF4D5_0D87, 0x455A_14ED,
+    0xA9E3_E905, 0xFCEF_A3F8, 0x676F_02D9, 0x8D2A_4C8A,
+    0xFFFA_3942, 0x8771_F681, 0x6D9D_6122, 0xFDE5_380C,
+    0xA4BE_EA44, 0x4BDE_CFA9, 0xF6BB_4B60, 0xBEBF_BC70,
+    0x289B_7EC6, 0xEAA1_27FA, 0xD4EF_3085, 0x0488_1D05,
+    0xD9D4_D039, 0xE6DB_99E5, 0x1FA2_7CF8, 0xC4AC_5665,
+    0xF429_2244, 0x432A_FF97, 0xAB94_23A7, 0xFC93_A039,
+    0x655B_59C3, 0x8F0C_CC92, 0xFFEF_F47D, 0x8584_5DD1,
+    0x6FA8_7E4F, 0xFE2C_E6E0, 0xA301_4314, 0x4E08_11A1,
+    0xF753_7E82, 0xBD3A_F235, 0x2AD7_D2BB, 0xEB86_D391,
+];
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// FM2 port device numbers.
+const DEVICE_NONE: &str = "0";
+const DEVICE_GAMEPAD: &str = "1";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MovieFrame {
+    pub commands: MovieCommands,
+    /// Players 1 to 4; the last two only with a Four Score.
+    pub pads: [Buttons; 4],
+}
+
+/// An input movie in the FCEUX FM2 text format.
+///
+/// Besides the standard header keys, `ramChecksums` lists `frame:crc` pairs
+/// holding the CRC-32 of work RAM after those frames, which playback uses to
+/// notice desyncs. FCEUX ignores the key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub emu_version: String,
+    pub rerecord_count: u32,
+    pub pal: bool,
+    pub new_ppu: bool,
+    pub rom_filename: String,
+    pub rom_checksum: String,
+    pub guid: String,
+    /// Whether a gamepad is plugged into each port.
+    pub ports: [bool; 2],
+    /// A Four Score across both ports, with a gamepad for each of four
+    /// players. `ports` is then ignored.
+    pub fourscore: bool,
+    pub comments: Vec<String>,
+    pub frames: Vec<MovieFrame>,
+    pub checksums: BTreeMap<usize, u32>,
+}
+
+impl Default for Movie {
+    fn default() -> Self {
+        Movie {
+            emu_version: "0".to_string(),
+            rerecord_count: 0,
+            pal: false,
+            new_ppu: false,
+            rom_filename: String::new(),
+            rom_checksum: String::new(),
+            guid: "00000000-0000-0000-0000-000000000000".to_string(),
+            ports: [true, true],
+            fourscore: false,
+            comments: Vec::new(),
+            frames: Vec::new(),
+            checksums: BTreeMap::new(),
+        }
+    }
+}
+
+impl Movie {
+    pub fn parse_fm2(text: &str) -> Result<Movie, MovieError> {
+        let mut movie = Movie::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let syntax = |message: &str| MovieError::Syntax { line: line_number, message: message.to_string() };
+
+            if line.starts_with('|') {
+                let frame = parse_input_line(line, movie.pads()).map_err(|message| syntax(&message))?;
+                movie.frames.push(frame);
+                continue;
+            }
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" if value != "3" => return Err(MovieError::UnsupportedVersion(value.to_string())),
+                "version" => {}
+                "emuVersion" => movie.emu_version = value.to_string(),
+                "rerecordCount" => movie.rerecord_count = value.parse().map_err(|_| syntax("bad rerecordCount"))?,
+                "palFlag" => movie.pal = value == "1",
+                "NewPPU" => movie.new_ppu = value == "1",
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "romChecksum" => movie.rom_checksum = value.to_string(),
+                "guid" => movie.guid = value.to_string(),
+                "comment" => movie.comments.push(value.to_string()),
+                "fourscore" => movie.fourscore = value == "1",
+                "port0" | "port1" => {
+                    let port = (key == "port1") as usize;
+                    movie.ports[port] = match value {
+                        DEVICE_NONE => false,
+                        DEVICE_GAMEPAD => true,
+                        _ => return Err(MovieError::UnsupportedDevice { port, device: value.to_string() }),
+                    };
+                }
+                "ramChecksums" => {
+                    for entry in value.split_whitespace() {
+                        let (frame, crc) = entry.split_once(':').ok_or_else(|| syntax("bad ramChecksums entry"))?;
+                        let frame = frame.parse().map_err(|_| syntax("bad ramChecksums frame"))?;
+                        let crc = u32::from_str_radix(crc, 16).map_err(|_| syntax("bad ramChecksums value"))?;
+                        movie.checksums.insert(frame, crc);
+                    }
+                }
+                "savestate" => return Err(syntax("movies that start from a savestate are not supported")),
+                // port2, FDS, microphone, subtitles and anything newer
+                _ => {}
+            }
+        }
+        Ok(movie)
+    }
+
+    pub fn to_fm2(&self) -> String {
+        let mut out = String::new();
+        let flag = |set: bool| if set { 1 } else { 0 };
+
+        writeln!(out, "version 3").unwrap();
+        writeln!(out, "emuVersion {}", self.emu_version).unwrap();
+        writeln!(out, "rerecordCount {}", self.rerecord_count).unwrap();
+        writeln!(out, "palFlag {}", flag(self.pal)).unwrap();
+        writeln!(out, "NewPPU {}", flag(self.new_ppu)).unwrap();
+        writeln!(out, "FDS 0").unwrap();
+        writeln!(out, "fourscore {}", flag(self.fourscore)).unwrap();
+        writeln!(out, "port0 {}", flag(self.ports[0])).unwrap();
+        writeln!(out, "port1 {}", flag(self.ports[1])).unwrap();
+        writeln!(out, "port2 0").unwrap();
+        writeln!(out, "romFilename {}", self.rom_filename).unwrap();
+        writeln!(out, "romChecksum {}", self.rom_checksum).unwrap();
+        writeln!(out, "guid {}", self.guid).unwrap();
+        for comment in &self.comments {
+            writeln!(out, "comment {}", comment).unwrap();
+        }
+        if !self.checksums.is_empty() {
+            let entries: Vec<String> = self.checksums.iter()
+                .map(|(frame, crc)| format!("{}:{:08x}", frame, crc))
+                .collect();
+            writeln!(out, "ramChecksums {}", entries.join(" ")).unwrap();
+        }
+
+        for frame in &self.frames {
+            write!(out, "|{}|", frame.commands.bits()).unwrap();
+            for (&plugged, buttons) in self.pads().iter().zip(&frame.pads) {
+                if plugged {
+                    out.extend(PAD_COLUMNS.iter()
+                        .map(|&(name, button)| if buttons.contains(button) { name } else { '.' }));
+                }
+                out.push('|');
+            }
+            out.push_str("|\n");
+        }
+        out
+    }
+
+    /// The gamepad fields of an input line, and whether each has a gamepad:
+    /// one per port, or one per player with a Four Score.
+    fn pads(&self) -> &[bool] {
+        if self.fourscore {
+            &[true; 4]
+        } else {
+            &self.ports
+        }
+    }
+}
+
+/// `|commands|port0|port1|port2|`, or `|commands|pad1|pad2|pad3|pad4|port2|`
+/// with a Four Score.
+fn parse_input_line(line: &str, pads: &[bool]) -> Result<MovieFrame, String> {
+    let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('|').collect();
+    if fields.len() < pads.len() + 2 {
+        return Err(format!("input line needs a command and {} gamepad fields", pads.len()));
+    }
+
+    let commands = fields[1].trim().parse::<u8>()
+        .map_err(|_| format!("bad command field {:?}", fields[1]))?;
+    let mut frame = MovieFrame {
+        commands: MovieCommands::from_bits_retain(commands),
+        pads: [Buttons::empty(); 4],
+    };
+
+    for (player, &plugged) in pads.iter().enumerate() {
+        if !plugged {
+            continue;
+        }
+        let field = fields[player + 2];
+        if field.chars().count() != 8 {
+            return Err(format!("gamepad field {:?} must be 8 characters", field));
+        }
+        for (c, &(_, button)) in field.chars().zip(PAD_COLUMNS.iter()) {
+            frame.pads[player].set(button, c != '.' && c != ' ');
+        }
+    }
+    Ok(frame)
+}
+
+/// Feeds one frame's input to the console: commands first, then the
+/// controller state the game will read during the frame.
+fn apply(cpu: &mut CPU<NesBus>, frame: &MovieFrame) {
+    if frame.commands.contains(MovieCommands::POWER) {
+        cpu.power_on(RamPattern::Zeros);
+    } else if frame.commands.contains(MovieCommands::RESET) {
+        cpu.reset();
+    }
+    for (player, &buttons) in frame.pads.iter().enumerate() {
+        cpu.bus.set_buttons(player, buttons);
+    }
+}
+
+/// The ROM's `romChecksum` as FCEUX writes it: the MD5 of PRG-ROM followed
+/// by CHR-ROM, in base64.
+pub fn rom_checksum(cartridge: &Cartridge) -> String {
+    let mut rom = cartridge.prg_rom.clone();
+    if !cartridge.chr_is_ram {
+        rom.extend_from_slice(&cartridge.chr);
+    }
+    format!("base64:{}", base64(&md5(&rom)))
+}
+
+/// MD5 (RFC 1321).
+fn md5(data: &[u8]) -> [u8; 16] {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    for block in message.chunks_exact(64) {
+        let words: Vec<u32> = block.chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a.wrapping_add(f).wrapping_add(MD5_K[i]).wrapping_add(words[g])
+                .rotate_left(MD5_SHIFTS[i / 16 * 4 + i % 4]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn has_four_score(cpu: &CPU<NesBus>) -> bool {
+    matches!(cpu.bus.ports[0], PortDevice::FourScore(_))
+}
+
+fn ram_checksum(cpu: &CPU<NesBus>) -> u32 {
+    crc32(cpu.bus.ram())
+}
+
+/// Records input frame by frame, starting from power-on.
+pub struct Recorder {
+    movie: Movie,
+    checksum_interval: usize,
+}
+
+impl Recorder {
+    /// Powers the console on and starts a movie with `header`'s metadata.
+    /// A RAM checksum is stored every `checksum_interval` frames (never if
+    /// zero). The PAL flag is taken from the console's region, and the
+    /// Four Score flag from its controller ports and `romChecksum` from the
+    /// cartridge.
+    pub fn start(cpu: &mut CPU<NesBus>, header: Movie, checksum_interval: usize) -> Self {
+        cpu.power_on(RamPattern::Zeros);
+        let movie = Movie {
+            rom_checksum: rom_checksum(&cpu.bus.cartridge),
+            pal: cpu.bus.region() == Region::Pal,
+            fourscore: has_four_score(cpu),
+            frames: Vec::new(),
+            checksums: BTreeMap::new(),
+            ..header
+        };
+        Recorder { movie, checksum_interval }
+    }
+
+    /// Applies `input`, runs one frame and records it.
+    pub fn frame(&mut self, cpu: &mut CPU<NesBus>, input: MovieFrame) -> Result<bool, CPUError> {
+        let mut input = input;
+        let pads = self.movie.pads();
+        for (player, buttons) in input.pads.iter_mut().enumerate() {
+            if !pads.get(player).copied().unwrap_or(false) {
+                *buttons = Buttons::empty();
+            }
+        }
+
+        apply(cpu, &input);
+        let running = cpu.run_frame()?;
+
+        let index = self.movie.frames.len();
+        self.movie.frames.push(input);
+        if self.checksum_interval > 0 && (index + 1).is_multiple_of(self.checksum_interval) {
+            self.movie.checksums.insert(index, ram_checksum(cpu));
+        }
+        Ok(running)
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Playback {
+    Playing,
+    Finished,
+    /// Work RAM after `frame` does not match what was recorded.
+    Desync { frame: usize, expected: u32, actual: u32 },
+}
+
+/// Replays a movie frame by frame, starting from power-on.
+pub struct Player {
+    movie: Movie,
+    position: usize,
+}
+
+impl Player {
+    /// Powers the console on for the movie: NTSC or PAL as `palFlag` says,
+    /// with two controllers or a Four Score as the movie was recorded with,
+    /// whatever was set up before.
+    ///
+    /// Fails if the movie's `romChecksum` names another ROM; a movie
+    /// without one plays on any.
+    pub fn start(cpu: &mut CPU<NesBus>, movie: Movie) -> Result<Self, MovieError> {
+        let actual = rom_checksum(&cpu.bus.cartridge);
+        if !movie.rom_checksum.is_empty() && movie.rom_checksum != actual {
+            return Err(MovieError::RomMismatch { expected: movie.rom_checksum, actual });
+        }
+        cpu.bus.set_region(if movie.pal { Region::Pal } else { Region::Ntsc });
+        let layout = if movie.fourscore { InputLayout::FourScore } else { InputLayout::Standard };
+        cpu.bus.ports = layout.devices();
+        cpu.power_on(RamPattern::Zeros);
+        Ok(Player { movie, position: 0 })
+    }
+
+    /// The number of frames played so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Plays the next frame, checking RAM against the recording when the
+    /// movie has a checksum for it.
+    pub fn frame(&mut self, cpu: &mut CPU<NesBus>) -> Result<Playback, CPUError> {
+        let Some(input) = self.movie.frames.get(self.position).copied() else {
+            return Ok(Playback::Finished);
+        };
+
+        apply(cpu, &input);
+        cpu.run_frame()?;
+
+        let frame = self.position;
+        self.position += 1;
+        match self.movie.checksums.get(&frame) {
+            Some(&expected) => {
+                let actual = ram_checksum(cpu);
+                if actual != expected {
+                    return Ok(Playback::Desync { frame, expected, actual });
+                }
+                Ok(Playback::Playing)
+            }
+            None => Ok(Playback::Playing),
+        }
+    }
+
+    /// Plays to the end, stopping early at the first desync.
+    pub fn play_to_end(&mut self, cpu: &mut CPU<NesBus>) -> Result<Playback, CPUError> {
+        loop {
+            match self.frame(cpu)? {
+                Playback::Playing => {}
+                result => return Ok(result),
+            }
+        }
+    }
+}
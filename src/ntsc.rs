@@ -0,0 +1,208 @@
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Width of the filtered picture: every 3 NES pixels become 7 output
+/// pixels, as in blargg's nes_ntsc.
+pub const NTSC_OUT_WIDTH: usize = (SCREEN_WIDTH - 1) / 3 * 7 + 7;
+
+/// The PPU emits 8 samples of its composite signal per pixel, at 12
+/// samples per cycle of the color subcarrier.
+const SAMPLES_PER_PIXEL: usize = 8;
+const SAMPLES_PER_CYCLE: usize = 12;
+
+/// Signal voltages relative to sync for each brightness column, at the low
+/// and the high half of the chroma square wave.
+const LEVELS_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const LEVELS_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+/// How much an emphasis bit attenuates the signal while it is active.
+const ATTENUATION: f32 = 0.746;
+/// Where colorburst falls relative to the PPU's sample phases, which sets
+/// the hue of every color.
+const BURST_OFFSET: f32 = 3.8;
+/// Unfiltered border on each side of the picture.
+const BORDER_PIXELS: usize = 1;
+const BORDER_COLOR: u16 = 0x0F;
+
+/// The composite level, black at 0 and white at 1, that the PPU puts out
+/// for `pixel` (palette index plus emphasis, as in the framebuffer) at one
+/// of the 12 subcarrier phases.
+pub(crate) fn signal(pixel: u16, phase: usize) -> f32 {
+    let color = (pixel & 0x0F) as usize;
+    let emphasis = pixel >> 6;
+    // columns $E and $F output the $1D level
+    let level = if color > 0x0D { 1 } else { (pixel >> 4 & 0b11) as usize };
+
+    let in_phase = |color: usize| (color + phase) % SAMPLES_PER_CYCLE < 6;
+    let high = if color > 0x0C { LEVELS_LOW[level] } else { LEVELS_HIGH[level] };
+    let low = if color == 0 { LEVELS_HIGH[level] } else { LEVELS_LOW[level] };
+    let mut voltage = if in_phase(color) { high } else { low };
+
+    // each emphasis bit darkens the part of the wave opposite its hue
+    if (emphasis & 0b001 != 0 && in_phase(0))
+        || (emphasis & 0b010 != 0 && in_phase(4))
+        || (emphasis & 0b100 != 0 && in_phase(8)) {
+        voltage *= ATTENUATION;
+    }
+    (voltage - BLACK) / (WHITE - BLACK)
+}
+
+/// The average level of `pixel`'s signal, which is how bright it looks.
+pub(crate) fn luma(pixel: u16) -> f32 {
+    (0..SAMPLES_PER_CYCLE).map(|phase| signal(pixel, phase)).sum::<f32>() / SAMPLES_PER_CYCLE as f32
+}
+
+/// The I and Q demodulation weights for a sample at `phase`.
+fn carrier(phase: usize, hue: f32) -> (f32, f32) {
+    let angle = std::f32::consts::PI * (phase as f32 + BURST_OFFSET) / 6.0 + hue.to_radians();
+    (angle.cos(), angle.sin())
+}
+
+pub(crate) fn yiq_to_rgb(y: f32, i: f32, q: f32) -> [u8; 3] {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [
+        channel(y + 0.956 * i + 0.621 * q),
+        channel(y - 0.272 * i - 0.647 * q),
+        channel(y - 1.106 * i + 1.703 * q),
+    ]
+}
+
+/// Decodes `pixel` as a TV would a large area of it: the average over one
+/// subcarrier cycle, which is free of artifacts.
+pub(crate) fn decode(pixel: u16) -> [u8; 3] {
+    let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+    for phase in 0..SAMPLES_PER_CYCLE {
+        let level = signal(pixel, phase);
+        let (cos, sin) = carrier(phase, 0.0);
+        y += level;
+        i += level * cos;
+        q += level * sin;
+    }
+    let n = SAMPLES_PER_CYCLE as f32;
+    yiq_to_rgb(y / n, 2.0 * i / n, 2.0 * q / n)
+}
+
+/// Picture controls for [`NtscFilter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NtscSetup {
+    /// Rotation of every hue, in degrees.
+    pub hue: f32,
+    /// 1.0 is normal; 0.0 gives a monochrome picture.
+    pub saturation: f32,
+    /// Added to every level; 0.0 is normal.
+    pub brightness: f32,
+    /// Scales every level; 1.0 is normal.
+    pub contrast: f32,
+    /// Averages each frame with the next burst phase, which hides the
+    /// crawling dot pattern at the cost of some blur.
+    pub merge_fields: bool,
+}
+
+impl Default for NtscSetup {
+    fn default() -> Self {
+        NtscSetup {
+            hue: 0.0,
+            saturation: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+            merge_fields: false,
+        }
+    }
+}
+
+/// Renders the framebuffer the way it looks over composite video: each
+/// line is turned into the PPU's signal and decoded again, so fine detail
+/// bleeds into color fringes and dot crawl like on a real TV.
+pub struct NtscFilter {
+    setup: NtscSetup,
+    /// The signal level of each of the 512 pixel values at each phase.
+    levels: Vec<[f32; SAMPLES_PER_CYCLE]>,
+    /// I and Q demodulation weights at each phase, hue applied.
+    carriers: [(f32, f32); SAMPLES_PER_CYCLE],
+}
+
+impl Default for NtscFilter {
+    fn default() -> Self {
+        Self::new(NtscSetup::default())
+    }
+}
+
+impl NtscFilter {
+    pub fn new(setup: NtscSetup) -> Self {
+        let levels = (0..512)
+            .map(|pixel| std::array::from_fn(|phase| signal(pixel, phase)))
+            .collect();
+        NtscFilter {
+            setup,
+            levels,
+            carriers: std::array::from_fn(|phase| carrier(phase, setup.hue)),
+        }
+    }
+
+    pub fn setup(&self) -> NtscSetup {
+        self.setup
+    }
+
+    /// Filters a whole frame into `NTSC_OUT_WIDTH` x `SCREEN_HEIGHT` pixels
+    /// of 0x00RRGGBB. `burst_phase` (0-2) is where the subcarrier starts the
+    /// frame; advancing it every frame, e.g. with `ppu.frame % 3`, makes the
+    /// artifacts crawl as they do on hardware.
+    pub fn render(&self, frame: &[u16], burst_phase: u8) -> Vec<u32> {
+        let mut out = vec![0; NTSC_OUT_WIDTH * SCREEN_HEIGHT];
+        for (y, line) in frame.chunks_exact(SCREEN_WIDTH).enumerate().take(SCREEN_HEIGHT) {
+            let row = &mut out[y * NTSC_OUT_WIDTH..(y + 1) * NTSC_OUT_WIDTH];
+            self.render_line(line, burst_phase as usize + y, row);
+            if self.setup.merge_fields {
+                let mut next = vec![0; NTSC_OUT_WIDTH];
+                self.render_line(line, burst_phase as usize + y + 1, &mut next);
+                for (pixel, other) in row.iter_mut().zip(next) {
+                    *pixel = average(*pixel, other);
+                }
+            }
+        }
+        out
+    }
+
+    /// A scanline is 341 dots of 8 samples, which leaves the subcarrier a
+    /// third of a cycle further along at the start of each line.
+    fn render_line(&self, line: &[u16], line_phase: usize, out: &mut [u32]) {
+        let start_phase = line_phase % 3 * 4;
+        let pixels = BORDER_PIXELS * 2 + SCREEN_WIDTH;
+        let samples = pixels * SAMPLES_PER_PIXEL;
+
+        // running sums of the luma and the demodulated chroma
+        let mut sums = vec![(0.0f32, 0.0f32, 0.0f32); samples + 1];
+        for n in 0..samples {
+            let x = n / SAMPLES_PER_PIXEL;
+            let pixel = match x.checked_sub(BORDER_PIXELS).and_then(|x| line.get(x)) {
+                Some(&pixel) => pixel,
+                None => BORDER_COLOR,
+            };
+            let phase = (start_phase + n) % SAMPLES_PER_CYCLE;
+            let level = self.levels[pixel as usize & 0x1FF][phase];
+            let (cos, sin) = self.carriers[phase];
+            let (y, i, q) = sums[n];
+            sums[n + 1] = (y + level, i + level * cos, q + level * sin);
+        }
+
+        // each output pixel sees one subcarrier cycle of signal around it
+        let n = SAMPLES_PER_CYCLE as f32;
+        for (x, pixel) in out.iter_mut().enumerate() {
+            let center = ((x * 2 + 1) * samples) / (NTSC_OUT_WIDTH * 2);
+            let start = center.saturating_sub(SAMPLES_PER_CYCLE / 2);
+            let end = (start + SAMPLES_PER_CYCLE).min(samples);
+            let (y0, i0, q0) = sums[start];
+            let (y1, i1, q1) = sums[end];
+
+            let chroma = 2.0 * self.setup.saturation / n;
+            let y = (y1 - y0) / n * self.setup.contrast + self.setup.brightness;
+            let [r, g, b] = yiq_to_rgb(y, (i1 - i0) * chroma, (q1 - q0) * chroma);
+            *pixel = u32::from_be_bytes([0, r, g, b]);
+        }
+    }
+}
+
+fn average(a: u32, b: u32) -> u32 {
+    // halve each channel first so they cannot carry into each other
+    (a >> 1 & 0x7F7F7F) + (b >> 1 & 0x7F7F7F) + (a & b & 0x010101)
+}
@@ -68,8 +68,8 @@ lazy_static! {
         OpCode::new(0x31, "AND", 2, 5, AddressingMode::IndirectY),
 
         OpCode::new(0x0A, "ASL", 1, 2, AddressingMode::Accumulator),
-        OpCode::new(0x06, "ASL", 1, 2, AddressingMode::ZeroPage),
-        OpCode::new(0x16, "ASL", 2, 5, AddressingMode::ZeroPageX),
+        OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPageX),
         OpCode::new(0x0E, "ASL", 3, 6, AddressingMode::Absolute),
         OpCode::new(0x1E, "ASL", 3, 7, AddressingMode::AbsoluteX),
 
@@ -133,6 +133,98 @@ lazy_static! {
         OpCode::new(0xC8, "INY", 1, 2, AddressingMode::Implied),
 
         OpCode::new(0x4C, "JMP", 3, 3, AddressingMode::Absolute),
-        OpCode::new(0x6C, "JMP", 3, 5, AddressingMode::Indirect)
+        OpCode::new(0x6C, "JMP", 3, 5, AddressingMode::Indirect),
+
+        OpCode::new(0x20, "JSR", 3, 6, AddressingMode::Absolute),
+
+        OpCode::new(0xA2, "LDX", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xA6, "LDX", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xB6, "LDX", 2, 4, AddressingMode::ZeroPageY),
+        OpCode::new(0xAE, "LDX", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xBE, "LDX", 3, 4, AddressingMode::AbsoluteY),
+
+        OpCode::new(0xA0, "LDY", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xA4, "LDY", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xB4, "LDY", 2, 4, AddressingMode::ZeroPageX),
+        OpCode::new(0xAC, "LDY", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xBC, "LDY", 3, 4, AddressingMode::AbsoluteX),
+
+        OpCode::new(0x4A, "LSR", 1, 2, AddressingMode::Accumulator),
+        OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPageX),
+        OpCode::new(0x4E, "LSR", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x5E, "LSR", 3, 7, AddressingMode::AbsoluteX),
+
+        OpCode::new(0xEA, "NOP", 1, 2, AddressingMode::Implied),
+
+        OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPageX),
+        OpCode::new(0x0D, "ORA", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0x1D, "ORA", 3, 4, AddressingMode::AbsoluteX),
+        OpCode::new(0x19, "ORA", 3, 4, AddressingMode::AbsoluteY),
+        OpCode::new(0x01, "ORA", 2, 6, AddressingMode::IndirectX),
+        OpCode::new(0x11, "ORA", 2, 5, AddressingMode::IndirectY),
+
+        OpCode::new(0x48, "PHA", 1, 3, AddressingMode::Implied),
+        OpCode::new(0x08, "PHP", 1, 3, AddressingMode::Implied),
+        OpCode::new(0x68, "PLA", 1, 4, AddressingMode::Implied),
+        OpCode::new(0x28, "PLP", 1, 4, AddressingMode::Implied),
+
+        OpCode::new(0x2A, "ROL", 1, 2, AddressingMode::Accumulator),
+        OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPageX),
+        OpCode::new(0x2E, "ROL", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x3E, "ROL", 3, 7, AddressingMode::AbsoluteX),
+
+        OpCode::new(0x6A, "ROR", 1, 2, AddressingMode::Accumulator),
+        OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),
+        OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPageX),
+        OpCode::new(0x6E, "ROR", 3, 6, AddressingMode::Absolute),
+        OpCode::new(0x7E, "ROR", 3, 7, AddressingMode::AbsoluteX),
+
+        OpCode::new(0x40, "RTI", 1, 6, AddressingMode::Implied),
+        OpCode::new(0x60, "RTS", 1, 6, AddressingMode::Implied),
+
+        OpCode::new(0xE9, "SBC", 2, 2, AddressingMode::Immediate),
+        OpCode::new(0xE5, "SBC", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0xF5, "SBC", 2, 4, AddressingMode::ZeroPageX),
+        OpCode::new(0xED, "SBC", 3, 4, AddressingMode::Absolute),
+        OpCode::new(0xFD, "SBC", 3, 4, AddressingMode::AbsoluteX),
+        OpCode::new(0xF9, "SBC", 3, 4, AddressingMode::AbsoluteY),
+        OpCode::new(0xE1, "SBC", 2, 6, AddressingMode::IndirectX),
+        OpCode::new(0xF1, "SBC", 2, 5, AddressingMode::IndirectY),
+
+        OpCode::new(0x38, "SEC", 1, 2, AddressingMode::Implied),
+        OpCode::new(0xF8, "SED", 1, 2, AddressingMode::Implied),
+        OpCode::new(0x78, "SEI", 1, 2, AddressingMode::Implied),
+
+        OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPageY),
+        OpCode::new(0x8E, "STX", 3, 4, AddressingMode::Absolute),
+
+        OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage),
+        OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPageX),
+        OpCode::new(0x8C, "STY", 3, 4, AddressingMode::Absolute),
+
+        OpCode::new(0xA8, "TAY", 1, 2, AddressingMode::Implied),
+        OpCode::new(0xBA, "TSX", 1, 2, AddressingMode::Implied),
+        OpCode::new(0x8A, "TXA", 1, 2, AddressingMode::Implied),
+        OpCode::new(0x9A, "TXS", 1, 2, AddressingMode::Implied),
+        OpCode::new(0x98, "TYA", 1, 2, AddressingMode::Implied),
     ];
+
+    static ref OPCODES_BY_BYTE: [Option<&'static OpCode<'static>>; 256] = {
+        let mut table = [None; 256];
+        for op in CPU_OPCODES.iter() {
+            table[op.opcode as usize] = Some(op);
+        }
+        table
+    };
+}
+
+/// Looks up the table entry for an opcode byte, `None` for the opcodes the
+/// 6502 does not officially define.
+pub fn lookup(code: u8) -> Option<&'static OpCode<'static>> {
+    OPCODES_BY_BYTE[code as usize]
 }
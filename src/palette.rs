@@ -0,0 +1,80 @@
+use crate::{error::PaletteError, ntsc};
+
+/// Colors in a palette: the 64 palette indices under each of the 8
+/// combinations of PPUMASK's emphasis bits.
+pub const PALETTE_ENTRIES: usize = 512;
+
+/// How much an emphasis bit dims the channels it does not emphasize, for
+/// palettes that only give the 64 base colors.
+const EMPHASIS_DIMMING: f32 = 0.746;
+
+/// RGB colors for the values the PPU outputs: a palette index in bits 0-5
+/// and the emphasis bits (red, green, blue) in bits 6-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::ntsc()
+    }
+}
+
+impl Palette {
+    /// The colors a TV decodes from the NTSC PPU's composite signal, which
+    /// is what `Palette::default()` gives.
+    pub fn ntsc() -> Self {
+        Palette {
+            colors: (0..PALETTE_ENTRIES as u16).map(ntsc::decode).collect(),
+        }
+    }
+
+    /// Reads a `.pal` file: 64 RGB triples, or 512 that also cover every
+    /// emphasis combination in PPUMASK order. The emphasized colors of a
+    /// 64-color file are made by dimming the other channels.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PaletteError> {
+        let colors: Vec<[u8; 3]> = data.chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+        match data.len() {
+            192 => Ok(Palette {
+                colors: (0..PALETTE_ENTRIES)
+                    .map(|pixel| emphasize(colors[pixel & 0x3F], pixel >> 6))
+                    .collect(),
+            }),
+            1536 => Ok(Palette { colors }),
+            len => Err(PaletteError::InvalidSize(len)),
+        }
+    }
+
+    /// The 512-color `.pal` form of the palette.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.colors.concat()
+    }
+
+    /// The color of one framebuffer pixel.
+    pub fn rgb(&self, pixel: u16) -> [u8; 3] {
+        self.colors[pixel as usize % PALETTE_ENTRIES]
+    }
+
+    /// Converts a framebuffer to 0x00RRGGBB pixels.
+    pub fn render(&self, frame: &[u16]) -> Vec<u32> {
+        frame.iter()
+            .map(|&pixel| {
+                let [r, g, b] = self.rgb(pixel);
+                u32::from_be_bytes([0, r, g, b])
+            })
+            .collect()
+    }
+}
+
+fn emphasize(rgb: [u8; 3], emphasis: usize) -> [u8; 3] {
+    let mut rgb = rgb;
+    for (channel, value) in rgb.iter_mut().enumerate() {
+        if emphasis & !(1 << channel) != 0 {
+            *value = (*value as f32 * EMPHASIS_DIMMING).round() as u8;
+        }
+    }
+    rgb
+}
@@ -0,0 +1,588 @@
+use crate::{
+    cartridge::{Cartridge, Mirroring},
+    error::SaveStateError,
+    region::Region,
+    savestate::{Chunk, ChunkReader},
+    statehash::StateHasher,
+};
+
+/// The PPU as seen through its CPU-facing registers at $2000-$2007.
+pub struct PPU {
+    pub ctrl: u8,
+    pub mask: u8,
+    pub status: u8,
+    pub oam_addr: u8,
+    pub oam: [u8; 256],
+    pub vram: [u8; 0x1000],
+    pub palette: [u8; 32],
+    /// Current VRAM address (loopy `v`).
+    pub v: u16,
+    /// Temporary VRAM address (loopy `t`).
+    pub t: u16,
+    pub fine_x: u8,
+    /// First/second write toggle shared by $2005 and $2006.
+    pub w: bool,
+    pub data_buffer: u8,
+    /// 0-239 visible, then post-render lines and vblank; the last line of
+    /// the frame is the pre-render line. On NTSC vblank is 241-260 and the
+    /// pre-render line 261.
+    pub scanline: u16,
+    pub dot: u16,
+    pub frame: u64,
+    /// Sets the number of scanlines and where vblank falls.
+    pub region: Region,
+    /// Set when vblank starts with NMI enabled; the bus hands it to the CPU.
+    pub nmi_pending: bool,
+    /// The capacitance on the PPU's data lines, which holds the last value
+    /// written to or read from any register. Reads of write-only registers
+    /// and of the bits a register does not drive return it.
+    pub io_latch: u8,
+    /// The frame in which each latch bit was last driven.
+    latch_frames: [u64; 8],
+    /// The picture as the PPU outputs it, row by row: each pixel holds a
+    /// palette index (0-63) in bits 0-5 and the color emphasis in effect in
+    /// bits 6-8 (red, green, blue), the form [`Palette`] and [`NtscFilter`]
+    /// take. Each line is drawn when the PPU reaches its end.
+    ///
+    /// [`Palette`]: crate::palette::Palette
+    /// [`NtscFilter`]: crate::ntsc::NtscFilter
+    pub frame_buffer: Vec<u16>,
+}
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+/// Frames a latch bit holds a 1 without being refreshed, about 600 ms.
+const LATCH_DECAY_FRAMES: u64 = 36;
+
+const DOTS_PER_SCANLINE: u16 = 341;
+
+const STATUS_VBLANK: u8 = 0b1000_0000;
+const STATUS_SPRITE_0_HIT: u8 = 0b0100_0000;
+const STATUS_SPRITE_OVERFLOW: u8 = 0b0010_0000;
+const CTRL_NMI: u8 = 0b1000_0000;
+const CTRL_TALL_SPRITES: u8 = 0b0010_0000;
+const CTRL_BACKGROUND_TABLE: u8 = 0b0001_0000;
+const CTRL_SPRITE_TABLE: u8 = 0b0000_1000;
+const MASK_EMPHASIS: u8 = 0b1110_0000;
+const MASK_RENDERING: u8 = 0b0001_1000;
+const MASK_SPRITES: u8 = 0b0001_0000;
+const MASK_BACKGROUND: u8 = 0b0000_1000;
+const MASK_SPRITES_LEFT: u8 = 0b0000_0100;
+const MASK_BACKGROUND_LEFT: u8 = 0b0000_0010;
+const MASK_GRAYSCALE: u8 = 0b0000_0001;
+
+/// Sprite attribute bits.
+const SPRITE_FLIP_V: u8 = 0b1000_0000;
+const SPRITE_FLIP_H: u8 = 0b0100_0000;
+const SPRITE_BEHIND: u8 = 0b0010_0000;
+
+impl Default for PPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PPU {
+    pub fn new() -> Self {
+        PPU {
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_addr: 0,
+            oam: [0; 256],
+            vram: [0; 0x1000],
+            palette: [0; 32],
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            w: false,
+            data_buffer: 0,
+            scanline: 0,
+            dot: 0,
+            frame: 0,
+            region: Region::Ntsc,
+            nmi_pending: false,
+            io_latch: 0,
+            latch_frames: [0; 8],
+            frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    pub fn power_on(&mut self) {
+        self.ctrl = 0;
+        self.mask = 0;
+        // vblank and sprite overflow are usually found set after power-up
+        self.status = 0b1010_0000;
+        self.oam_addr = 0;
+        self.v = 0;
+        self.t = 0;
+        self.fine_x = 0;
+        self.w = false;
+        self.data_buffer = 0;
+        self.scanline = 0;
+        self.dot = 0;
+        self.frame = 0;
+        self.nmi_pending = false;
+        self.io_latch = 0;
+        self.latch_frames = [0; 8];
+    }
+
+    /// The reset line only reaches part of the PPU: PPUCTRL, PPUMASK, the
+    /// scroll registers, the write toggle and the read buffer are cleared,
+    /// while PPUSTATUS, OAMADDR, PPUADDR and all memories keep their contents.
+    pub fn reset(&mut self) {
+        self.ctrl = 0;
+        self.mask = 0;
+        self.t = 0;
+        self.fine_x = 0;
+        self.w = false;
+        self.data_buffer = 0;
+    }
+
+    pub fn read_register(&mut self, addr: u16, cartridge: &mut Cartridge) -> u8 {
+        match addr & 0x0007 {
+            2 => {
+                // only the top three bits are driven
+                let status = self.status;
+                self.status &= !STATUS_VBLANK;
+                self.w = false;
+                self.drive_latch(status, 0b1110_0000)
+            }
+            4 => self.drive_latch(self.oam[self.oam_addr as usize], 0xFF),
+            7 => {
+                let addr = self.v & 0x3FFF;
+                self.increment_vram_addr();
+
+                if addr >= 0x3F00 {
+                    // palette reads bypass the buffer, which is refilled with
+                    // the nametable byte "underneath" the palette; palette
+                    // entries are six bits wide
+                    self.data_buffer = self.read_vram(addr - 0x1000, cartridge);
+                    let color = self.read_vram(addr, cartridge);
+                    self.drive_latch(color, 0b0011_1111)
+                } else {
+                    let buffered = self.data_buffer;
+                    self.data_buffer = self.read_vram(addr, cartridge);
+                    self.drive_latch(buffered, 0xFF)
+                }
+            }
+            // write-only registers
+            _ => self.io_latch,
+        }
+    }
+
+    /// Puts the `mask` bits of `data` on the latch, refreshing them, and
+    /// returns the byte the CPU sees.
+    fn drive_latch(&mut self, data: u8, mask: u8) -> u8 {
+        self.io_latch = (self.io_latch & !mask) | (data & mask);
+        for (bit, frame) in self.latch_frames.iter_mut().enumerate() {
+            if mask & (1 << bit) != 0 {
+                *frame = self.frame;
+            }
+        }
+        self.io_latch
+    }
+
+    /// Lets the latch bits that have gone unrefreshed for too long leak
+    /// away to 0.
+    fn decay_latch(&mut self) {
+        for (bit, &frame) in self.latch_frames.iter().enumerate() {
+            if self.frame.saturating_sub(frame) >= LATCH_DECAY_FRAMES {
+                self.io_latch &= !(1 << bit);
+            }
+        }
+    }
+
+    pub fn peek_register(&self, addr: u16) -> u8 {
+        match addr & 0x0007 {
+            2 => (self.status & 0b1110_0000) | (self.io_latch & 0b0001_1111),
+            4 => self.oam[self.oam_addr as usize],
+            7 => self.data_buffer,
+            _ => self.io_latch,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8, cartridge: &mut Cartridge) {
+        self.drive_latch(data, 0xFF);
+        match addr & 0x0007 {
+            0 => {
+                // enabling NMI during vblank raises one straight away
+                if self.ctrl & CTRL_NMI == 0 && data & CTRL_NMI != 0 && self.status & STATUS_VBLANK != 0 {
+                    self.nmi_pending = true;
+                }
+                self.ctrl = data;
+                self.t = (self.t & 0xF3FF) | ((data as u16 & 0b11) << 10);
+            }
+            1 => self.mask = data,
+            3 => self.oam_addr = data,
+            4 => {
+                self.oam[self.oam_addr as usize] = data;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            5 => {
+                if !self.w {
+                    self.t = (self.t & 0xFFE0) | (data as u16 >> 3);
+                    self.fine_x = data & 0b111;
+                } else {
+                    self.t = (self.t & 0x8C1F)
+                        | ((data as u16 & 0b111) << 12)
+                        | ((data as u16 & 0b1111_1000) << 2);
+                }
+                self.w = !self.w;
+            }
+            6 => {
+                if !self.w {
+                    self.t = (self.t & 0x00FF) | ((data as u16 & 0x3F) << 8);
+                } else {
+                    self.t = (self.t & 0xFF00) | data as u16;
+                    self.v = self.t;
+                }
+                self.w = !self.w;
+            }
+            7 => {
+                let addr = self.v & 0x3FFF;
+                self.write_vram(addr, data, cartridge);
+                self.increment_vram_addr();
+            }
+            // PPUSTATUS is read-only
+            _ => {}
+        }
+    }
+
+    /// Advances the dot clock, raising vblank (and NMI) at the start of the
+    /// region's vblank scanline and clearing the status flags on the
+    /// pre-render line. Visible lines are drawn into `frame_buffer` at dot 256.
+    pub fn tick(&mut self, dots: u32, cartridge: &mut Cartridge) {
+        let pre_render = self.pre_render_scanline();
+        for _ in 0..dots {
+            self.dot += 1;
+
+            // with rendering on, odd NTSC frames skip the last pre-render dot
+            let skip = self.scanline == pre_render
+                && self.dot == DOTS_PER_SCANLINE - 1
+                && self.frame % 2 == 1
+                && self.mask & MASK_RENDERING != 0
+                && self.region.skips_odd_frame_dot();
+
+            if self.dot == DOTS_PER_SCANLINE || skip {
+                self.dot = 0;
+                self.scanline += 1;
+                if self.scanline > pre_render {
+                    self.scanline = 0;
+                    self.frame += 1;
+                    self.decay_latch();
+                }
+            }
+
+            if self.dot == 1 {
+                if self.scanline == self.region.vblank_scanline() {
+                    self.status |= STATUS_VBLANK;
+                    if self.ctrl & CTRL_NMI != 0 {
+                        self.nmi_pending = true;
+                    }
+                } else if self.scanline == pre_render {
+                    // vblank, sprite 0 hit and sprite overflow
+                    self.status &= 0b0001_1111;
+                }
+            }
+
+            let visible = (self.scanline as usize) < SCREEN_HEIGHT;
+            if self.mask & MASK_RENDERING == 0 {
+                if visible && self.dot == 256 {
+                    let backdrop = self.output_pixel(self.palette[0]);
+                    self.frame_line(self.scanline as usize).fill(backdrop);
+                }
+                continue;
+            }
+            match (visible || self.scanline == pre_render, self.dot) {
+                (true, 256) => {
+                    if visible {
+                        self.render_scanline(cartridge);
+                    }
+                    self.increment_y();
+                }
+                // horizontal scroll is reloaded from `t` after every line
+                (true, 257) => self.v = (self.v & !0x041F) | (self.t & 0x041F),
+                // and vertical scroll once per frame, on the pre-render line
+                (true, 280) if !visible => self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0),
+                _ => {}
+            }
+        }
+    }
+
+    fn pre_render_scanline(&self) -> u16 {
+        self.region.scanlines() - 1
+    }
+
+    fn frame_line(&mut self, y: usize) -> &mut [u16] {
+        &mut self.frame_buffer[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH]
+    }
+
+    /// Draws the current scanline from `v`, as the fetches during dots
+    /// 1-256 would, then composites the sprites found in OAM.
+    fn render_scanline(&mut self, cartridge: &mut Cartridge) {
+        let y = self.scanline as usize;
+
+        // 0 where transparent, otherwise the palette RAM index
+        let mut background = [0u8; SCREEN_WIDTH];
+        if self.mask & MASK_BACKGROUND != 0 {
+            let table = if self.ctrl & CTRL_BACKGROUND_TABLE != 0 { 0x1000 } else { 0 };
+            let fine_y = (self.v >> 12) & 0b111;
+            let mut v = self.v;
+            for tile in 0..33usize {
+                let index = self.vram[self.mirror_nametable(0x2000 | (v & 0x0FFF), cartridge.mirroring)];
+                let attribute_addr = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+                let attribute = self.vram[self.mirror_nametable(attribute_addr, cartridge.mirroring)];
+                let palette = (attribute >> (((v >> 4) & 0b100) | (v & 0b10))) & 0b11;
+
+                let addr = table + index as u16 * 16 + fine_y;
+                let low = cartridge.fetch_chr(addr, true);
+                let high = cartridge.fetch_chr(addr + 8, true);
+                for bit in 0..8 {
+                    let Some(x) = (tile * 8 + bit).checked_sub(self.fine_x as usize) else {
+                        continue;
+                    };
+                    let pixel = pattern_pixel(low, high, 7 - bit);
+                    if x < SCREEN_WIDTH && pixel != 0 {
+                        background[x] = palette << 2 | pixel;
+                    }
+                }
+
+                // coarse X, wrapping into the next nametable
+                if v & 0x001F == 31 {
+                    v = (v & !0x001F) ^ 0x0400;
+                } else {
+                    v += 1;
+                }
+            }
+            if self.mask & MASK_BACKGROUND_LEFT == 0 {
+                background[..8].fill(0);
+            }
+        }
+
+        // palette RAM index, behind the background, sprite 0
+        let mut sprites = [None::<(u8, bool, bool)>; SCREEN_WIDTH];
+        if self.mask & MASK_SPRITES != 0 {
+            let height = if self.ctrl & CTRL_TALL_SPRITES != 0 { 16 } else { 8 };
+            let mut found = 0;
+            for (i, sprite) in self.oam.chunks_exact(4).enumerate() {
+                // OAM holds the line above the sprite's top
+                let Some(row) = y.checked_sub(sprite[0] as usize + 1).filter(|&row| row < height) else {
+                    continue;
+                };
+                found += 1;
+                if found > 8 {
+                    self.status |= STATUS_SPRITE_OVERFLOW;
+                    break;
+                }
+
+                let (tile, attributes, left) = (sprite[1] as u16, sprite[2], sprite[3] as usize);
+                let row = if attributes & SPRITE_FLIP_V != 0 { height - 1 - row } else { row } as u16;
+                let addr = if height == 16 {
+                    (tile & 1) * 0x1000 + ((tile & 0xFE) + row / 8) * 16 + row % 8
+                } else {
+                    let table = if self.ctrl & CTRL_SPRITE_TABLE != 0 { 0x1000 } else { 0 };
+                    table + tile * 16 + row
+                };
+                let low = cartridge.fetch_chr(addr, true);
+                let high = cartridge.fetch_chr(addr + 8, true);
+
+                for bit in 0..8 {
+                    let x = left + bit;
+                    if x >= SCREEN_WIDTH {
+                        break;
+                    }
+                    let shift = if attributes & SPRITE_FLIP_H != 0 { bit } else { 7 - bit };
+                    let pixel = pattern_pixel(low, high, shift);
+                    // lower OAM indices win where sprites overlap
+                    if pixel != 0 && sprites[x].is_none() {
+                        let index = 0x10 | (attributes & 0b11) << 2 | pixel;
+                        sprites[x] = Some((index, attributes & SPRITE_BEHIND != 0, i == 0));
+                    }
+                }
+            }
+            if self.mask & MASK_SPRITES_LEFT == 0 {
+                sprites[..8].fill(None);
+            }
+        }
+
+        let mut line = [0u16; SCREEN_WIDTH];
+        for x in 0..SCREEN_WIDTH {
+            let index = match sprites[x] {
+                Some((sprite, behind, sprite_0)) => {
+                    if sprite_0 && background[x] != 0 && x != 255 {
+                        self.status |= STATUS_SPRITE_0_HIT;
+                    }
+                    if behind && background[x] != 0 { background[x] } else { sprite }
+                }
+                None => background[x],
+            };
+            line[x] = self.output_pixel(self.palette[mirror_palette(0x3F00 | index as u16)]);
+        }
+        self.frame_line(y).copy_from_slice(&line);
+    }
+
+    /// A palette RAM entry as it leaves the PPU: grayscale keeps only the
+    /// brightness column, and PPUMASK's emphasis bits ride along above it.
+    /// PAL and Dendy PPUs swap the red and green emphasis bits.
+    fn output_pixel(&self, color: u8) -> u16 {
+        let color = if self.mask & MASK_GRAYSCALE != 0 { color & 0x30 } else { color & 0x3F };
+        let mut emphasis = (self.mask & MASK_EMPHASIS) >> 5;
+        if self.region != Region::Ntsc {
+            emphasis = (emphasis & 0b100) | (emphasis & 0b01) << 1 | (emphasis & 0b10) >> 1;
+        }
+        color as u16 | (emphasis as u16) << 6
+    }
+
+    /// Fine Y, then coarse Y, moving down a nametable after row 29.
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let coarse_y = match (self.v & 0x03E0) >> 5 {
+            29 => {
+                self.v ^= 0x0800;
+                0
+            }
+            // rows 30 and 31 hold attributes but can still be scrolled into
+            31 => 0,
+            y => y + 1,
+        };
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    pub fn save_state(&self, chunk: &mut Chunk) {
+        chunk.write_u8(self.ctrl);
+        chunk.write_u8(self.mask);
+        chunk.write_u8(self.status);
+        chunk.write_u8(self.oam_addr);
+        chunk.write_bytes(&self.oam);
+        chunk.write_bytes(&self.vram);
+        chunk.write_bytes(&self.palette);
+        chunk.write_u16(self.v);
+        chunk.write_u16(self.t);
+        chunk.write_u8(self.fine_x);
+        chunk.write_bool(self.w);
+        chunk.write_u8(self.data_buffer);
+        chunk.write_u16(self.scanline);
+        chunk.write_u16(self.dot);
+        chunk.write_u64(self.frame);
+        chunk.write_bool(self.nmi_pending);
+        chunk.write_u8(self.io_latch);
+        for &frame in self.latch_frames.iter() {
+            chunk.write_u64(frame);
+        }
+    }
+
+    pub fn load_state(&mut self, chunk: &mut ChunkReader) -> Result<(), SaveStateError> {
+        self.ctrl = chunk.read_u8()?;
+        self.mask = chunk.read_u8()?;
+        self.status = chunk.read_u8()?;
+        self.oam_addr = chunk.read_u8()?;
+        chunk.read_into(&mut self.oam)?;
+        chunk.read_into(&mut self.vram)?;
+        chunk.read_into(&mut self.palette)?;
+        self.v = chunk.read_u16()?;
+        self.t = chunk.read_u16()?;
+        self.fine_x = chunk.read_u8()?;
+        self.w = chunk.read_bool()?;
+        self.data_buffer = chunk.read_u8()?;
+
+        // version 1 states predate the dot clock and start at the top of a frame
+        if chunk.remaining() == 0 {
+            self.scanline = 0;
+            self.dot = 0;
+            self.frame = 0;
+            self.nmi_pending = false;
+            self.io_latch = 0;
+            self.latch_frames = [0; 8];
+            return Ok(());
+        }
+        self.scanline = chunk.read_u16()?;
+        self.dot = chunk.read_u16()?;
+        self.frame = chunk.read_u64()?;
+        self.nmi_pending = chunk.read_bool()?;
+
+        // the I/O latch joined in version 5
+        if chunk.remaining() == 0 {
+            self.io_latch = 0;
+            self.latch_frames = [self.frame; 8];
+            return Ok(());
+        }
+        self.io_latch = chunk.read_u8()?;
+        for frame in self.latch_frames.iter_mut() {
+            *frame = chunk.read_u64()?;
+        }
+        Ok(())
+    }
+
+    pub fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&[self.ctrl, self.mask, self.status, self.oam_addr]);
+        hasher.write_bytes(&self.oam);
+        hasher.write_bytes(&self.vram);
+        hasher.write_bytes(&self.palette);
+        hasher.write_u16(self.v);
+        hasher.write_u16(self.t);
+        hasher.write_bytes(&[self.fine_x, self.w as u8, self.data_buffer]);
+        hasher.write_u16(self.scanline);
+        hasher.write_u16(self.dot);
+        hasher.write_u64(self.frame);
+        hasher.write_u8(self.io_latch);
+        for &frame in &self.latch_frames {
+            hasher.write_u64(frame);
+        }
+    }
+
+    fn increment_vram_addr(&mut self) {
+        let step = if self.ctrl & 0b0000_0100 != 0 { 32 } else { 1 };
+        self.v = self.v.wrapping_add(step) & 0x7FFF;
+    }
+
+    fn read_vram(&self, addr: u16, cartridge: &mut Cartridge) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => cartridge.fetch_chr(addr, false),
+            0x2000..=0x3EFF => self.vram[self.mirror_nametable(addr, cartridge.mirroring)],
+            _ => self.palette[mirror_palette(addr)],
+        }
+    }
+
+    fn write_vram(&mut self, addr: u16, data: u8, cartridge: &mut Cartridge) {
+        match addr {
+            0x0000..=0x1FFF => cartridge.write_chr(addr, data),
+            0x2000..=0x3EFF => {
+                let index = self.mirror_nametable(addr, cartridge.mirroring);
+                self.vram[index] = data;
+            }
+            _ => self.palette[mirror_palette(addr)] = data & 0x3F,
+        }
+    }
+
+    fn mirror_nametable(&self, addr: u16, mirroring: Mirroring) -> usize {
+        let offset = (addr - 0x2000) & 0x0FFF;
+        let table = offset / 0x0400;
+        let table = match (mirroring, table) {
+            (Mirroring::Horizontal, 0 | 1) => 0,
+            (Mirroring::Horizontal, _) => 1,
+            (Mirroring::Vertical, t) => t % 2,
+            (Mirroring::FourScreen, t) => t,
+        };
+        (table * 0x0400 + (offset & 0x03FF)) as usize
+    }
+}
+
+/// The 2-bit colour of one pixel from a pattern's two bit planes.
+fn pattern_pixel(low: u8, high: u8, shift: usize) -> u8 {
+    ((low >> shift) & 1) | ((high >> shift) & 1) << 1
+}
+
+fn mirror_palette(addr: u16) -> usize {
+    let index = (addr & 0x1F) as usize;
+    // $3F10/$3F14/$3F18/$3F1C mirror the background entries
+    match index {
+        0x10 | 0x14 | 0x18 | 0x1C => index - 0x10,
+        _ => index,
+    }
+}
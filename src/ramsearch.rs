@@ -0,0 +1,124 @@
+use std::ops::Range;
+use crate::{bus::Bus, compare::Comparison};
+
+const WORK_RAM: Range<u16> = 0x0000..0x0800;
+const PRG_RAM: Range<u16> = 0x6000..0x8000;
+
+/// What a candidate's current value is compared with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    /// Its value when the search last looked.
+    Previous,
+    /// A fixed value.
+    Value(u8),
+    /// Its previous value plus a (wrapping) amount.
+    Delta(i16),
+}
+
+/// A relation every remaining candidate must satisfy, read as
+/// `current <comparison> <operand>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub comparison: Comparison,
+    pub operand: Operand,
+}
+
+impl Filter {
+    pub fn new(comparison: Comparison, operand: Operand) -> Self {
+        Filter { comparison, operand }
+    }
+    pub fn changed() -> Self {
+        Filter::new(Comparison::NotEqual, Operand::Previous)
+    }
+    pub fn unchanged() -> Self {
+        Filter::new(Comparison::Equal, Operand::Previous)
+    }
+    pub fn equal_to(value: u8) -> Self {
+        Filter::new(Comparison::Equal, Operand::Value(value))
+    }
+    pub fn increased_by(amount: u8) -> Self {
+        Filter::new(Comparison::Equal, Operand::Delta(amount as i16))
+    }
+    pub fn decreased_by(amount: u8) -> Self {
+        Filter::new(Comparison::Equal, Operand::Delta(-(amount as i16)))
+    }
+    pub fn increased() -> Self {
+        Filter::new(Comparison::Greater, Operand::Previous)
+    }
+    pub fn decreased() -> Self {
+        Filter::new(Comparison::Less, Operand::Previous)
+    }
+
+    fn matches(&self, current: u8, previous: u8) -> bool {
+        let operand = match self.operand {
+            Operand::Previous => previous,
+            Operand::Value(value) => value,
+            Operand::Delta(delta) => previous.wrapping_add(delta as u8),
+        };
+        self.comparison.test(current as u16, operand as u16)
+    }
+}
+
+/// An address still in the running, with its value when last looked at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    pub address: u16,
+    pub value: u8,
+}
+
+/// Narrows down where a game keeps a variable, such as a lives counter, by
+/// repeatedly comparing RAM with how it looked before.
+///
+/// Start a search, let the game run until the variable changes in a known
+/// way, then filter with the matching relation; a few rounds usually leave a
+/// handful of addresses. Memory is read with `Bus::peek_ram`, so searching
+/// has no effect on the game and sees past any cheats.
+pub struct RamSearch {
+    candidates: Vec<Candidate>,
+}
+
+impl RamSearch {
+    /// Starts with every byte of work RAM, plus the cartridge's PRG-RAM at
+    /// $6000-$7FFF when `include_prg_ram` is set.
+    pub fn new<B: Bus>(bus: &B, include_prg_ram: bool) -> Self {
+        let mut addresses: Vec<u16> = WORK_RAM.collect();
+        if include_prg_ram {
+            addresses.extend(PRG_RAM);
+        }
+        let candidates = addresses.into_iter()
+            .map(|address| Candidate { address, value: bus.peek_ram(address) })
+            .collect();
+        RamSearch { candidates }
+    }
+
+    /// Drops the candidates whose current value fails `filter`, then takes
+    /// the current values as the new baseline. Returns how many are left.
+    pub fn filter<B: Bus>(&mut self, bus: &B, filter: Filter) -> usize {
+        self.candidates.retain_mut(|candidate| {
+            let current = bus.peek_ram(candidate.address);
+            let keep = filter.matches(current, candidate.value);
+            candidate.value = current;
+            keep
+        });
+        self.candidates.len()
+    }
+
+    /// Takes the current values as the baseline without filtering.
+    pub fn update<B: Bus>(&mut self, bus: &B) {
+        for candidate in &mut self.candidates {
+            candidate.value = bus.peek_ram(candidate.address);
+        }
+    }
+
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
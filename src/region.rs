@@ -0,0 +1,167 @@
+use std::fmt;
+
+/// The console variants, which share a CPU and PPU design but run them
+/// from different clocks and with different video timing.
+///
+/// | region | master clock  | CPU  | PPU | scanlines | vblank starts |
+/// |--------|---------------|------|-----|-----------|---------------|
+/// | NTSC   | 21.477272 MHz | /12  | /4  | 262       | 241           |
+/// | PAL    | 26.601712 MHz | /16  | /5  | 312       | 241           |
+/// | Dendy  | 26.601712 MHz | /15  | /5  | 312       | 291           |
+///
+/// Dendy, a Famicom clone sold in Russia, pairs PAL video with an APU that
+/// counts like the NTSC one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+const NTSC_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_NOISE_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+const NTSC_DMC_RATES: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_DMC_RATES: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// CPU cycles at which the frame counter reaches each step; 4-step mode
+/// wraps after the fourth, 5-step mode after the fifth.
+const NTSC_FRAME_STEPS: [u32; 5] = [7457, 14913, 22371, 29829, 37281];
+const PAL_FRAME_STEPS: [u32; 5] = [8313, 16627, 24939, 33253, 41565];
+
+impl Region {
+    pub const ALL: [Region; 3] = [Region::Ntsc, Region::Pal, Region::Dendy];
+
+    /// Parses a region name as given on the command line, e.g. `pal`.
+    pub fn parse(name: &str) -> Option<Region> {
+        match name.to_ascii_lowercase().as_str() {
+            "ntsc" => Some(Region::Ntsc),
+            "pal" => Some(Region::Pal),
+            "dendy" => Some(Region::Dendy),
+            _ => None,
+        }
+    }
+
+    /// The region a NES 2.0 header asks for in the CPU/PPU timing byte, or
+    /// `None` for images that run on any console.
+    pub fn from_nes2_timing(timing: u8) -> Option<Region> {
+        match timing & 0b11 {
+            0 => Some(Region::Ntsc),
+            1 => Some(Region::Pal),
+            3 => Some(Region::Dendy),
+            _ => None,
+        }
+    }
+
+    pub fn master_clock_hz(&self) -> f64 {
+        match self {
+            Region::Ntsc => 236.25e6 / 11.0,
+            Region::Pal | Region::Dendy => 26_601_712.5,
+        }
+    }
+
+    /// Master clock ticks per CPU cycle.
+    pub fn cpu_divider(&self) -> u32 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        }
+    }
+
+    /// Master clock ticks per PPU dot.
+    pub fn ppu_divider(&self) -> u32 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal | Region::Dendy => 5,
+        }
+    }
+
+    pub fn cpu_clock_hz(&self) -> f64 {
+        self.master_clock_hz() / self.cpu_divider() as f64
+    }
+
+    pub fn scanlines(&self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /// The scanline on which vblank, and NMI, begins. Dendy keeps NTSC's
+    /// 20 lines of vblank and spends the extra lines before it instead.
+    pub fn vblank_scanline(&self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    /// Only the NTSC PPU drops a dot from every other frame.
+    pub fn skips_odd_frame_dot(&self) -> bool {
+        *self == Region::Ntsc
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        let mut dots = 341.0 * self.scanlines() as f64;
+        if self.skips_odd_frame_dot() {
+            dots -= 0.5;
+        }
+        self.master_clock_hz() / (self.ppu_divider() as f64 * dots)
+    }
+
+    /// Noise channel timer periods in CPU cycles, indexed by the low
+    /// nibble of $400E.
+    pub fn noise_periods(&self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_NOISE_PERIODS,
+            Region::Pal => &PAL_NOISE_PERIODS,
+        }
+    }
+
+    /// DMC output rates in CPU cycles per bit, indexed by the low nibble
+    /// of $4010.
+    pub fn dmc_rates(&self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_DMC_RATES,
+            Region::Pal => &PAL_DMC_RATES,
+        }
+    }
+
+    pub fn frame_counter_steps(&self) -> &'static [u32; 5] {
+        match self {
+            Region::Ntsc | Region::Dendy => &NTSC_FRAME_STEPS,
+            Region::Pal => &PAL_FRAME_STEPS,
+        }
+    }
+
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Region::Ntsc => 0,
+            Region::Pal => 1,
+            Region::Dendy => 2,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Option<Region> {
+        Region::ALL.get(byte as usize).copied()
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Region::Ntsc => "NTSC",
+            Region::Pal => "PAL",
+            Region::Dendy => "Dendy",
+        })
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+use crate::{bus::Bus, cpu::CPU, error::CPUError};
+
+/// A save state taken at the start of `frame`.
+struct Snapshot {
+    frame: u64,
+    state: Vec<u8>,
+}
+
+/// An older snapshot stored as the run-length encoded XOR against the
+/// snapshot that followed it.
+struct Delta {
+    frame: u64,
+    len: usize,
+    encoded: Vec<u8>,
+}
+
+/// The controller input of one frame, as `Bus::input` gives it.
+struct FrameInput {
+    frame: u64,
+    input: Vec<u8>,
+}
+
+/// Ring buffer of machine snapshots for scrubbing backwards in time.
+///
+/// Only the newest snapshot is kept whole. Each older one is a delta against
+/// its successor, so rewinding walks back from the newest; when the buffer
+/// exceeds its memory budget the oldest deltas are dropped first.
+///
+/// Every frame's controller input is recorded as well, and frames between
+/// two snapshots are recreated by running the emulator forward with it.
+pub struct Rewind {
+    interval: u64,
+    budget: usize,
+    latest: Option<Snapshot>,
+    history: VecDeque<Delta>,
+    history_bytes: usize,
+    inputs: VecDeque<FrameInput>,
+    input_bytes: usize,
+}
+
+impl Rewind {
+    /// Captures a snapshot every `interval` frames, keeping at most
+    /// `budget` bytes of history.
+    pub fn new(interval: u64, budget: usize) -> Self {
+        Rewind {
+            interval: interval.max(1),
+            budget,
+            latest: None,
+            history: VecDeque::new(),
+            history_bytes: 0,
+            inputs: VecDeque::new(),
+            input_bytes: 0,
+        }
+    }
+
+    /// Call once per frame, once its input is set and before it runs.
+    /// Records the input and takes a snapshot when one is due.
+    pub fn on_frame<B: Bus>(&mut self, cpu: &CPU<B>) {
+        let frame = cpu.bus.frame();
+        self.drop_inputs_from(frame);
+        let input = cpu.bus.input();
+        self.input_bytes += input.len();
+        self.inputs.push_back(FrameInput { frame, input });
+
+        match &self.latest {
+            Some(latest) if frame < latest.frame + self.interval => self.trim(),
+            _ => self.capture(cpu),
+        }
+    }
+
+    /// Takes a snapshot now, regardless of the interval.
+    pub fn capture<B: Bus>(&mut self, cpu: &CPU<B>) {
+        let snapshot = Snapshot {
+            frame: cpu.bus.frame(),
+            state: cpu.save_state(),
+        };
+
+        if let Some(previous) = self.latest.replace(snapshot) {
+            let current = &self.latest.as_ref().unwrap().state;
+            let encoded = encode(&xor(&previous.state, current));
+            self.history_bytes += encoded.len();
+            self.history.push_back(Delta {
+                frame: previous.frame,
+                len: previous.state.len(),
+                encoded,
+            });
+        }
+        self.trim();
+    }
+
+    /// Drops the oldest deltas while over budget, along with the input of
+    /// frames that can no longer be reached.
+    fn trim(&mut self) {
+        while self.memory_used() > self.budget {
+            let Some(oldest) = self.history.pop_front() else {
+                break;
+            };
+            self.history_bytes -= oldest.encoded.len();
+        }
+        let oldest = self.oldest_frame().unwrap_or(u64::MAX);
+        while self.inputs.front().is_some_and(|input| input.frame < oldest) {
+            let input = self.inputs.pop_front().unwrap();
+            self.input_bytes -= input.input.len();
+        }
+    }
+
+    fn drop_inputs_from(&mut self, frame: u64) {
+        while self.inputs.back().is_some_and(|input| input.frame >= frame) {
+            let input = self.inputs.pop_back().unwrap();
+            self.input_bytes -= input.input.len();
+        }
+    }
+
+    fn input_for(&self, frame: u64) -> Option<&[u8]> {
+        self.inputs.iter().rev().find(|input| input.frame == frame).map(|input| &input.input[..])
+    }
+
+    /// The earliest frame that can still be rewound to.
+    pub fn oldest_frame(&self) -> Option<u64> {
+        self.history.front().map(|delta| delta.frame)
+            .or(self.latest.as_ref().map(|snapshot| snapshot.frame))
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.history.len() + self.latest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Bytes held by the buffer, the newest full snapshot and the recorded
+    /// input included.
+    pub fn memory_used(&self) -> usize {
+        self.history_bytes + self.input_bytes + self.latest.as_ref().map_or(0, |snapshot| snapshot.state.len())
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.history.clear();
+        self.history_bytes = 0;
+        self.inputs.clear();
+        self.input_bytes = 0;
+    }
+
+    /// Goes back one frame. Returns `Ok(false)` when there is no earlier
+    /// frame to go back to.
+    pub fn step_back<B: Bus>(&mut self, cpu: &mut CPU<B>) -> Result<bool, CPUError> {
+        match cpu.bus.frame().checked_sub(1) {
+            Some(frame) => self.rewind_to(cpu, frame),
+            None => Ok(false),
+        }
+    }
+
+    /// Puts the machine back at the start of `frame`, with the input it had
+    /// then: the nearest snapshot at or before it is restored and the
+    /// remaining frames are run again with their recorded input. Snapshots
+    /// and input after `frame` are discarded, since running on from there
+    /// starts a new timeline. Returns `Ok(false)` if `frame` is older than
+    /// anything in the buffer or has not happened yet.
+    pub fn rewind_to<B: Bus>(&mut self, cpu: &mut CPU<B>, frame: u64) -> Result<bool, CPUError> {
+        if frame > cpu.bus.frame() || self.oldest_frame().is_none_or(|oldest| frame < oldest) {
+            return Ok(false);
+        }
+
+        while self.latest.as_ref().is_some_and(|latest| latest.frame > frame) {
+            let delta = self.history.pop_back().expect("older snapshot checked above");
+            self.history_bytes -= delta.encoded.len();
+
+            let newer = self.latest.take().unwrap();
+            let mut state = decode(&delta.encoded, delta.len.max(newer.state.len()));
+            for (byte, newer) in state.iter_mut().zip(&newer.state) {
+                *byte ^= newer;
+            }
+            state.truncate(delta.len);
+            self.latest = Some(Snapshot { frame: delta.frame, state });
+        }
+
+        let latest = self.latest.as_ref().unwrap();
+        cpu.load_state(&latest.state)
+            .expect("rewind snapshots come from save_state on the same machine");
+
+        loop {
+            if let Some(input) = self.input_for(cpu.bus.frame()) {
+                cpu.bus.set_input(input);
+            }
+            if cpu.bus.frame() >= frame || !cpu.run_frame()? {
+                break;
+            }
+        }
+        self.drop_inputs_from(frame + 1);
+        Ok(true)
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| a.get(i).copied().unwrap_or(0) ^ b.get(i).copied().unwrap_or(0))
+        .collect()
+}
+
+// The XOR of two consecutive snapshots is mostly zeros, so it is stored as
+// pairs of varint counts: a run of zeros, then that many literal bytes.
+
+fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let zeros = data[i..].iter().take_while(|&&b| b == 0).count();
+        i += zeros;
+        let literals = data[i..].iter().take_while(|&&b| b != 0).count();
+
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend_from_slice(&data[i..i + literals]);
+        i += literals;
+    }
+    out
+}
+
+fn decode(encoded: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < encoded.len() {
+        let zeros = read_varint(encoded, &mut i);
+        let literals = read_varint(encoded, &mut i);
+        out.resize(out.len() + zeros, 0);
+        out.extend_from_slice(&encoded[i..i + literals]);
+        i += literals;
+    }
+    out.resize(len, 0);
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*i];
+        *i += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use crate::{
+    bus::Bus,
+    cpu::CPU,
+    error::SaveStateError,
+    flags::StatusFlags,
+};
+
+// Layout of a save state, all integers little-endian:
+//
+//   0  "NESS"          magic
+//   4  u16             format version
+//   6  u16             reserved, zero
+//   8  u32             payload length
+//  12  u32             CRC-32 of the payload
+//  16  payload         chunks of [tag: 4 bytes][length: u32][data]
+//
+// Each component writes its own chunk. Loaders skip chunks they do not know,
+// and fields added to a chunk in later versions go at its end, so that a
+// reader can tell an older state by the chunk simply running out early.
+
+const MAGIC: [u8; 4] = *b"NESS";
+const HEADER_LEN: usize = 16;
+
+/// The format version written by this build. States with a lower version
+/// still load; newer ones are rejected.
+///
+/// 1: initial format
+/// 2: PPU dot clock, frame counter and pending NMI
+/// 3: controllers
+/// 4: CPU NMI latches
+/// 5: open bus and the PPU I/O latch
+/// 6: region, master clock phase and APU frame counter
+/// 7: input devices other than standard controllers
+pub const VERSION: u16 = 7;
+
+/// Collects the chunks of a save state.
+#[derive(Default)]
+pub struct StateWriter {
+    chunks: Vec<([u8; 4], Chunk)>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new chunk and returns it for writing.
+    pub fn chunk(&mut self, tag: [u8; 4]) -> &mut Chunk {
+        self.chunks.push((tag, Chunk::default()));
+        &mut self.chunks.last_mut().unwrap().1
+    }
+
+    /// Frames the chunks with the header and checksum.
+    pub fn finish(self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        for (tag, chunk) in self.chunks {
+            payload.extend_from_slice(&tag);
+            payload.extend_from_slice(&(chunk.0.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&chunk.0);
+        }
+
+        let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+        data.extend_from_slice(&MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend_from_slice(&crc32(&payload).to_le_bytes());
+        data.extend_from_slice(&payload);
+        data
+    }
+}
+
+#[derive(Default)]
+pub struct Chunk(Vec<u8>);
+
+impl Chunk {
+    pub fn write_u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+    pub fn write_bool(&mut self, value: bool) {
+        self.0.push(value as u8);
+    }
+    pub fn write_u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn write_u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn write_u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+/// A parsed save state whose header and checksum have been verified.
+pub struct StateReader<'a> {
+    pub version: u16,
+    chunks: BTreeMap<[u8; 4], &'a [u8]>,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, SaveStateError> {
+        if data.len() < HEADER_LEN || data[0..4] != MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version == 0 || version > VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        let payload_len = u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(data[12..16].try_into().unwrap());
+        let payload = data.get(HEADER_LEN..HEADER_LEN + payload_len)
+            .ok_or(SaveStateError::Truncated)?;
+        if crc32(payload) != checksum {
+            return Err(SaveStateError::ChecksumMismatch);
+        }
+
+        let mut chunks = BTreeMap::new();
+        let mut rest = payload;
+        while !rest.is_empty() {
+            if rest.len() < 8 {
+                return Err(SaveStateError::Truncated);
+            }
+            let tag: [u8; 4] = rest[0..4].try_into().unwrap();
+            let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+            let body = rest.get(8..8 + len).ok_or(SaveStateError::Truncated)?;
+            chunks.insert(tag, body);
+            rest = &rest[8 + len..];
+        }
+
+        Ok(StateReader { version, chunks })
+    }
+
+    pub fn chunk(&self, tag: [u8; 4]) -> Result<ChunkReader<'a>, SaveStateError> {
+        self.chunks.get(&tag)
+            .map(|&data| ChunkReader { data })
+            .ok_or_else(|| SaveStateError::MissingChunk(String::from_utf8_lossy(&tag).into_owned()))
+    }
+
+    pub fn has_chunk(&self, tag: [u8; 4]) -> bool {
+        self.chunks.contains_key(&tag)
+    }
+}
+
+pub struct ChunkReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ChunkReader<'a> {
+    /// Bytes left in the chunk; zero once an older, shorter chunk has been
+    /// read to its end.
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.data.len() < len {
+            return Err(SaveStateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+    pub fn read_into(&mut self, buffer: &mut [u8]) -> Result<(), SaveStateError> {
+        buffer.copy_from_slice(self.read_bytes(buffer.len())?);
+        Ok(())
+    }
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        Ok(self.read_u8()? != 0)
+    }
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+    pub fn read_u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+}
+
+impl<B: Bus> CPU<B> {
+    /// Snapshots the whole machine: registers, the cycle counter and
+    /// everything on the bus.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+
+        let cpu = state.chunk(*b"CPU ");
+        cpu.write_u8(self.register_a);
+        cpu.write_u8(self.register_x);
+        cpu.write_u8(self.register_y);
+        cpu.write_u8(self.status.bits());
+        cpu.write_u8(self.stack_pointer);
+        cpu.write_u16(self.program_counter);
+        cpu.write_u64(self.cycles);
+        cpu.write_bool(self.nmi_detected);
+        cpu.write_bool(self.nmi_pending);
+
+        self.bus.save_state(&mut state);
+        state.finish()
+    }
+
+    /// Restores a snapshot made by `save_state`. If the state turns out to
+    /// be unusable part way through, the machine is put back as it was.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let state = StateReader::new(data)?;
+        let backup = self.save_state();
+
+        let result = self.restore(&state);
+        if result.is_err() {
+            self.restore(&StateReader::new(&backup)?)?;
+        }
+        result
+    }
+
+    fn restore(&mut self, state: &StateReader) -> Result<(), SaveStateError> {
+        let mut cpu = state.chunk(*b"CPU ")?;
+        self.register_a = cpu.read_u8()?;
+        self.register_x = cpu.read_u8()?;
+        self.register_y = cpu.read_u8()?;
+        self.status = StatusFlags::from_bits_retain(cpu.read_u8()?);
+        self.stack_pointer = cpu.read_u8()?;
+        self.program_counter = cpu.read_u16()?;
+        self.cycles = cpu.read_u64()?;
+        // states before version 4 were taken with no NMI on its way in
+        if cpu.remaining() == 0 {
+            self.nmi_detected = false;
+            self.nmi_pending = false;
+        } else {
+            self.nmi_detected = cpu.read_bool()?;
+            self.nmi_pending = cpu.read_bool()?;
+        }
+
+        self.bus.load_state(state)
+    }
+}
+
+/// CRC-32 (IEEE 802.3), as used by zip and PNG.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
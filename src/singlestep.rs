@@ -0,0 +1,280 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+use serde_json::Value;
+use crate::{
+    bus::{AccessKind, Bus, BusAccess, TestBus},
+    cpu::CPU,
+    error::{CPUError, SingleStepError},
+    flags::StatusFlags,
+    opcode,
+};
+
+// Runs the single-step CPU tests from the ProcessorTests project
+// (github.com/SingleStepTests/65x02, the `nes6502` set). Each opcode has a
+// JSON file holding an array of tests like
+//
+//   { "name": "a9 2c 7e",
+//     "initial": { "pc": 1234, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+//                  "ram": [[1234, 169], [1235, 44]] },
+//     "final":   { ... },
+//     "cycles":  [[1234, 169, "read"], [1235, 44, "read"]] }
+//
+// where `cycles` lists the bus access made on every cycle of the
+// instruction. Needs the `singlestep` feature, which brings in serde_json.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuSnapshot {
+    pub pc: u16,
+    pub s: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    /// The memory the test sets up or checks; everything else is zero.
+    pub ram: Vec<(u16, u8)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SingleStepTest {
+    pub name: String,
+    pub initial: CpuSnapshot,
+    pub expected: CpuSnapshot,
+    pub cycles: Vec<BusAccess>,
+}
+
+impl SingleStepTest {
+    /// The opcode under test: the byte at the initial PC.
+    pub fn opcode(&self) -> u8 {
+        self.initial.ram.iter()
+            .find(|&&(addr, _)| addr == self.initial.pc)
+            .map_or(0, |&(_, value)| value)
+    }
+}
+
+pub fn parse_tests(json: &str) -> Result<Vec<SingleStepTest>, SingleStepError> {
+    let value: Value = serde_json::from_str(json).map_err(|err| SingleStepError::Json(err.to_string()))?;
+    let tests = value.as_array().ok_or_else(|| format_error("the top level must be an array"))?;
+    tests.iter().map(parse_test).collect()
+}
+
+fn format_error(message: &str) -> SingleStepError {
+    SingleStepError::Format(message.to_string())
+}
+
+fn parse_test(test: &Value) -> Result<SingleStepTest, SingleStepError> {
+    let name = test["name"].as_str().unwrap_or("").to_string();
+    let missing = |field: &str| SingleStepError::Format(format!("test {:?} has no valid {:?}", name, field));
+
+    let cycles = test["cycles"].as_array().ok_or_else(|| missing("cycles"))?
+        .iter()
+        .map(|cycle| {
+            let kind = match cycle[2].as_str() {
+                Some("read") => AccessKind::Read,
+                Some("write") => AccessKind::Write,
+                _ => return None,
+            };
+            Some(BusAccess { address: number(&cycle[0])?, value: number(&cycle[1])?, kind })
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| missing("cycles"))?;
+
+    Ok(SingleStepTest {
+        initial: parse_snapshot(&test["initial"]).ok_or_else(|| missing("initial"))?,
+        expected: parse_snapshot(&test["final"]).ok_or_else(|| missing("final"))?,
+        cycles,
+        name,
+    })
+}
+
+fn parse_snapshot(state: &Value) -> Option<CpuSnapshot> {
+    let ram = state["ram"].as_array()?
+        .iter()
+        .map(|entry| Some((number(&entry[0])?, number(&entry[1])?)))
+        .collect::<Option<Vec<_>>>()?;
+    Some(CpuSnapshot {
+        pc: number(&state["pc"])?,
+        s: number(&state["s"])?,
+        a: number(&state["a"])?,
+        x: number(&state["x"])?,
+        y: number(&state["y"])?,
+        p: number(&state["p"])?,
+        ram,
+    })
+}
+
+fn number<T: TryFrom<u64>>(value: &Value) -> Option<T> {
+    value.as_u64().and_then(|n| T::try_from(n).ok())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    /// Registers and memory ended up right, but the accesses on the way
+    /// there did not match cycle for cycle.
+    BusMismatch(String),
+    StateMismatch(String),
+    Error(CPUError),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "passed"),
+            Outcome::BusMismatch(message) => write!(f, "bus: {}", message),
+            Outcome::StateMismatch(message) => write!(f, "state: {}", message),
+            Outcome::Error(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Sets up the initial state, executes one instruction and compares the
+/// registers, the listed memory and every bus access with the test.
+pub fn run_test(test: &SingleStepTest) -> Outcome {
+    let mut bus = TestBus::new();
+    for &(addr, value) in &test.initial.ram {
+        bus.load(addr, value);
+    }
+
+    let mut cpu = CPU::with_bus(bus);
+    let initial = &test.initial;
+    cpu.program_counter = initial.pc;
+    cpu.stack_pointer = initial.s;
+    cpu.register_a = initial.a;
+    cpu.register_x = initial.x;
+    cpu.register_y = initial.y;
+    cpu.status = StatusFlags::from_bits_retain(initial.p);
+
+    if let Err(err) = cpu.step() {
+        return Outcome::Error(err);
+    }
+
+    let expected = &test.expected;
+    let registers = [
+        ("PC", cpu.program_counter, expected.pc),
+        ("S", cpu.stack_pointer as u16, expected.s as u16),
+        ("A", cpu.register_a as u16, expected.a as u16),
+        ("X", cpu.register_x as u16, expected.x as u16),
+        ("Y", cpu.register_y as u16, expected.y as u16),
+        ("P", cpu.status.bits() as u16, expected.p as u16),
+    ];
+    for (name, actual, expected) in registers {
+        if actual != expected {
+            return Outcome::StateMismatch(format!("{} is ${:02X}, expected ${:02X}", name, actual, expected));
+        }
+    }
+    for &(addr, value) in &expected.ram {
+        let actual = cpu.bus.peek(addr);
+        if actual != value {
+            return Outcome::StateMismatch(format!("${:04X} is ${:02X}, expected ${:02X}", addr, actual, value));
+        }
+    }
+
+    let accesses = &cpu.bus.accesses;
+    if let Some(i) = (0..accesses.len().max(test.cycles.len()))
+        .find(|&i| accesses.get(i) != test.cycles.get(i)) {
+        let describe = |access: Option<&BusAccess>| access.map_or("nothing".to_string(), |a| a.to_string());
+        return Outcome::BusMismatch(format!("cycle {}: {}, expected {}",
+            i + 1, describe(accesses.get(i)), describe(test.cycles.get(i))));
+    }
+    if cpu.cycles != test.cycles.len() as u64 {
+        return Outcome::BusMismatch(format!("took {} cycles, expected {}", cpu.cycles, test.cycles.len()));
+    }
+    Outcome::Passed
+}
+
+/// Results for one opcode.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpcodeResult {
+    pub total: usize,
+    /// Tests where everything matched.
+    pub passed: usize,
+    /// Tests that ended in the right state, whether or not the bus matched.
+    pub state_passed: usize,
+    /// The first failing test's name and outcome.
+    pub first_failure: Option<(String, Outcome)>,
+}
+
+/// Pass rates per opcode.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub opcodes: BTreeMap<u8, OpcodeResult>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, test: &SingleStepTest, outcome: Outcome) {
+        let result = self.opcodes.entry(test.opcode()).or_default();
+        result.total += 1;
+        match outcome {
+            Outcome::Passed => {
+                result.passed += 1;
+                result.state_passed += 1;
+                return;
+            }
+            Outcome::BusMismatch(_) => result.state_passed += 1,
+            _ => {}
+        }
+        if result.first_failure.is_none() {
+            result.first_failure = Some((test.name.clone(), outcome));
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.opcodes.values().map(|result| result.total).sum()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.opcodes.values().map(|result| result.passed).sum()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |n: usize, total: usize| 100.0 * n as f64 / total.max(1) as f64;
+
+        writeln!(f, "op   name   passed   state    tests  first failure")?;
+        for (&code, result) in &self.opcodes {
+            let name = opcode::lookup(code).map_or("???", |op| op.name);
+            let failure = result.first_failure.as_ref()
+                .map_or(String::new(), |(test, outcome)| format!("{}: {}", test, outcome));
+            writeln!(f, "${:02X}  {:<5} {:>6.1}%  {:>6.1}%  {:>6}  {}",
+                code, name,
+                percent(result.passed, result.total),
+                percent(result.state_passed, result.total),
+                result.total, failure)?;
+        }
+        write!(f, "total {}/{} passed ({:.1}%)", self.passed(), self.total(), percent(self.passed(), self.total()))
+    }
+}
+
+/// Runs every test in `tests` and tallies the results.
+pub fn run_tests(tests: &[SingleStepTest]) -> Report {
+    let mut report = Report::new();
+    for test in tests {
+        report.record(test, run_test(test));
+    }
+    report
+}
+
+/// Runs every `.json` file in `dir`, such as a checkout of the `nes6502/v1`
+/// directory.
+pub fn run_directory(dir: &Path) -> Result<Report, SingleStepError> {
+    let io_error = |path: &Path, err: std::io::Error| SingleStepError::Io(format!("{}: {}", path.display(), err));
+
+    let mut paths: Vec<_> = fs::read_dir(dir).map_err(|err| io_error(dir, err))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut report = Report::new();
+    for path in paths {
+        let json = fs::read_to_string(&path).map_err(|err| io_error(&path, err))?;
+        for test in parse_tests(&json)? {
+            report.record(&test, run_test(&test));
+        }
+    }
+    Ok(report)
+}
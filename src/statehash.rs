@@ -0,0 +1,65 @@
+use crate::{bus::Bus, cpu::CPU};
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// 64-bit FNV-1a over machine state. Unlike `std::hash::DefaultHasher`, the
+/// result is specified, so hashes can be compared across builds, platforms
+/// and Rust versions.
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        StateHasher { hash: FNV_OFFSET_BASIS }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    pub fn write_u8(&mut self, value: u8) {
+        self.write_bytes(&[value]);
+    }
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl<B: Bus> CPU<B> {
+    /// A stable hash of the registers, the cycle counter and the state of
+    /// the devices on the bus: work RAM, VRAM, OAM, palette, controller
+    /// ports and mapper state for the NES. Given the same ROM, RAM pattern and input, every run
+    /// produces the same sequence of hashes.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        hasher.write_u8(self.register_a);
+        hasher.write_u8(self.register_x);
+        hasher.write_u8(self.register_y);
+        hasher.write_u8(self.status.bits());
+        hasher.write_u8(self.stack_pointer);
+        hasher.write_u16(self.program_counter);
+        hasher.write_u64(self.cycles);
+        hasher.write_u8(self.nmi_detected as u8);
+        hasher.write_u8(self.nmi_pending as u8);
+
+        self.bus.hash_state(&mut hasher);
+        hasher.finish()
+    }
+}
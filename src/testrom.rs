@@ -0,0 +1,90 @@
+use crate::{
+    bus::{Bus, NesBus, RamPattern},
+    cartridge::Cartridge,
+    cpu::CPU,
+    error::TestRomError,
+};
+
+// Blargg's test ROMs report through PRG-RAM:
+//
+//   $6000        status: $80 running, $81 press reset, $00-$7F result code
+//   $6001-$6003  $DE $B0 $61 once the status byte is valid
+//   $6004-       null-terminated text, the same as printed on screen
+
+const STATUS: u16 = 0x6000;
+const SIGNATURE: u16 = 0x6001;
+const TEXT: u16 = 0x6004;
+const SIGNATURE_BYTES: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET: u8 = 0x81;
+/// The ROMs ask for reset to be held off at least 100 ms.
+const RESET_DELAY_FRAMES: u64 = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestRomResult {
+    /// 0 when every test passed, otherwise the number of the failing test
+    /// or an error code.
+    pub code: u8,
+    pub message: String,
+    pub frames: u64,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// Runs a test ROM without a display until it reports a result through
+/// $6000, pressing reset whenever it asks. Gives up after `frame_limit`
+/// frames.
+pub fn run_test_rom(rom: &[u8], frame_limit: u64) -> Result<TestRomResult, TestRomError> {
+    let mut cpu = CPU::with_bus(NesBus::new(Cartridge::new(rom)?));
+    cpu.power_on(RamPattern::Zeros);
+
+    let mut reset_at = None;
+    for frame in 1..=frame_limit {
+        if !cpu.run_frame()? {
+            return Err(TestRomError::Halted { message: message(&cpu.bus) });
+        }
+        if !signature_present(&cpu.bus) {
+            continue;
+        }
+
+        match cpu.bus.peek(STATUS) {
+            STATUS_RUNNING => {}
+            STATUS_RESET => match reset_at {
+                None => reset_at = Some(frame + RESET_DELAY_FRAMES),
+                Some(at) if frame >= at => {
+                    reset_at = None;
+                    cpu.reset();
+                }
+                Some(_) => {}
+            },
+            code @ 0x00..=0x7F => {
+                return Ok(TestRomResult { code, message: message(&cpu.bus), frames: frame });
+            }
+            // undefined; keep waiting
+            _ => {}
+        }
+    }
+    Err(TestRomError::TimedOut { frames: frame_limit, message: message(&cpu.bus) })
+}
+
+fn signature_present(bus: &NesBus) -> bool {
+    (0..3).all(|i| bus.peek(SIGNATURE + i) == SIGNATURE_BYTES[i as usize])
+}
+
+/// The text at $6004, up to its terminator or the end of PRG-RAM. Empty
+/// until the signature says the ROM has started writing it.
+fn message(bus: &NesBus) -> String {
+    if !signature_present(bus) {
+        return String::new();
+    }
+    let bytes: Vec<u8> = (TEXT..0x8000)
+        .map(|addr| bus.peek(addr))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -0,0 +1,396 @@
+use crate::{
+    ntsc::{NtscFilter, NtscSetup},
+    palette::Palette,
+    ppu::{PPU, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+/// An RGB picture, 0x00RRGGBB per pixel, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image { width, height, pixels: vec![0; width * height] }
+    }
+
+    /// The pixel at (`x`, `y`), repeating the edge pixels outside the picture.
+    pub fn at(&self, x: isize, y: isize) -> u32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, pixel: u32) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+
+    /// The picture as a binary PPM (P6) file.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in &self.pixels {
+            data.extend_from_slice(&pixel.to_be_bytes()[1..]);
+        }
+        data
+    }
+}
+
+/// Pixel-art upscalers for the palette-decoded picture.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Scaler {
+    #[default]
+    None,
+    Scale2x,
+    Scale3x,
+    Hq2x,
+    /// xBR-style edge blending at 2x, 3x or 4x.
+    Xbrz2,
+    Xbrz3,
+    Xbrz4,
+}
+
+impl Scaler {
+    /// Parses a scaler name as given on the command line, e.g. `xbrz3`.
+    pub fn parse(name: &str) -> Option<Scaler> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Some(Scaler::None),
+            "scale2x" => Some(Scaler::Scale2x),
+            "scale3x" => Some(Scaler::Scale3x),
+            "hq2x" => Some(Scaler::Hq2x),
+            "xbrz2" => Some(Scaler::Xbrz2),
+            "xbrz3" => Some(Scaler::Xbrz3),
+            "xbrz4" => Some(Scaler::Xbrz4),
+            _ => None,
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        match self {
+            Scaler::None => 1,
+            Scaler::Scale2x | Scaler::Hq2x | Scaler::Xbrz2 => 2,
+            Scaler::Scale3x | Scaler::Xbrz3 => 3,
+            Scaler::Xbrz4 => 4,
+        }
+    }
+
+    pub fn apply(&self, image: &Image) -> Image {
+        match self {
+            Scaler::None => image.clone(),
+            Scaler::Scale2x => scale2x(image),
+            Scaler::Scale3x => scale3x(image),
+            Scaler::Hq2x => hq2x(image),
+            Scaler::Xbrz2 | Scaler::Xbrz3 | Scaler::Xbrz4 => xbr(image, self.factor()),
+        }
+    }
+}
+
+/// How a frame gets from the PPU to the screen or a screenshot.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VideoConfig {
+    pub palette: Palette,
+    /// Decodes through the NTSC composite filter instead of the palette.
+    /// Its output is no longer pixel art, so the scaler is not applied.
+    pub ntsc: Option<NtscSetup>,
+    pub scaler: Scaler,
+    /// How much the gaps between lines are darkened, in percent; 0 is off.
+    pub scanlines: u8,
+}
+
+impl VideoConfig {
+    pub fn render(&self, ppu: &PPU) -> Image {
+        let image = match self.ntsc {
+            Some(setup) => {
+                let pixels = NtscFilter::new(setup).render(&ppu.frame_buffer, (ppu.frame % 3) as u8);
+                Image { width: pixels.len() / SCREEN_HEIGHT, height: SCREEN_HEIGHT, pixels }
+            }
+            None => {
+                let pixels = self.palette.render(&ppu.frame_buffer);
+                self.scaler.apply(&Image { width: SCREEN_WIDTH, height: SCREEN_HEIGHT, pixels })
+            }
+        };
+        if self.scanlines == 0 {
+            return image;
+        }
+        scanlines(&image, SCREEN_HEIGHT, self.scanlines)
+    }
+}
+
+/// Darkens the last row of each of the `lines` source lines by `intensity`
+/// percent, doubling the picture's height first if it has one row per line.
+pub fn scanlines(image: &Image, lines: usize, intensity: u8) -> Image {
+    let mut image = image.clone();
+    if image.height / lines < 2 {
+        image = Image {
+            width: image.width,
+            height: image.height * 2,
+            pixels: image.pixels.chunks_exact(image.width)
+                .flat_map(|row| row.iter().chain(row))
+                .copied()
+                .collect(),
+        };
+    }
+
+    let rows = image.height / lines;
+    let keep = 100 - intensity.min(100) as u32;
+    for (y, row) in image.pixels.chunks_exact_mut(image.width).enumerate() {
+        if y % rows == rows - 1 {
+            for pixel in row {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                let dim = |channel: u8| (channel as u32 * keep / 100) as u8;
+                *pixel = u32::from_be_bytes([0, dim(r), dim(g), dim(b)]);
+            }
+        }
+    }
+    image
+}
+
+/// The 3x3 neighbourhood of a pixel:
+///
+/// ```text
+/// A B C
+/// D E F
+/// G H I
+/// ```
+fn neighbours(image: &Image, x: usize, y: usize) -> [u32; 9] {
+    let (x, y) = (x as isize, y as isize);
+    std::array::from_fn(|i| image.at(x + i as isize % 3 - 1, y + i as isize / 3 - 1))
+}
+
+/// AdvMAME2x: each corner takes the colour of the two edge neighbours
+/// beside it when they agree and the edge does not continue past them.
+fn scale2x(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 2, image.height * 2);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let [_, b, _, d, e, f, _, h, _] = neighbours(image, x, y);
+            let mut block = [e; 4];
+            if b != h && d != f {
+                if d == b { block[0] = d; }
+                if b == f { block[1] = f; }
+                if d == h { block[2] = d; }
+                if h == f { block[3] = f; }
+            }
+            for (i, &pixel) in block.iter().enumerate() {
+                out.set(x * 2 + i % 2, y * 2 + i / 2, pixel);
+            }
+        }
+    }
+    out
+}
+
+/// AdvMAME3x, the same rule extended to the edge centres of a 3x3 block.
+fn scale3x(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 3, image.height * 3);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let [a, b, c, d, e, f, g, h, i] = neighbours(image, x, y);
+            let mut block = [e; 9];
+            if b != h && d != f {
+                if d == b { block[0] = d; }
+                if (d == b && e != c) || (b == f && e != a) { block[1] = b; }
+                if b == f { block[2] = f; }
+                if (d == b && e != g) || (d == h && e != a) { block[3] = d; }
+                if (b == f && e != i) || (h == f && e != c) { block[5] = f; }
+                if d == h { block[6] = d; }
+                if (d == h && e != i) || (h == f && e != g) { block[7] = h; }
+                if h == f { block[8] = f; }
+            }
+            for (n, &pixel) in block.iter().enumerate() {
+                out.set(x * 3 + n % 3, y * 3 + n / 3, pixel);
+            }
+        }
+    }
+    out
+}
+
+fn yuv(pixel: u32) -> [f32; 3] {
+    let [_, r, g, b] = pixel.to_be_bytes().map(|channel| channel as f32);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.169 * r - 0.331 * g + 0.5 * b,
+        0.5 * r - 0.419 * g - 0.081 * b,
+    ]
+}
+
+/// HQx's test for colours close enough to belong to the same shape.
+fn similar(a: u32, b: u32) -> bool {
+    let ([y1, u1, v1], [y2, u2, v2]) = (yuv(a), yuv(b));
+    (y1 - y2).abs() <= 48.0 && (u1 - u2).abs() <= 7.0 && (v1 - v2).abs() <= 6.0
+}
+
+/// Weighted distance between two colours, with luma counting most.
+fn distance(a: u32, b: u32) -> f32 {
+    let ([y1, u1, v1], [y2, u2, v2]) = (yuv(a), yuv(b));
+    48.0 * (y1 - y2).abs() + 7.0 * (u1 - u2).abs() + 6.0 * (v1 - v2).abs()
+}
+
+/// Mixes colours in the given proportions.
+fn blend(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|&(_, weight)| weight).sum();
+    let channel = |shift: u32| {
+        let sum: u32 = colors.iter().map(|&(pixel, weight)| (pixel >> shift & 0xFF) * weight).sum();
+        (sum + total / 2) / total
+    };
+    channel(16) << 16 | channel(8) << 8 | channel(0)
+}
+
+/// How HQ2x fills the top left quarter of a pixel's 2x block, looked up by
+/// which of its neighbours differ from it.
+#[derive(Clone, Copy)]
+enum Hq2xRule {
+    /// Always the blend of this number.
+        Mix(u8),
+    /// The first blend when the two neighbours differ from each other, the
+    /// second when they are alike.
+        If(usize, usize, u8, u8),
+}
+
+/// HQ2x's pattern table for the top left quarter. Bit n of the pattern is
+/// set when the nth of A B C D F G H I differs from E; the blends are
+/// numbered as in the original's `PIXEL00_*` macros.
+const HQ2X: [Hq2xRule; 256] = {
+    use Hq2xRule::{If, Mix};
+    const B: usize = 1;
+    const D: usize = 3;
+    const F: usize = 5;
+    const H: usize = 7;
+    [
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0x00
+        Mix(21), Mix(12), If(D, B, 10, 20), If(D, B, 0, 20), Mix(21), Mix(12), If(D, B, 10, 90), If(D, B, 0, 90), // 0x08
+        Mix(20), Mix(20), Mix(22), If(B, F, 11, 60), Mix(20), Mix(20), Mix(22), If(B, F, 11, 60), // 0x10
+        Mix(21), Mix(12), If(D, B, 0, 20), If(D, B, 0, 20), Mix(21), Mix(12), Mix(10), If(D, B, 0, 20), // 0x18
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0x20
+        Mix(21), Mix(12), If(D, B, 10, 90), If(D, B, 0, 90), Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 100), // 0x28
+        Mix(20), Mix(20), Mix(22), If(B, F, 11, 60), Mix(20), Mix(20), Mix(22), If(B, F, 11, 60), // 0x30
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), Mix(21), Mix(12), Mix(10), If(D, B, 0, 100), // 0x38
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0x40
+        Mix(21), If(H, D, 12, 61), If(D, B, 0, 20), If(D, B, 0, 20), Mix(21), If(H, D, 12, 61), If(D, B, 10, 70), If(D, B, 0, 20), // 0x48
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0x50
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), // 0x58
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0x60
+        Mix(21), If(H, D, 12, 61), Mix(10), If(D, B, 0, 20), Mix(21), If(H, D, 12, 61), Mix(10), If(D, B, 0, 100), // 0x68
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), If(B, F, 11, 60), // 0x70
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), Mix(21), If(H, D, 12, 61), Mix(10), If(D, B, 0, 100), // 0x78
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0x80
+        Mix(21), Mix(12), If(D, B, 10, 20), If(D, B, 0, 20), Mix(21), Mix(12), If(D, B, 10, 90), If(D, B, 0, 90), // 0x88
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0x90
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), // 0x98
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0xa0
+        Mix(21), Mix(12), If(D, B, 10, 90), If(D, B, 0, 90), Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 100), // 0xa8
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0xb0
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 90), Mix(21), Mix(12), Mix(10), If(D, B, 0, 100), // 0xb8
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0xc0
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 90), // 0xc8
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0xd0
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), Mix(21), Mix(12), Mix(10), If(D, B, 0, 20), // 0xd8
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0xe0
+        Mix(21), Mix(12), If(D, B, 10, 70), If(D, B, 0, 20), Mix(21), Mix(12), Mix(10), If(D, B, 0, 100), // 0xe8
+        Mix(20), Mix(20), Mix(22), Mix(11), Mix(20), Mix(20), Mix(22), Mix(11), // 0xf0
+        Mix(21), Mix(12), Mix(10), If(D, B, 0, 20), Mix(21), Mix(12), Mix(10), If(D, B, 0, 100), // 0xf8
+    ]
+};
+
+/// The blends HQ2x mixes the top left quarter from.
+fn hq2x_mix(n: &[u32; 9], mix: u8) -> u32 {
+    let [a, b, _, d, e, ..] = *n;
+    match mix {
+        0 => e,
+        10 => blend(&[(e, 3), (a, 1)]),
+        11 => blend(&[(e, 3), (d, 1)]),
+        12 => blend(&[(e, 3), (b, 1)]),
+        20 => blend(&[(e, 2), (d, 1), (b, 1)]),
+        21 => blend(&[(e, 2), (a, 1), (b, 1)]),
+        22 => blend(&[(e, 2), (a, 1), (d, 1)]),
+        60 => blend(&[(e, 5), (b, 2), (d, 1)]),
+        61 => blend(&[(e, 5), (d, 2), (b, 1)]),
+        70 => blend(&[(e, 6), (d, 1), (b, 1)]),
+        90 => blend(&[(e, 2), (d, 3), (b, 3)]),
+        100 => blend(&[(e, 14), (d, 1), (b, 1)]),
+        _ => unreachable!("HQ2x has no blend {}", mix),
+    }
+}
+
+/// HQ2x. The original's 256 cases treat the four quarters of the block
+/// alike, so each quarter turns the neighbourhood until it sits top left
+/// and goes through the one table.
+fn hq2x(image: &Image) -> Image {
+    // where each neighbour of the turned neighbourhood comes from, for the
+    // top left, top right, bottom left and bottom right quarter
+    const TURNS: [[usize; 9]; 4] = [
+        [0, 1, 2, 3, 4, 5, 6, 7, 8],
+        [2, 5, 8, 1, 4, 7, 0, 3, 6],
+        [6, 3, 0, 7, 4, 1, 8, 5, 2],
+        [8, 7, 6, 5, 4, 3, 2, 1, 0],
+    ];
+
+    let mut out = Image::new(image.width * 2, image.height * 2);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let n = neighbours(image, x, y);
+            for (i, turn) in TURNS.iter().enumerate() {
+                let n = turn.map(|k| n[k]);
+                let pattern = [0, 1, 2, 3, 5, 6, 7, 8].iter().enumerate()
+                    .filter(|&(_, &k)| !similar(n[4], n[k]))
+                    .fold(0, |pattern, (bit, _)| pattern | 1 << bit);
+                let mix = match HQ2X[pattern] {
+                    Hq2xRule::Mix(mix) => mix,
+                    Hq2xRule::If(p, q, differ, alike) => if similar(n[p], n[q]) { alike } else { differ },
+                };
+                out.set(x * 2 + i % 2, y * 2 + i / 2, hq2x_mix(&n, mix));
+            }
+        }
+    }
+    out
+}
+
+/// xBR's edge rule at any scale: a corner whose pixel lies across a
+/// diagonal edge from its neighbours has that side of the edge painted in,
+/// anti-aliased along the line, so staircases become smooth slopes.
+fn xbr(image: &Image, factor: usize) -> Image {
+    let mut out = Image::new(image.width * factor, image.height * factor);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let e = image.at(x as isize, y as isize);
+            let mut block = vec![e; factor * factor];
+
+            // the rule is written for the bottom right corner and mirrored
+            // onto the other three
+            for (sx, sy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+                let p = |dx: isize, dy: isize| image.at(x as isize + sx * dx, y as isize + sy * dy);
+                let (f, h, i) = (p(1, 0), p(0, 1), p(1, 1));
+                if e == f || e == h {
+                    continue;
+                }
+                let (b, c, d, g) = (p(0, -1), p(1, -1), p(-1, 0), p(-1, 1));
+                let (f4, i4, h5, i5) = (p(2, 0), p(2, 1), p(0, 2), p(1, 2));
+                let across = distance(e, c) + distance(e, g) + distance(i, f4) + distance(i, h5) + 4.0 * distance(h, f);
+                let along = distance(h, d) + distance(h, i5) + distance(f, i4) + distance(f, b) + 4.0 * distance(e, i);
+                if across >= along {
+                    continue;
+                }
+
+                let fill = if distance(e, f) <= distance(e, h) { f } else { h };
+                for (n, pixel) in block.iter_mut().enumerate() {
+                    let corner = |offset: usize, sign: isize| {
+                        let t = (offset as f32 + 0.5) / factor as f32;
+                        if sign > 0 { t } else { 1.0 - t }
+                    };
+                    let past_edge = corner(n % factor, sx) + corner(n / factor, sy) - 1.5;
+                    let coverage = (0.5 + past_edge * factor as f32 / 2.0).clamp(0.0, 1.0);
+                    let weight = (coverage * 16.0).round() as u32;
+                    if weight > 0 {
+                        *pixel = blend(&[(*pixel, 16 - weight), (fill, weight)]);
+                    }
+                }
+            }
+
+            for (n, &pixel) in block.iter().enumerate() {
+                out.set(x * factor + n % factor, y * factor + n / factor, pixel);
+            }
+        }
+    }
+    out
+}
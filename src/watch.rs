@@ -0,0 +1,83 @@
+use std::ops::RangeInclusive;
+use bitflags::bitflags;
+
+bitflags! {
+    /// The kinds of bus access a watch reports.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct WatchKind: u8 {
+        const READ    = 0b0000_0001;
+        const WRITE   = 0b0000_0010;
+        /// An opcode fetch.
+        const EXECUTE = 0b0000_0100;
+    }
+}
+
+/// One access seen by a watch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchEvent {
+    pub kind: WatchKind,
+    pub address: u16,
+    pub value: u8,
+    /// Address of the instruction making the access.
+    pub pc: u16,
+    /// CPU cycle count at the start of the cycle making the access.
+    pub cycle: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchId(usize);
+
+struct Watch {
+    id: WatchId,
+    kind: WatchKind,
+    range: RangeInclusive<u16>,
+    callback: Box<dyn FnMut(&WatchEvent) + Send>,
+}
+
+/// The watches registered on a CPU. With none registered, each memory
+/// access costs a single `Vec::is_empty` check.
+#[derive(Default)]
+pub struct Watches {
+    watches: Vec<Watch>,
+    next_id: usize,
+    /// PC of the instruction being executed, kept only while watching.
+    pub(crate) pc: u16,
+}
+
+impl Watches {
+    pub fn add(
+        &mut self,
+        kind: WatchKind,
+        range: RangeInclusive<u16>,
+        callback: impl FnMut(&WatchEvent) + Send + 'static,
+    ) -> WatchId {
+        let id = WatchId(self.next_id);
+        self.next_id += 1;
+        self.watches.push(Watch { id, kind, range, callback: Box::new(callback) });
+        id
+    }
+
+    pub fn remove(&mut self, id: WatchId) -> bool {
+        let before = self.watches.len();
+        self.watches.retain(|watch| watch.id != id);
+        self.watches.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.watches.clear();
+    }
+
+    #[inline(always)]
+    pub fn is_active(&self) -> bool {
+        !self.watches.is_empty()
+    }
+
+    pub(crate) fn notify(&mut self, kind: WatchKind, address: u16, value: u8, cycle: u64) {
+        let event = WatchEvent { kind, address, value, pc: self.pc, cycle };
+        for watch in &mut self.watches {
+            if watch.kind.intersects(kind) && watch.range.contains(&address) {
+                (watch.callback)(&event);
+            }
+        }
+    }
+}
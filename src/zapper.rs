@@ -0,0 +1,98 @@
+use crate::{
+    ntsc,
+    ppu::{PPU, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+/// Scanlines the photodiode keeps reporting light after the beam has
+/// passed over a bright spot.
+const LIGHT_SCANLINES: u16 = 20;
+/// How far around the aim point, in pixels, the sensor sees.
+const SENSE_RADIUS: isize = 2;
+/// The average brightness, black 0 to white 1, that trips the sensor.
+const LIGHT_THRESHOLD: f32 = 0.5;
+
+/// $4017 bits.
+const LIGHT_NOT_SENSED: u8 = 0b0000_1000;
+const TRIGGER_PULLED: u8 = 0b0001_0000;
+
+/// What the player is doing with the gun, as a frontend or a script sets it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ZapperInput {
+    /// The framebuffer pixel the gun points at, `None` when it points away
+    /// from the screen.
+    pub aim: Option<(usize, usize)>,
+    pub trigger: bool,
+}
+
+/// The NES Zapper light gun, read through $4017 when plugged into the
+/// second port. Its photodiode only sees light while the beam is drawing
+/// near the aim point, so games flash targets white for a frame and poll
+/// the gun as the picture is drawn.
+#[derive(Debug, Default, Clone)]
+pub struct Zapper {
+    pub input: ZapperInput,
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn aim_at(&mut self, x: usize, y: usize) {
+        self.input.aim = Some((x, y));
+    }
+
+    /// Aims at the point (`x`, `y`) of a view `width` by `height` that shows
+    /// the whole picture, such as a mouse position in a scaled or filtered
+    /// window. Points outside the view aim away from the screen.
+    pub fn aim_from_view(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let x = x / width * SCREEN_WIDTH as f32;
+        let y = y / height * SCREEN_HEIGHT as f32;
+        let on_screen = (0.0..SCREEN_WIDTH as f32).contains(&x) && (0.0..SCREEN_HEIGHT as f32).contains(&y);
+        self.input.aim = on_screen.then_some((x as usize, y as usize));
+    }
+
+    /// The port's value: the trigger in bit 4 and the light sensor in bit 3,
+    /// which reads 0 while it sees light.
+    pub fn read(&self, ppu: &PPU) -> u8 {
+        let mut data = 0;
+        if !self.senses_light(ppu) {
+            data |= LIGHT_NOT_SENSED;
+        }
+        if self.input.trigger {
+            data |= TRIGGER_PULLED;
+        }
+        data
+    }
+
+    /// Whether the pixels around the aim point, as far as the PPU has drawn
+    /// them in the last few scanlines, are bright enough on average.
+    pub fn senses_light(&self, ppu: &PPU) -> bool {
+        let Some((x, y)) = self.input.aim else {
+            return false;
+        };
+        // a line is drawn into the framebuffer when the beam reaches dot 256
+        let lit = |row: usize| {
+            let row = row as u16;
+            let drawn = row < ppu.scanline || (row == ppu.scanline && ppu.dot >= 256);
+            drawn && ppu.scanline - row <= LIGHT_SCANLINES
+        };
+
+        let mut brightness = 0.0;
+        for dy in -SENSE_RADIUS..=SENSE_RADIUS {
+            let row = y as isize + dy;
+            if row < 0 || row >= SCREEN_HEIGHT as isize || !lit(row as usize) {
+                continue;
+            }
+            for dx in -SENSE_RADIUS..=SENSE_RADIUS {
+                let column = x as isize + dx;
+                if (0..SCREEN_WIDTH as isize).contains(&column) {
+                    let pixel = ppu.frame_buffer[row as usize * SCREEN_WIDTH + column as usize];
+                    brightness += ntsc::luma(pixel);
+                }
+            }
+        }
+        let area = (SENSE_RADIUS * 2 + 1).pow(2) as f32;
+        brightness / area >= LIGHT_THRESHOLD
+    }
+}
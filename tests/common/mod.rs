@@ -0,0 +1,43 @@
+//! Cartridges built from assembly source, shared by the integration tests.
+#![allow(dead_code)]
+
+use nes_emulator::asm::assemble_segments;
+use nes_emulator::bus::{NesBus, RamPattern};
+use nes_emulator::cartridge::Cartridge;
+use nes_emulator::cpu::CPU;
+
+/// An NROM image with 16 KiB of PRG-ROM, mirrored at $8000 and $C000, and
+/// 8 KiB of blank CHR-ROM. `source` is assembled into the PRG-ROM and
+/// should set the vectors at $FFFA.
+pub fn nrom_image(source: &str) -> Vec<u8> {
+    let mut image = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x00];
+    image.resize(16, 0);
+    let mut prg = vec![0; 0x4000];
+    for segment in assemble_segments(source).unwrap() {
+        let offset = (segment.origin & 0x3FFF) as usize;
+        prg[offset..offset + segment.bytes.len()].copy_from_slice(&segment.bytes);
+    }
+    image.extend(prg);
+    image.extend(vec![0; 0x2000]);
+    image
+}
+
+/// [`nrom_image`] with 8 KiB of CHR-RAM in place of the CHR-ROM.
+pub fn nrom_image_chr_ram(source: &str) -> Vec<u8> {
+    let mut image = nrom_image(source);
+    image[5] = 0;
+    image.truncate(16 + 0x4000);
+    image
+}
+
+/// A console around `cartridge`, powered on with zeroed RAM.
+pub fn console(cartridge: Cartridge) -> CPU<NesBus> {
+    let mut cpu = CPU::with_bus(NesBus::new(cartridge));
+    cpu.power_on(RamPattern::Zeros);
+    cpu
+}
+
+/// A console running `source`, as built by [`nrom_image`].
+pub fn nes(source: &str) -> CPU<NesBus> {
+    console(Cartridge::new(&nrom_image(source)).unwrap())
+}
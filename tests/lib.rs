@@ -1,3 +1,5 @@
+#![allow(clippy::module_inception)]
+
 pub mod test_clear_opcodes;
 pub mod test_break_opcodes;
 pub mod test_dec_opcodes;
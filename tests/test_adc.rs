@@ -1,5 +1,6 @@
 mod test_adc {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_adc_carry_flag() {
@@ -7,7 +8,7 @@ mod test_adc {
         cpu.register_a = 0xFF;
         cpu.load_and_run(vec![0x69, 0x01, 0x00]).unwrap();
         assert_eq!(cpu.register_a, 0x00);
-        assert_eq!(cpu.status & 0b0000_0001, 1);
+        assert!(cpu.status.contains(StatusFlags::CARRY));
     }
 
     #[test]
@@ -16,14 +17,14 @@ mod test_adc {
         cpu.register_a = 0x00;
         cpu.load_and_run(vec![0x69, 0x00, 0x00]).unwrap();
         assert_eq!(cpu.register_a, 0x00);
-        assert_eq!(cpu.status & 0b0000_0010, 0b10);
+        assert!(cpu.status.contains(StatusFlags::ZERO));
     }
 
     #[test]
     fn test_adc_with_carry() {
         let mut cpu = CPU::new();
         cpu.register_a = 0x50;
-        cpu.status |= 0b0000_0001;
+        cpu.status.insert(StatusFlags::CARRY);
         cpu.load_and_run(vec![0x69, 0x50, 0x00]).unwrap();
         assert_eq!(cpu.register_a, 0xA1);
     }
@@ -33,6 +34,6 @@ mod test_adc {
         let mut cpu = CPU::new();
         cpu.register_a = 0x50;
         cpu.load_and_run(vec![0x69, 0x50, 0x00]).unwrap();
-        assert_eq!(cpu.status & 0b0100_0000, 0b0100_0000);
+        assert!(cpu.status.contains(StatusFlags::OVERFLOW));
     }
 }
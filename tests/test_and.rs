@@ -1,5 +1,6 @@
 mod test_and {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_and_basic() {
@@ -15,7 +16,7 @@ mod test_and {
         cpu.register_a = 0xFF;
         cpu.load_and_run(vec![0x29, 0x00, 0x00]).unwrap();
         assert_eq!(cpu.register_a, 0x00);
-        assert_eq!(cpu.status & 0b0000_0010, 0b0000_0010);
+        assert!(cpu.status.contains(StatusFlags::ZERO));
     }
 
     #[test]
@@ -24,6 +25,6 @@ mod test_and {
         cpu.register_a = 0xFF;
         cpu.load_and_run(vec![0x29, 0b1000_0000, 0x00]).unwrap();
         assert_eq!(cpu.register_a, 0b1000_0000);
-        assert_eq!(cpu.status & 0b1000_0000, 0b1000_0000);
+        assert!(cpu.status.contains(StatusFlags::NEGATIVE));
     }
 }
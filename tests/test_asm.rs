@@ -0,0 +1,132 @@
+mod test_asm {
+    use nes_emulator::asm;
+    use nes_emulator::asm::{assemble, assemble_segments};
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::error::AsmError;
+
+    #[test]
+    fn test_asm_macro_matches_hand_assembly() {
+        let program = asm!("LDA #$c0", "TAX", "INX", "BRK");
+        assert_eq!(program, vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+
+        let mut cpu = CPU::new();
+        cpu.load_and_run(program).unwrap();
+        assert_eq!(cpu.register_x, 0xc1);
+    }
+
+    #[test]
+    fn test_asm_addressing_modes() {
+        let program = assemble("
+            LDA $10,X
+            LDX $20,Y
+            LDA $1234,Y
+            STA ($40,X)
+            LDA ($40),Y
+            JMP ($FFFC)
+            ASL A
+            LSR
+        ").unwrap();
+
+        assert_eq!(program, vec![
+            0xB5, 0x10,
+            0xB6, 0x20,
+            0xB9, 0x34, 0x12,
+            0x81, 0x40,
+            0xB1, 0x40,
+            0x6C, 0xFC, 0xFF,
+            0x0A,
+            0x4A,
+        ]);
+    }
+
+    #[test]
+    fn test_asm_labels_and_branches() {
+        let program = assemble("
+        start:
+            DEX
+            BNE start      ; backward branch
+            BEQ done       ; forward branch
+            JMP start
+        done:
+            BRK
+        ").unwrap();
+
+        assert_eq!(program, vec![0xCA, 0xD0, 0xFD, 0xF0, 0x03, 0x4C, 0x00, 0x80, 0x00]);
+    }
+
+    #[test]
+    fn test_asm_forward_reference_uses_absolute() {
+        let program = assemble("
+            LDA later
+        later = $10
+        ").unwrap();
+
+        assert_eq!(program, vec![0xAD, 0x10, 0x00]);
+    }
+
+    #[test]
+    fn test_asm_directives_and_expressions() {
+        let program = assemble("
+            .org $C000
+        PPUCTRL = $2000
+        table:
+            .byte 1, 2 + 3, %1010, 'A', \"hi\"
+            .word table, PPUCTRL + 1
+            LDA #<table
+            LDX #>(table + $100)
+            LDY #(1 << 4) | 2
+        ").unwrap();
+
+        assert_eq!(program, vec![
+            0x01, 0x05, 0x0A, 0x41, b'h', b'i',
+            0x00, 0xC0, 0x01, 0x20,
+            0xA9, 0x00,
+            0xA2, 0xC1,
+            0xA0, 0x12,
+        ]);
+    }
+
+    #[test]
+    fn test_asm_quoted_punctuation() {
+        let program = assemble("
+            .byte ';', ',', '\"', \"a;b,c\" ; comment
+            CMP #';'   ; semicolon
+            LDA ',',X
+        ").unwrap();
+
+        assert_eq!(program, vec![
+            b';', b',', b'"', b'a', b';', b'b', b',', b'c',
+            0xC9, b';',
+            0xB5, b',',
+        ]);
+    }
+
+    #[test]
+    fn test_asm_segments() {
+        let segments = assemble_segments("
+            .org $FFFC
+            .word reset
+            .org $C000
+        reset:
+            SEI
+        ").unwrap();
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].origin, 0xFFFC);
+        assert_eq!(segments[0].bytes, vec![0x00, 0xC0]);
+        assert_eq!(segments[1].origin, 0xC000);
+        assert_eq!(segments[1].bytes, vec![0x78]);
+    }
+
+    #[test]
+    fn test_asm_errors() {
+        assert!(matches!(assemble("FOO #1"), Err(AsmError::UnknownInstruction { line: 1, .. })));
+        assert!(matches!(assemble("STA #1"), Err(AsmError::InvalidAddressingMode { .. })));
+        assert!(matches!(assemble("\nJMP nowhere"), Err(AsmError::UndefinedSymbol { line: 2, .. })));
+        assert!(matches!(assemble("a:\na:"), Err(AsmError::DuplicateSymbol { .. })));
+        assert!(matches!(
+            assemble("BNE far\n.org $8100\nfar: BRK"),
+            Err(AsmError::BranchOutOfRange { .. })
+        ));
+    }
+}
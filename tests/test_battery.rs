@@ -0,0 +1,91 @@
+mod common;
+
+mod test_battery {
+    use crate::common::nrom_image;
+    use std::fs;
+    use std::path::PathBuf;
+    use nes_emulator::battery::BatterySave;
+    use nes_emulator::cartridge::Cartridge;
+
+    fn cartridge(battery: bool) -> Cartridge {
+        let mut image = nrom_image("");
+        if battery {
+            image[6] = 0b0000_0010;
+        }
+        Cartridge::new(&image).unwrap()
+    }
+
+    fn temp_rom(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nes-battery-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("game.nes")
+    }
+
+    #[test]
+    fn test_battery_ram_export_import() {
+        let mut cart = cartridge(true);
+        cart.write_prg(0x6000, 0x12);
+        cart.write_prg(0x7FFF, 0x34);
+        assert!(cart.prg_ram_dirty);
+
+        let saved = cart.battery_ram().unwrap().to_vec();
+        assert_eq!(saved.len(), 0x2000);
+
+        let mut other = cartridge(true);
+        other.load_battery_ram(&saved);
+        assert_eq!(other.read_prg(0x6000), 0x12);
+        assert_eq!(other.read_prg(0x7FFF), 0x34);
+        assert!(!other.prg_ram_dirty);
+
+        assert!(cartridge(false).battery_ram().is_none());
+    }
+
+    #[test]
+    fn test_sav_file_next_to_rom() {
+        let rom = temp_rom("file");
+        let mut save = BatterySave::for_rom(&rom, 60);
+        assert_eq!(save.path(), rom.with_extension("sav"));
+
+        let mut cart = cartridge(true);
+        assert!(!save.load(&mut cart).unwrap());
+
+        cart.write_prg(0x6010, 0xAB);
+        save.flush(&mut cart).unwrap();
+        assert!(!cart.prg_ram_dirty);
+        assert_eq!(fs::read(save.path()).unwrap()[0x10], 0xAB);
+
+        let mut reloaded = cartridge(true);
+        assert!(save.load(&mut reloaded).unwrap());
+        assert_eq!(reloaded.read_prg(0x6010), 0xAB);
+
+        fs::remove_dir_all(rom.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_periodic_flush() {
+        let rom = temp_rom("periodic");
+        let mut save = BatterySave::for_rom(&rom, 60);
+        let mut cart = cartridge(true);
+
+        cart.write_prg(0x6000, 1);
+        save.on_frame(&mut cart, 30).unwrap();
+        assert!(!save.path().exists());
+        save.on_frame(&mut cart, 60).unwrap();
+        assert!(save.path().exists());
+
+        fs::remove_dir_all(rom.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_no_file_without_battery() {
+        let rom = temp_rom("none");
+        let mut save = BatterySave::for_rom(&rom, 60);
+        let mut cart = cartridge(false);
+
+        cart.write_prg(0x6000, 1);
+        save.flush(&mut cart).unwrap();
+        assert!(!save.path().exists());
+
+        fs::remove_dir_all(rom.parent().unwrap()).unwrap();
+    }
+}
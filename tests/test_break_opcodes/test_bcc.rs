@@ -1,10 +1,11 @@
 mod test_bcc {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_bcc_no_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0001;
+        cpu.status = StatusFlags::CARRY;
 
         cpu.load_and_run(vec![0x90, 0x05]).unwrap();
         assert_eq!(cpu.program_counter, 0x8003);
@@ -13,7 +14,7 @@ mod test_bcc {
     #[test]
     fn test_bcc_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0000;
+        cpu.status = StatusFlags::empty();
 
         cpu.load_and_run(vec![0x90, 0x05]).unwrap();
         assert_eq!(cpu.program_counter, 0x8008);
@@ -1,10 +1,11 @@
 mod bcs_test {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_bcs_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0000;
+        cpu.status = StatusFlags::empty();
 
         cpu.load_and_run(vec![0x90, 0x05, 0x0a]).unwrap();
         assert_eq!(cpu.program_counter, 0x8008);
@@ -13,7 +14,7 @@ mod bcs_test {
     #[test]
     fn test_bcs_no_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0001;
+        cpu.status = StatusFlags::CARRY;
 
         cpu.load_and_run(vec![0x90, 0x05, 0x0a]).unwrap();
         assert_eq!(cpu.program_counter, 0x8004);
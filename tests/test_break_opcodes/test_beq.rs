@@ -1,10 +1,11 @@
 mod test_beq {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_beq_no_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0000;
+        cpu.status = StatusFlags::empty();
         
         cpu.load_and_run(vec![0xF0, 0x03, 0xA9, 0x0A]).unwrap();
         assert_eq!(cpu.program_counter, 0x8005);
@@ -13,7 +14,7 @@ mod test_beq {
     #[test]
     fn test_beq_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0010;
+        cpu.status = StatusFlags::ZERO;
         
         cpu.load_and_run(vec![0xF0, 0x03, 0xA9, 0x0A]).unwrap();
         assert_eq!(cpu.program_counter, 0x8006);
@@ -2,7 +2,7 @@ mod test_bne {
     #[test]
     fn test_bne_branch() {
         let mut cpu = nes_emulator::cpu::CPU::new();
-        cpu.status = 0b0000_0000;
+        cpu.status = nes_emulator::flags::StatusFlags::empty();
 
         cpu.load_and_run(vec![0xD0, 0x05]).unwrap();
         assert_eq!(cpu.program_counter, 0x8008);
@@ -11,7 +11,7 @@ mod test_bne {
     #[test]
     fn test_bne_no_branch() {
         let mut cpu = nes_emulator::cpu::CPU::new();
-        cpu.status = 0b0000_0010;
+        cpu.status = nes_emulator::flags::StatusFlags::ZERO;
 
         cpu.load_and_run(vec![0xD0, 0x05]).unwrap();
         assert_eq!(cpu.program_counter, 0x8003);
@@ -1,10 +1,11 @@
 mod test_bpl {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_bpl_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0000;
+        cpu.status = StatusFlags::empty();
 
         cpu.load_and_run(vec![0x10, 0x05]).unwrap();
         assert_eq!(cpu.program_counter, 0x8008);
@@ -13,7 +14,7 @@ mod test_bpl {
     #[test]
     fn test_bpl_no_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b1000_0000;
+        cpu.status = StatusFlags::NEGATIVE;
 
         cpu.load_and_run(vec![0x10, 0x05]).unwrap();
         assert_eq!(cpu.program_counter, 0x8003);
@@ -1,10 +1,11 @@
 mod test_bvs {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_bvs_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0100_0000;
+        cpu.status = StatusFlags::OVERFLOW;
 
         cpu.load_and_run(vec![0x70, 0x02]).unwrap();
         assert_eq!(cpu.program_counter, 0x8005);
@@ -13,7 +14,7 @@ mod test_bvs {
     #[test]
     fn test_bvs_no_branch() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0000;
+        cpu.status = StatusFlags::empty();
 
         cpu.load_and_run(vec![0x70, 0x02]).unwrap();
         assert_eq!(cpu.program_counter, 0x8003);
@@ -0,0 +1,181 @@
+mod common;
+
+mod test_bus_cycles {
+    use crate::common::nes;
+    use nes_emulator::asm::assemble;
+    use nes_emulator::bus::{AccessKind, Bus, BusAccess, RamPattern, TestBus};
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::error::SaveStateError;
+    use nes_emulator::flags::StatusFlags;
+    use nes_emulator::savestate::{StateReader, StateWriter};
+    use nes_emulator::statehash::StateHasher;
+
+    fn cpu_with(source: &str, x: u8) -> CPU<TestBus> {
+        let mut bus = TestBus::new();
+        for (i, byte) in assemble(source).unwrap().into_iter().enumerate() {
+            bus.load(0x8000 + i as u16, byte);
+        }
+        let mut cpu = CPU::with_bus(bus);
+        cpu.program_counter = 0x8000;
+        cpu.stack_pointer = 0xFD;
+        cpu.register_x = x;
+        cpu
+    }
+
+    fn read(address: u16, value: u8) -> BusAccess {
+        BusAccess { address, value, kind: AccessKind::Read }
+    }
+
+    fn write(address: u16, value: u8) -> BusAccess {
+        BusAccess { address, value, kind: AccessKind::Write }
+    }
+
+    #[test]
+    fn test_read_modify_write_writes_twice() {
+        let mut cpu = cpu_with("INC $10", 0);
+        cpu.bus.load(0x10, 0x41);
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.bus.accesses, vec![
+            read(0x8000, 0xE6),
+            read(0x8001, 0x10),
+            read(0x0010, 0x41),
+            write(0x0010, 0x41),
+            write(0x0010, 0x42),
+        ]);
+        assert_eq!(cpu.cycles, 5);
+    }
+
+    #[test]
+    fn test_indexed_page_cross_reads_wrong_page() {
+        let mut cpu = cpu_with("LDA $10F0,X", 0x20);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.accesses[3], read(0x1010, 0));
+        assert_eq!(cpu.bus.accesses[4], read(0x1110, 0));
+        assert_eq!(cpu.cycles, 5);
+
+        // no page crossed, no extra read
+        let mut cpu = cpu_with("LDA $1000,X", 0x20);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.accesses.len(), 4);
+        assert_eq!(cpu.cycles, 4);
+
+        // stores always take the extra cycle
+        let mut cpu = cpu_with("STA $1000,X", 0x20);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.accesses[3], read(0x1020, 0));
+        assert_eq!(cpu.bus.accesses[4], write(0x1020, 0));
+        assert_eq!(cpu.cycles, 5);
+    }
+
+    #[test]
+    fn test_branch_timing() {
+        let mut cpu = cpu_with("BNE skip\nskip: NOP", 0);
+        cpu.status.insert(StatusFlags::ZERO);
+        cpu.step().unwrap();
+        assert_eq!(cpu.cycles, 2);
+
+        let mut cpu = cpu_with("BNE skip\nNOP\nskip: NOP", 0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x8003);
+        assert_eq!(cpu.cycles, 3);
+
+        let mut cpu = cpu_with("BNE $7FF0", 0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x7FF0);
+        assert_eq!(cpu.bus.accesses[3], read(0x80F0, 0));
+        assert_eq!(cpu.cycles, 4);
+    }
+
+    /// Flat memory that raises NMI on one particular cycle.
+    struct NmiAt {
+        memory: TestBus,
+        cycle: u64,
+        now: u64,
+    }
+
+    impl Bus for NmiAt {
+        fn mem_read(&mut self, addr: u16) -> u8 {
+            self.memory.peek(addr)
+        }
+        fn mem_write(&mut self, addr: u16, data: u8) {
+            self.memory.load(addr, data);
+        }
+        fn peek(&self, addr: u16) -> u8 {
+            self.memory.peek(addr)
+        }
+        fn power_on(&mut self, _pattern: RamPattern) {}
+        fn reset(&mut self) {}
+        fn tick(&mut self, cycles: u8) {
+            self.now += cycles as u64;
+        }
+        fn poll_nmi(&mut self) -> bool {
+            self.now == self.cycle
+        }
+        fn save_state(&self, _state: &mut StateWriter) {}
+        fn load_state(&mut self, _state: &StateReader) -> Result<(), SaveStateError> {
+            Ok(())
+        }
+        fn hash_state(&self, _hasher: &mut StateHasher) {}
+    }
+
+    fn nmi_at(cycle: u64) -> CPU<NmiAt> {
+        let mut memory = TestBus::new();
+        for (i, byte) in assemble("NOP\nNOP\nNOP").unwrap().into_iter().enumerate() {
+            memory.load(0x8000 + i as u16, byte);
+        }
+        memory.load(0xFFFA, 0x00);
+        memory.load(0xFFFB, 0x90);
+
+        let mut cpu = CPU::with_bus(NmiAt { memory, cycle, now: 0 });
+        cpu.program_counter = 0x8000;
+        cpu.stack_pointer = 0xFD;
+        cpu
+    }
+
+    #[test]
+    fn test_nmi_polled_before_last_cycle() {
+        // raised during the first cycle of a NOP: taken right after it
+        let mut cpu = nmi_at(1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x8001);
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x9000);
+        assert_eq!(cpu.cycles, 2 + 7);
+
+        // raised during its last cycle: one more instruction runs first
+        let mut cpu = nmi_at(2);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x8002);
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x9000);
+    }
+
+    #[test]
+    fn test_oam_dma_halts_cpu() {
+        let mut cpu = nes("
+            .org $C000
+            reset:  LDA #$02
+                    STA $4014
+                    BRK
+            .org $FFFA
+            .word reset, reset, reset
+        ");
+        for i in 0..=0xFF {
+            cpu.bus.mem_write(0x0200 + i, i as u8 ^ 0x5A);
+        }
+
+        cpu.step().unwrap();
+        let before = cpu.cycles;
+        cpu.step().unwrap();
+        let stall = cpu.cycles - before - 4;
+        assert!(stall == 513 || stall == 514, "DMA took {} cycles", stall);
+        // reads from the page fall on even cycles, so a halt on an even
+        // cycle needs a cycle to line up
+        assert_eq!(stall == 514, (before + 4).is_multiple_of(2));
+
+        assert_eq!(cpu.bus.ppu.oam[0], 0x5A);
+        assert_eq!(cpu.bus.ppu.oam[0xFF], 0xA5);
+    }
+}
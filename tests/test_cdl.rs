@@ -0,0 +1,123 @@
+mod common;
+
+mod test_cdl {
+    use crate::common::{console, nrom_image};
+    use nes_emulator::bus::NesBus;
+    use nes_emulator::cartridge::Cartridge;
+    use nes_emulator::cdl::{ChrFlags, CodeDataLogger, PrgFlags};
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::disasm::disassemble_logged;
+
+    /// 16 KiB of PRG-ROM at $C000 and 8 KiB of CHR-ROM, logged.
+    fn nes(source: &str) -> CPU<NesBus> {
+        let mut cartridge = Cartridge::new(&nrom_image(source)).unwrap();
+        cartridge.enable_cdl();
+        console(cartridge)
+    }
+
+    const PROGRAM: &str = "
+        .org $C000
+        reset:  LDA table
+                LDA #<pointed
+                STA $10
+                LDA #>pointed
+                STA $11
+                LDA ($10),Y
+                JMP (vector)
+                .byte $FF
+        target: LDA #$00
+                STA $2006
+                STA $2006
+                LDA $2007
+                LDA $2007
+                LDA #$08
+                STA $2001
+        loop:   JMP loop
+        table:  .byte $01, $02
+        pointed: .byte $03
+        vector: .word target
+        .org $FFFA
+        .word reset, reset, reset
+    ";
+
+    fn prg(cpu: &CPU<NesBus>, addr: u16) -> PrgFlags {
+        cpu.bus.cartridge.cdl.as_ref().unwrap().prg_flags((addr - 0xC000) as usize)
+    }
+
+    #[test]
+    fn test_code_and_data_are_logged() {
+        let mut cpu = nes(PROGRAM);
+        for _ in 0..3 {
+            cpu.run_frame().unwrap();
+        }
+
+        // LDA table: opcode and operand are code, in the $C000 window
+        assert_eq!(prg(&cpu, 0xC000), PrgFlags::CODE | PrgFlags::from_bits_retain(0x08));
+        assert_eq!(prg(&cpu, 0xC002), PrgFlags::CODE | PrgFlags::from_bits_retain(0x08));
+
+        let table = 0xC027;
+        assert!(prg(&cpu, table).contains(PrgFlags::DATA));
+        assert!(!prg(&cpu, table).contains(PrgFlags::CODE));
+        assert!(prg(&cpu, table + 1).is_empty());
+        assert!(prg(&cpu, table + 2).contains(PrgFlags::DATA | PrgFlags::INDIRECT_DATA));
+
+        // the byte skipped by the indirect jump is never touched
+        assert!(prg(&cpu, 0xC010).is_empty());
+        assert!(prg(&cpu, 0xC011).contains(PrgFlags::CODE | PrgFlags::INDIRECT_CODE));
+        assert!(!prg(&cpu, 0xC013).contains(PrgFlags::INDIRECT_CODE));
+
+        // vectors are read as data
+        assert!(prg(&cpu, 0xFFFC).contains(PrgFlags::DATA));
+    }
+
+    #[test]
+    fn test_chr_reads_and_rendering_are_logged() {
+        let mut cpu = nes(PROGRAM);
+        for _ in 0..3 {
+            cpu.run_frame().unwrap();
+        }
+
+        let cdl = cpu.bus.cartridge.cdl.as_ref().unwrap();
+        // tile 0 fills the nametable, so all of its rows are drawn
+        for offset in 0..16 {
+            assert!(cdl.chr_flags(offset).contains(ChrFlags::RENDERED));
+        }
+        assert!(cdl.chr_flags(16).is_empty());
+        // and the program read the first two bytes through $2007
+        assert!(cdl.chr_flags(0).contains(ChrFlags::READ));
+        assert!(cdl.chr_flags(1).contains(ChrFlags::READ));
+        assert!(!cdl.chr_flags(2).contains(ChrFlags::READ));
+    }
+
+    #[test]
+    fn test_cdl_file_layout() {
+        let mut cpu = nes(PROGRAM);
+        cpu.run_frame().unwrap();
+
+        let cdl = cpu.bus.cartridge.cdl.as_ref().unwrap();
+        let bytes = cdl.to_bytes();
+        assert_eq!(bytes.len(), 0x4000 + 0x2000);
+        assert_eq!(bytes[0], cdl.prg_flags(0).bits());
+
+        let restored = CodeDataLogger::from_bytes(&bytes, 0x4000, 0x2000).unwrap();
+        assert_eq!(restored.to_bytes(), bytes);
+        assert!(CodeDataLogger::from_bytes(&bytes, 0x8000, 0x2000).is_none());
+    }
+
+    #[test]
+    fn test_disassembler_seeded_from_log() {
+        let mut cpu = nes(PROGRAM);
+        cpu.run_frame().unwrap();
+
+        let cartridge = &cpu.bus.cartridge;
+        let listing = disassemble_logged(&cartridge.prg_rom, cartridge.cdl.as_ref().unwrap(), 0xC000);
+        let text: Vec<String> = listing.iter().take(9).map(|i| i.to_string()).collect();
+        assert_eq!(text, [
+            "LDA $C027", "LDA #$29", "STA $10", "LDA #$C0", "STA $11",
+            "LDA ($10),Y", "JMP ($C02A)", ".byte $FF", "LDA #$00",
+        ]);
+
+        let table = listing.iter().find(|i| i.address == 0xC027).unwrap();
+        assert_eq!(table.to_string(), ".byte $01");
+    }
+}
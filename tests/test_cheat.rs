@@ -0,0 +1,101 @@
+mod common;
+
+mod test_cheat {
+    use crate::common::nes;
+    use nes_emulator::bus::Bus;
+    use nes_emulator::cheat::{CheatCode, Cheats};
+    use nes_emulator::error::CheatError;
+
+    const PROGRAM: &str = "
+        .org $C000
+        reset:  LDA value
+                STA $10
+                LDA $0810
+                BRK
+        value:  .byte $03
+        .org $FFFC
+        .word reset
+    ";
+
+    #[test]
+    fn test_decode_six_letter_code() {
+        // Super Mario Bros. infinite lives
+        let code = CheatCode::parse("SXIOPO").unwrap();
+        assert_eq!(code, CheatCode::Patch { address: 0x91D9, value: 0xAD, compare: None });
+        assert_eq!(code.to_string(), "SXIOPO");
+        assert_eq!(CheatCode::parse("sxiopo"), Ok(code));
+    }
+
+    #[test]
+    fn test_eight_letter_code_round_trip() {
+        let code = CheatCode::Patch { address: 0xD1DD, value: 0x7E, compare: Some(0x42) };
+        let text = code.to_string();
+        assert_eq!(text.len(), 8);
+        // eight-letter codes have bit 3 set in their third letter
+        assert!("EOXUKSVN".contains(&text[2..3]));
+        assert_eq!(CheatCode::parse(&text), Ok(code));
+    }
+
+    #[test]
+    fn test_freeze_codes() {
+        let freeze = CheatCode::Freeze { address: 0x075F, value: 0x07 };
+        assert_eq!(CheatCode::parse("075F:07"), Ok(freeze));
+        assert_eq!(CheatCode::parse("075f07"), Ok(freeze));
+        assert_eq!(freeze.to_string(), "075F:07");
+        assert_eq!(CheatCode::parse("XYZ"), Err(CheatError::InvalidCode("XYZ".to_string())));
+        assert!(CheatCode::parse("075F:0").is_err());
+    }
+
+    #[test]
+    fn test_patch_applies_to_cartridge_reads() {
+        let mut cpu = nes(PROGRAM);
+        let address = 0xC009;
+        let patch = cpu.bus.cheats.add(CheatCode::Patch { address, value: 0x09, compare: Some(0x03) }, "");
+        let wrong = cpu.bus.cheats.add(CheatCode::Patch { address, value: 0x55, compare: Some(0x04) }, "");
+        assert_eq!(cpu.bus.peek(address), 0x09);
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.bus.peek(0x10), 0x09);
+
+        cpu.bus.cheats.set_enabled(patch, false);
+        cpu.bus.cheats.remove(wrong);
+        cpu.reset();
+        cpu.run().unwrap();
+        assert_eq!(cpu.bus.peek(0x10), 0x03);
+    }
+
+    #[test]
+    fn test_freeze_pins_ram_and_its_mirrors() {
+        let mut cpu = nes(PROGRAM);
+        cpu.bus.cheats.add(CheatCode::parse("0010:63").unwrap(), "pinned");
+        cpu.run().unwrap();
+
+        assert_eq!(cpu.bus.ram()[0x10], 0x03);
+        assert_eq!(cpu.register_a, 0x63);
+        assert_eq!(cpu.bus.peek(0x1810), 0x63);
+    }
+
+    #[test]
+    fn test_cheat_file_round_trip() {
+        let text = "\
+# Super Mario Bros.
+on  SXIOPO    Infinite lives
+off 075F:07   Start in world 8
+
+on  AAEAULPA
+";
+        let cheats = Cheats::parse(text).unwrap();
+        assert_eq!(cheats.len(), 3);
+        assert_eq!(cheats.list()[0].description, "Infinite lives");
+        assert!(!cheats.list()[1].enabled);
+        assert_eq!(cheats.list()[2].description, "");
+
+        assert_eq!(Cheats::parse(&cheats.to_text()), Ok(cheats.clone()));
+        assert_eq!(cheats.to_text().lines().next(), Some("on  SXIOPO    Infinite lives"));
+
+        assert_eq!(Cheats::parse("on SXIOPO\nmaybe SXIOPO"), Err(CheatError::Syntax {
+            line: 2,
+            message: "expected on or off, found \"maybe\"".to_string(),
+        }));
+    }
+}
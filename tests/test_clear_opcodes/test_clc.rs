@@ -1,11 +1,12 @@
 mod test_clc {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_clc() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0001;
+        cpu.status = StatusFlags::CARRY;
         cpu.load_and_run(vec![0x18]).unwrap();
-        assert_eq!(cpu.status, 0b0000_0000);
+        assert_eq!(cpu.status, StatusFlags::empty());
     }
 }
\ No newline at end of file
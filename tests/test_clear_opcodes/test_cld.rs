@@ -1,11 +1,12 @@
 mod test_cld {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_cld() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_1000;
+        cpu.status = StatusFlags::DECIMAL;
         cpu.load_and_run(vec![0xD8]).unwrap();
-        assert_eq!(cpu.status, 0b0000_0000);
+        assert_eq!(cpu.status, StatusFlags::empty());
     }
 }
\ No newline at end of file
@@ -1,11 +1,12 @@
 mod test_cli {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_cli() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0000_0100;
+        cpu.status = StatusFlags::INTERRUPT_DISABLE;
         cpu.load_and_run(vec![0x58]).unwrap();
-        assert_eq!(cpu.status, 0b0000_0000);
+        assert_eq!(cpu.status, StatusFlags::empty());
     }
 }
\ No newline at end of file
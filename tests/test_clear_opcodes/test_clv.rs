@@ -1,11 +1,12 @@
 mod test_clv {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_clv() {
         let mut cpu = CPU::new();
-        cpu.status = 0b0100_0000;
+        cpu.status = StatusFlags::OVERFLOW;
         cpu.load_and_run(vec![0xB8]).unwrap();
-        assert_eq!(cpu.status, 0b0000_0000);
+        assert_eq!(cpu.status, StatusFlags::empty());
     }
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+mod test_debugger {
+    use nes_emulator::asm;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::debugger::{BreakpointKind, Debugger, Frame, Response, StopReason};
+
+    fn cpu_with(program: Vec<u8>) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.load(program);
+        cpu.stack_pointer = 0xFF;
+        cpu
+    }
+
+    fn output(response: Response) -> String {
+        match response {
+            Response::Output(text) => text,
+            Response::Quit => panic!("unexpected quit"),
+        }
+    }
+
+    #[test]
+    fn test_address_breakpoint() {
+        let mut cpu = cpu_with(asm!("LDA #0", "TAX", "loop: INX", "CPX #5", "BNE loop", "BRK"));
+        let mut debugger = Debugger::new();
+        let id = debugger.add_breakpoint(BreakpointKind::Address(0x8008), vec![]);
+
+        assert!(matches!(debugger.resume(&mut cpu, None), StopReason::Breakpoint(hit) if hit == id));
+        assert_eq!(cpu.program_counter, 0x8008);
+        assert_eq!(cpu.register_x, 5);
+
+        assert!(matches!(debugger.resume(&mut cpu, None), StopReason::Halted));
+    }
+
+    #[test]
+    fn test_conditional_breakpoint_command() {
+        let mut cpu = cpu_with(asm!("LDA #0", "TAX", "loop: INX", "CPX #5", "BNE loop", "BRK"));
+        let mut debugger = Debugger::new();
+
+        assert_eq!(output(debugger.execute(&mut cpu, "break $8004 if X == 3")), "breakpoint #1 set");
+        let text = output(debugger.execute(&mut cpu, "continue"));
+
+        assert!(text.starts_with("breakpoint #1 hit"));
+        assert_eq!(cpu.register_x, 3);
+    }
+
+    #[test]
+    fn test_watchpoint_stops_before_write() {
+        let mut cpu = cpu_with(asm!("LDA #1", "STA $10", "INY", "INY", "STA ($20),Y", "BRK"));
+        cpu.mem_write(0x20, 0x00);
+        cpu.mem_write(0x21, 0x03);
+        let mut debugger = Debugger::new();
+        output(debugger.execute(&mut cpu, "watch w $0302"));
+
+        assert!(matches!(debugger.resume(&mut cpu, None), StopReason::Breakpoint(1)));
+        assert_eq!(cpu.program_counter, 0x8006);
+        assert_eq!(cpu.mem_read(0x0302), 0);
+    }
+
+    #[test]
+    fn test_step_count_stops_at_breakpoints() {
+        let mut cpu = cpu_with(asm!("LDA #1", "STA $10", "INX", "INX", "INX", "BRK"));
+        let mut debugger = Debugger::new();
+        output(debugger.execute(&mut cpu, "watch w $10"));
+        output(debugger.execute(&mut cpu, "break $8006"));
+
+        let text = output(debugger.execute(&mut cpu, "step 10"));
+        assert!(text.starts_with("breakpoint #1 hit"), "{}", text);
+        assert_eq!(cpu.program_counter, 0x8002);
+        assert_eq!(cpu.mem_read(0x10), 0);
+
+        // stepping off a breakpoint goes ahead
+        let text = output(debugger.execute(&mut cpu, "step 10"));
+        assert!(text.starts_with("breakpoint #2 hit"), "{}", text);
+        assert_eq!((cpu.program_counter, cpu.register_x), (0x8006, 2));
+
+        let text = output(debugger.execute(&mut cpu, "step 1"));
+        assert!(!text.contains("hit"), "{}", text);
+        assert_eq!(cpu.register_x, 3);
+    }
+
+    #[test]
+    fn test_next_steps_over_subroutine() {
+        let mut cpu = cpu_with(asm!("JSR sub", "INX", "BRK", "sub: INY", "INY", "RTS"));
+        let mut debugger = Debugger::new();
+
+        assert!(matches!(debugger.next(&mut cpu), StopReason::Stepped));
+        assert_eq!(cpu.program_counter, 0x8003);
+        assert_eq!(cpu.register_y, 2);
+        assert!(debugger.call_stack().is_empty());
+    }
+
+    #[test]
+    fn test_call_stack_tracks_jsr_and_rts() {
+        let mut cpu = cpu_with(asm!("JSR sub", "BRK", "sub: INY", "RTS"));
+        let mut debugger = Debugger::new();
+
+        debugger.step(&mut cpu).unwrap();
+        assert_eq!(debugger.call_stack(), &[Frame {
+            call_site: 0x8000,
+            target: 0x8004,
+            return_address: 0x8003,
+        }]);
+
+        debugger.step(&mut cpu).unwrap();
+        debugger.step(&mut cpu).unwrap();
+        assert!(debugger.call_stack().is_empty());
+        assert_eq!(cpu.program_counter, 0x8003);
+    }
+
+    #[test]
+    fn test_set_and_mem_commands() {
+        let mut cpu = cpu_with(asm!("BRK"));
+        let mut debugger = Debugger::new();
+
+        output(debugger.execute(&mut cpu, "set a $42"));
+        output(debugger.execute(&mut cpu, "poke $0200 $48 $49"));
+
+        assert_eq!(cpu.register_a, 0x42);
+        assert!(output(debugger.execute(&mut cpu, "mem $0200 2")).starts_with("0200: 48 49"));
+        assert!(matches!(debugger.execute(&mut cpu, "quit"), Response::Quit));
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+mod test_determinism {
+    use crate::common::nrom_image;
+    use nes_emulator::bus::{Bus, NesBus, RamPattern};
+    use nes_emulator::cartridge::Cartridge;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::input::InputLayout;
+    use nes_emulator::statehash::StateHasher;
+
+    /// Mixes uninitialised RAM into a running value every frame, so any
+    /// difference in power-up state shows in later hashes.
+    const PROGRAM: &str = "
+        .org $C000
+        reset:  BIT $2002
+                LDA #$80
+                STA $2000
+        loop:   JMP loop
+        nmi:    INC $10
+                LDA ($10),Y
+                EOR $11
+                ASL A
+                ADC #$3B
+                STA $11
+                INY
+                RTI
+        .org $FFFA
+        .word nmi, reset, reset
+    ";
+
+    fn nes(pattern: RamPattern) -> CPU<NesBus> {
+        let mut cpu = CPU::with_bus(NesBus::new(Cartridge::new(&nrom_image(PROGRAM)).unwrap()));
+        cpu.power_on(pattern);
+        cpu
+    }
+
+    fn hashes(cpu: &mut CPU<NesBus>, frames: usize) -> Vec<u64> {
+        (0..frames)
+            .map(|_| {
+                cpu.run_frame().unwrap();
+                cpu.state_hash()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hasher_is_fnv1a() {
+        let mut hasher = StateHasher::new();
+        assert_eq!(hasher.finish(), 0xCBF2_9CE4_8422_2325);
+        hasher.write_bytes(b"a");
+        assert_eq!(hasher.finish(), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn test_same_seed_same_hashes() {
+        let first = hashes(&mut nes(RamPattern::Random(42)), 30);
+        let second = hashes(&mut nes(RamPattern::Random(42)), 30);
+        assert_eq!(first, second);
+
+        let other = hashes(&mut nes(RamPattern::Random(43)), 30);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_hash_changes_every_frame() {
+        let sequence = hashes(&mut nes(RamPattern::Zeros), 10);
+        for pair in sequence.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_save_state_preserves_hash_sequence() {
+        let mut cpu = nes(RamPattern::Random(7));
+        hashes(&mut cpu, 5);
+        let state = cpu.save_state();
+        let expected = hashes(&mut cpu, 10);
+
+        let mut restored = nes(RamPattern::Zeros);
+        restored.load_state(&state).unwrap();
+        assert_eq!(hashes(&mut restored, 10), expected);
+    }
+
+    #[test]
+    fn test_hash_covers_controller_ports() {
+        let mut cpu = nes(RamPattern::Zeros);
+        let before = cpu.state_hash();
+
+        // how far a game has read the controller decides its next reads
+        cpu.bus.mem_write(0x4016, 1);
+        cpu.bus.mem_write(0x4016, 0);
+        let strobed = cpu.state_hash();
+        cpu.bus.mem_read(0x4016);
+        assert_ne!(cpu.state_hash(), strobed);
+
+        cpu.bus.ports = InputLayout::FourScore.devices();
+        assert_ne!(cpu.state_hash(), before);
+    }
+}
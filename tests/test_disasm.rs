@@ -0,0 +1,67 @@
+mod test_disasm {
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::disasm::{disassemble, disassemble_range};
+
+    fn text(bytes: &[u8], origin: u16) -> Vec<String> {
+        disassemble(bytes, origin).iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn test_disasm_addressing_modes() {
+        let program = [
+            0xA9, 0x10,       // LDA #$10
+            0xB5, 0x10,       // LDA $10,X
+            0xB6, 0x20,       // LDX $20,Y
+            0xBD, 0x34, 0x12, // LDA $1234,X
+            0x6C, 0xFC, 0xFF, // JMP ($FFFC)
+            0xA1, 0x40,       // LDA ($40,X)
+            0xB1, 0x40,       // LDA ($40),Y
+            0x0A,             // ASL A
+            0xAA,             // TAX
+        ];
+
+        assert_eq!(text(&program, 0x8000), vec![
+            "LDA #$10",
+            "LDA $10,X",
+            "LDX $20,Y",
+            "LDA $1234,X",
+            "JMP ($FFFC)",
+            "LDA ($40,X)",
+            "LDA ($40),Y",
+            "ASL A",
+            "TAX",
+        ]);
+    }
+
+    #[test]
+    fn test_disasm_resolves_branch_targets() {
+        let instructions = disassemble(&[0xD0, 0xFE, 0x90, 0x05], 0xC000);
+
+        assert_eq!(instructions[0].to_string(), "BNE $C000");
+        assert_eq!(instructions[0].target(), Some(0xC000));
+        assert_eq!(instructions[1].to_string(), "BCC $C009");
+    }
+
+    #[test]
+    fn test_disasm_unknown_and_truncated_bytes() {
+        let instructions = disassemble(&[0x02, 0xAD, 0x00], 0x8000);
+
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].to_string(), ".byte $02");
+        assert!(instructions[0].opcode.is_none());
+        assert_eq!(instructions[1].to_string(), ".byte $AD");
+    }
+
+    #[test]
+    fn test_disasm_bus_range() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0x20, 0x00, 0x90, 0xE8, 0x00]);
+
+        let instructions = disassemble_range(&cpu.bus, 0x8000, 0x8004);
+
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].listing(), "8000  20 00 90  JSR $9000");
+        assert_eq!(instructions[1].address, 0x8003);
+        assert_eq!(instructions[2].mnemonic(), "BRK");
+    }
+}
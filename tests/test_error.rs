@@ -0,0 +1,37 @@
+mod test_error {
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::error::CPUError;
+
+    #[test]
+    fn test_unknown_opcode_records_state() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xA9, 0x05, 0xE8, 0x02]);
+        cpu.register_x = 0x10;
+
+        let err = cpu.run().unwrap_err();
+
+        assert!(matches!(err, CPUError::UnknownOpcode(_)));
+        let state = err.state();
+        assert_eq!(state.program_counter, 0x8003);
+        assert_eq!(state.opcode, 0x02);
+        assert_eq!(state.register_a, 0x05);
+        assert_eq!(state.register_x, 0x11);
+        assert_eq!(state.cycles, 4);
+    }
+
+    #[test]
+    fn test_error_is_std_error() {
+        let mut cpu = CPU::new();
+        let err: Box<dyn std::error::Error> = cpu.load_and_run(vec![0x02]).unwrap_err().into();
+
+        assert!(err.to_string().starts_with("unknown opcode: 0x02 at $8000"));
+    }
+
+    #[test]
+    fn test_cycles_are_counted() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]).unwrap();
+
+        assert_eq!(cpu.cycles, 2 + 2 + 2 + 7);
+    }
+}
@@ -0,0 +1,24 @@
+mod test_flags {
+    use nes_emulator::flags::StatusFlags;
+
+    #[test]
+    fn test_flags_display_all_clear() {
+        assert_eq!(StatusFlags::empty().to_string(), "nv-bdizc");
+    }
+
+    #[test]
+    fn test_flags_display_mixed() {
+        let status = StatusFlags::NEGATIVE | StatusFlags::UNUSED | StatusFlags::ZERO;
+        assert_eq!(status.to_string(), "Nv-bdiZc");
+    }
+
+    #[test]
+    fn test_flags_set_and_clear() {
+        let mut status = StatusFlags::empty();
+        status.set(StatusFlags::CARRY, true);
+        assert!(status.contains(StatusFlags::CARRY));
+
+        status.clear(StatusFlags::CARRY);
+        assert_eq!(status.bits(), 0);
+    }
+}
@@ -0,0 +1,185 @@
+mod test_gdb {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use nes_emulator::asm;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::gdb::GdbStub;
+
+    fn cpu_with(program: Vec<u8>) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.load(program);
+        cpu.stack_pointer = 0xFF;
+        cpu
+    }
+
+    fn send(stub: &mut GdbStub, cpu: &mut CPU, packet: &str) -> String {
+        stub.handle_packet(cpu, packet, &mut || false).unwrap()
+    }
+
+    #[test]
+    fn test_gdb_registers() {
+        let mut cpu = cpu_with(asm!("BRK"));
+        let mut stub = GdbStub::new();
+        cpu.register_a = 0x12;
+        cpu.register_x = 0x34;
+
+        assert_eq!(send(&mut stub, &mut cpu, "g"), "12340000ff0080");
+        assert_eq!(send(&mut stub, &mut cpu, "P5=0490"), "OK");
+        assert_eq!(cpu.program_counter, 0x9004);
+        assert_eq!(send(&mut stub, &mut cpu, "p2"), "00");
+    }
+
+    #[test]
+    fn test_gdb_memory() {
+        let mut cpu = cpu_with(asm!("BRK"));
+        let mut stub = GdbStub::new();
+
+        assert_eq!(send(&mut stub, &mut cpu, "M0200,3:a1b2c3"), "OK");
+        assert_eq!(send(&mut stub, &mut cpu, "m01ff,4"), "00a1b2c3");
+        assert_eq!(send(&mut stub, &mut cpu, "M0200,2:a1"), "E01");
+
+        // replies are limited to the announced packet size
+        assert_eq!(send(&mut stub, &mut cpu, "m0,800").len(), 0x1000);
+        assert_eq!(send(&mut stub, &mut cpu, "m0,801"), "E01");
+        assert_eq!(send(&mut stub, &mut cpu, "m0,ffffffffffffffff"), "E01");
+    }
+
+    #[test]
+    fn test_gdb_step_and_breakpoint() {
+        let mut cpu = cpu_with(asm!("LDA #1", "TAX", "INX", "INX", "BRK"));
+        let mut stub = GdbStub::new();
+
+        assert_eq!(send(&mut stub, &mut cpu, "s"), "S05");
+        assert_eq!(cpu.program_counter, 0x8002);
+
+        assert_eq!(send(&mut stub, &mut cpu, "Z0,8004,1"), "OK");
+        assert_eq!(send(&mut stub, &mut cpu, "c"), "T05swbreak:;");
+        assert_eq!(cpu.program_counter, 0x8004);
+        assert_eq!(cpu.register_x, 2);
+
+        assert_eq!(send(&mut stub, &mut cpu, "z0,8004,1"), "OK");
+        assert_eq!(send(&mut stub, &mut cpu, "c"), "S05");
+        assert_eq!(cpu.register_x, 3);
+    }
+
+    #[test]
+    fn test_gdb_watchpoint() {
+        let mut cpu = cpu_with(asm!("LDA #7", "STA $10", "STA $20", "BRK"));
+        let mut stub = GdbStub::new();
+
+        assert_eq!(send(&mut stub, &mut cpu, "Z2,20,1"), "OK");
+        assert_eq!(send(&mut stub, &mut cpu, "c"), "T05watch:20;");
+        assert_eq!(cpu.program_counter, 0x8004);
+        assert_eq!(cpu.mem_read(0x10), 7);
+        assert_eq!(cpu.mem_read(0x20), 0);
+    }
+
+    #[test]
+    fn test_gdb_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut cpu = cpu_with(asm!("LDA #$42", "BRK"));
+            let (stream, _) = listener.accept().unwrap();
+            GdbStub::new().serve(&mut cpu, stream).unwrap();
+            cpu
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        let mut exchange = |packet: &str| {
+            let checksum = packet.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+            write!(client, "${}#{:02x}", packet, checksum).unwrap();
+
+            let mut reply = Vec::new();
+            let mut byte = [0u8];
+            while !reply.ends_with(b"#") {
+                client.read_exact(&mut byte).unwrap();
+                reply.push(byte[0]);
+            }
+            client.read_exact(&mut [0u8; 2]).unwrap();
+            client.write_all(b"+").unwrap();
+            String::from_utf8(reply).unwrap()
+        };
+
+        assert_eq!(exchange("s"), "+$S05#");
+        assert_eq!(exchange("p0"), "+$42#");
+        assert_eq!(exchange("D"), "+$OK#");
+
+        let cpu = server.join().unwrap();
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_gdb_rejects_oversized_packets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut cpu = cpu_with(asm!("LDA #$42", "BRK"));
+            let (stream, _) = listener.accept().unwrap();
+            GdbStub::new().serve(&mut cpu, stream)
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        let body = "m".repeat(0x1001);
+        let checksum = body.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(client, "${}#{:02x}", body, checksum).unwrap();
+        let mut reply = [0u8; 8];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"+$E01#a6");
+
+        // the connection is still in step afterwards
+        client.write_all(b"+$p0#a0").unwrap();
+        let mut reply = [0u8; 7];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"+$00#60");
+
+        drop(client);
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_gdb_client_leaves_while_running() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut cpu = cpu_with(asm!("loop: JMP loop"));
+            let (stream, _) = listener.accept().unwrap();
+            GdbStub::new().serve(&mut cpu, stream)
+        });
+
+        // stray acks while running are not mistaken for anything else
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"$c#63++").unwrap();
+        let mut ack = [0u8];
+        client.read_exact(&mut ack).unwrap();
+        assert_eq!(&ack, b"+");
+        drop(client);
+
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_gdb_ctrl_c_stops_running_program() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut cpu = cpu_with(asm!("loop: JMP loop"));
+            let (stream, _) = listener.accept().unwrap();
+            GdbStub::new().serve(&mut cpu, stream)
+        });
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"$c#63+\x03").unwrap();
+        let mut reply = [0u8; 8];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"+$S02#b5");
+
+        drop(client);
+        server.join().unwrap().unwrap();
+    }
+}
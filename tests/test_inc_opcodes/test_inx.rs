@@ -1,5 +1,6 @@
 mod test_inx {
     use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
 
     #[test]
     fn test_inx() {
@@ -9,8 +10,8 @@ mod test_inx {
         cpu.load_and_run(vec![0xe8, 0x00]).unwrap();
 
         assert_eq!(cpu.register_x, 0x02);
-        assert_eq!(cpu.status & 0b0000_0010, 0);
-        assert_eq!(cpu.status & 0b1000_0000, 0);
+        assert!(!cpu.status.contains(StatusFlags::ZERO));
+        assert!(!cpu.status.contains(StatusFlags::NEGATIVE));
     }
     #[test]
     fn test_inx_overflow() {
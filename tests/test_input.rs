@@ -0,0 +1,148 @@
+mod common;
+
+mod test_input {
+    use crate::common::nes;
+    use nes_emulator::bus::{Bus, NesBus};
+    use nes_emulator::error::SaveStateError;
+    use nes_emulator::input::{InputLayout, PortDevice};
+    use nes_emulator::joypad::Buttons;
+    use nes_emulator::savestate::crc32;
+
+    /// Strobes the ports, then reads `count` bits from `addr`, masked to `mask`.
+    fn read_bits(bus: &mut NesBus, addr: u16, mask: u8, count: usize) -> Vec<u8> {
+        bus.mem_write(0x4016, 1);
+        bus.mem_write(0x4016, 0);
+        (0..count).map(|_| bus.mem_read(addr) & mask).collect()
+    }
+
+    fn bits(byte: u8) -> Vec<u8> {
+        (0..8).map(|bit| (byte >> bit) & 1).collect()
+    }
+
+    #[test]
+    fn test_four_score_stream() {
+        let mut bus = nes("").bus;
+        bus.ports = InputLayout::FourScore.devices();
+        bus.set_buttons(0, Buttons::A);
+        bus.set_buttons(1, Buttons::B);
+        bus.set_buttons(2, Buttons::START);
+        bus.set_buttons(3, Buttons::RIGHT);
+
+        let port1 = read_bits(&mut bus, 0x4016, 0x01, 25);
+        assert_eq!(port1[..8], bits(0x01));
+        assert_eq!(port1[8..16], bits(0x08));
+        assert_eq!(port1[16..24], [0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(port1[24], 1);
+
+        let port2 = read_bits(&mut bus, 0x4017, 0x01, 24);
+        assert_eq!(port2[..8], bits(0x02));
+        assert_eq!(port2[8..16], bits(0x80));
+        assert_eq!(port2[16..24], [0, 0, 1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_hori_stream() {
+        let mut bus = nes("").bus;
+        bus.ports = InputLayout::Hori.devices();
+        bus.set_buttons(0, Buttons::A);
+        bus.set_buttons(2, Buttons::START);
+        bus.set_buttons(3, Buttons::UP);
+
+        // the built-in controllers on D0 work as usual
+        let d0 = read_bits(&mut bus, 0x4016, 0x01, 9);
+        assert_eq!(d0, [1, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let port1 = read_bits(&mut bus, 0x4016, 0x02, 24);
+        assert_eq!(port1[..8].iter().map(|bit| bit >> 1).collect::<Vec<_>>(), bits(0x08));
+        assert_eq!(port1[8..16], [0; 8]);
+        assert_eq!(port1[16..24], [0, 0, 2, 0, 0, 0, 0, 0]);
+
+        let port2 = read_bits(&mut bus, 0x4017, 0x02, 24);
+        assert_eq!(port2[..8].iter().map(|bit| bit >> 1).collect::<Vec<_>>(), bits(0x10));
+        assert_eq!(port2[16..24], [0, 0, 0, 2, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_players_without_controllers() {
+        let mut bus = nes("").bus;
+        assert!(bus.buttons_mut(2).is_none());
+        // setting a missing player's buttons does nothing
+        bus.set_buttons(3, Buttons::A);
+
+        bus.ports = InputLayout::Zapper.devices();
+        assert!(bus.buttons_mut(1).is_none());
+        assert!(bus.zapper_mut().is_some());
+
+        bus.ports[1] = PortDevice::Empty;
+        assert_eq!(bus.mem_read(0x4017) & 0x1F, 0);
+    }
+
+    #[test]
+    fn test_game_reads_all_four_players() {
+        // reads each port's 24 bits into $10-$12 and $13-$15, the way
+        // Four Score games do
+        let mut cpu = nes("
+            .org $C000
+            reset:  LDA #1
+                    STA $4016
+                    LDA #0
+                    STA $4016
+                    LDX #0
+            byte:   LDY #8
+            bit:    LDA $4016
+                    LSR A
+                    ROR $10,X
+                    LDA $4017
+                    LSR A
+                    ROR $13,X
+                    DEY
+                    BNE bit
+                    INX
+                    CPX #3
+                    BNE byte
+                    BRK
+            .org $FFFA
+            .word reset, reset, reset
+        ");
+        cpu.bus.ports = InputLayout::FourScore.devices();
+        cpu.bus.set_buttons(2, Buttons::A | Buttons::B);
+        cpu.bus.set_buttons(3, Buttons::LEFT);
+        cpu.run().unwrap();
+
+        // bits arrive least significant first, so ROR rebuilds each byte
+        assert_eq!(cpu.bus.peek(0x11), 0x03);
+        assert_eq!(cpu.bus.peek(0x14), 0x40);
+        assert_eq!(cpu.bus.peek(0x12), 0x08);
+        assert_eq!(cpu.bus.peek(0x15), 0x04);
+    }
+
+    #[test]
+    fn test_devices_survive_save_state() {
+        let mut cpu = nes("");
+        cpu.bus.ports = InputLayout::FourScore.devices();
+        cpu.bus.set_buttons(3, Buttons::SELECT);
+        cpu.bus.mem_write(0x4016, 1);
+        cpu.bus.mem_write(0x4016, 0);
+        for _ in 0..10 {
+            cpu.bus.mem_read(0x4017);
+        }
+        let state = cpu.save_state();
+
+        cpu.bus.ports = InputLayout::Standard.devices();
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.bus.buttons_mut(3).copied(), Some(Buttons::SELECT));
+        // the 11th bit of port 2 is player 4's SELECT
+        assert_eq!(cpu.bus.mem_read(0x4017) & 1, 1);
+
+        // a device kind no build writes is refused, not unplugged
+        let mut corrupt = state.clone();
+        let ports = corrupt.windows(4).position(|tag| tag == b"PORT").unwrap();
+        corrupt[ports + 8] = 9;
+        let crc = crc32(&corrupt[16..]);
+        corrupt[12..16].copy_from_slice(&crc.to_le_bytes());
+        cpu.bus.ports = InputLayout::Zapper.devices();
+        assert_eq!(cpu.load_state(&corrupt),
+            Err(SaveStateError::InvalidValue("controller port device".to_string())));
+        assert!(matches!(cpu.bus.ports[1], PortDevice::Zapper(_)));
+    }
+}
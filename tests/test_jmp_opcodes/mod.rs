@@ -1 +1,2 @@
-pub mod test_jmp;
\ No newline at end of file
+pub mod test_jmp;
+pub mod test_jsr;
\ No newline at end of file
@@ -0,0 +1,30 @@
+mod test_jsr {
+    use nes_emulator::cpu::CPU;
+
+    #[test]
+    fn test_jsr_pushes_return_address() {
+        let mut cpu = CPU::new();
+        cpu.stack_pointer = 0xFF;
+
+        // JSR $8004 ; BRK ; INX ; BRK
+        cpu.load_and_run(vec![0x20, 0x04, 0x80, 0x00, 0xE8, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.stack_pointer, 0xFD);
+        assert_eq!(cpu.mem_read(0x01FF), 0x80);
+        assert_eq!(cpu.mem_read(0x01FE), 0x02);
+    }
+
+    #[test]
+    fn test_jsr_rts_round_trip() {
+        let mut cpu = CPU::new();
+        cpu.stack_pointer = 0xFF;
+
+        // JSR $8006 ; INX ; BRK ; (pad) ; INX ; RTS
+        cpu.load_and_run(vec![0x20, 0x06, 0x80, 0xE8, 0x00, 0x00, 0xE8, 0x60]).unwrap();
+
+        assert_eq!(cpu.register_x, 2);
+        assert_eq!(cpu.stack_pointer, 0xFF);
+        assert_eq!(cpu.program_counter, 0x8005);
+    }
+}
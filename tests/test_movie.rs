@@ -0,0 +1,204 @@
+mod common;
+
+mod test_movie {
+    use crate::common::nrom_image;
+    use nes_emulator::bus::{Bus, NesBus};
+    use nes_emulator::cartridge::Cartridge;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::error::MovieError;
+    use nes_emulator::input::{InputLayout, PortDevice};
+    use nes_emulator::joypad::Buttons;
+    use nes_emulator::movie::{rom_checksum, Movie, MovieCommands, MovieFrame, Playback, Player, Recorder};
+    use nes_emulator::region::Region;
+
+    /// Counts frames in $11 and frames with A held on controller 1 in $10.
+    const PROGRAM: &str = "
+        .org $C000
+        reset:  BIT $2002
+                LDA #$80
+                STA $2000
+        loop:   JMP loop
+        nmi:    LDA #1
+                STA $4016
+                LDA #0
+                STA $4016
+                LDA $4016
+                AND #1
+                CLC
+                ADC $10
+                STA $10
+                INC $11
+                RTI
+        .org $FFFA
+        .word nmi, reset, reset
+    ";
+
+    fn nes() -> CPU<NesBus> {
+        CPU::with_bus(NesBus::new(Cartridge::new(&nrom_image(PROGRAM)).unwrap()))
+    }
+
+    fn pad(buttons: Buttons) -> MovieFrame {
+        MovieFrame { commands: MovieCommands::empty(), pads: [buttons, Buttons::empty(), Buttons::empty(), Buttons::empty()] }
+    }
+
+    fn record(frames: usize) -> Movie {
+        let mut cpu = nes();
+        let header = Movie { rom_filename: "counter".to_string(), ..Movie::default() };
+        let mut recorder = Recorder::start(&mut cpu, header, 4);
+        for i in 0..frames {
+            let buttons = if i % 3 == 0 { Buttons::A } else { Buttons::empty() };
+            recorder.frame(&mut cpu, pad(buttons)).unwrap();
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn test_fm2_parse() {
+        let text = "version 3\nemuVersion 20604\nrerecordCount 7\npalFlag 0\n\
+            romFilename smb\nguid 1234\nport0 1\nport1 0\ncomment author me\n\
+            |0|R......A||\n|1|.L..T...||\n|2|........||\n";
+        let movie = Movie::parse_fm2(text).unwrap();
+
+        assert_eq!(movie.rerecord_count, 7);
+        assert_eq!(movie.rom_filename, "smb");
+        assert_eq!(movie.ports, [true, false]);
+        assert_eq!(movie.comments, vec!["author me"]);
+        assert_eq!(movie.frames.len(), 3);
+        assert_eq!(movie.frames[0].pads[0], Buttons::RIGHT | Buttons::A);
+        assert_eq!(movie.frames[1].pads[0], Buttons::LEFT | Buttons::START);
+        assert_eq!(movie.frames[1].commands, MovieCommands::RESET);
+        assert_eq!(movie.frames[2].commands, MovieCommands::POWER);
+    }
+
+    #[test]
+    fn test_fm2_errors() {
+        assert_eq!(Movie::parse_fm2("version 2\n"), Err(MovieError::UnsupportedVersion("2".to_string())));
+        assert!(matches!(Movie::parse_fm2("version 3\nport1 2\n"),
+            Err(MovieError::UnsupportedDevice { port: 1, .. })));
+        assert!(matches!(Movie::parse_fm2("version 3\n|0|RL|........|\n"),
+            Err(MovieError::Syntax { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_fourscore_movie() {
+        let text = "version 3\nfourscore 1\nport0 0\nport1 0\n\
+            |0|.......A|........|......B.|....T...||\n";
+        let movie = Movie::parse_fm2(text).unwrap();
+        assert!(movie.fourscore);
+        assert_eq!(movie.frames[0].pads, [Buttons::A, Buttons::empty(), Buttons::B, Buttons::START]);
+        assert!(movie.to_fm2().contains("fourscore 1\n"));
+        assert!(movie.to_fm2().ends_with(&text[text.find('|').unwrap()..]));
+        assert_eq!(Movie::parse_fm2(&movie.to_fm2()).unwrap(), movie);
+
+        // playback plugs in a Four Score and feeds all four players
+        let mut cpu = nes();
+        let mut player = Player::start(&mut cpu, movie).unwrap();
+        assert!(matches!(cpu.bus.ports[0], PortDevice::FourScore(_)));
+        player.play_to_end(&mut cpu).unwrap();
+        assert_eq!(cpu.bus.buttons_mut(2).copied(), Some(Buttons::B));
+        assert_eq!(cpu.bus.buttons_mut(3).copied(), Some(Buttons::START));
+        assert_eq!(cpu.bus.peek(0x10), 1);
+
+        // and recording on one keeps players 3 and 4
+        let mut recorder = Recorder::start(&mut cpu, Movie::default(), 0);
+        let mut input = pad(Buttons::empty());
+        input.pads[3] = Buttons::SELECT;
+        recorder.frame(&mut cpu, input).unwrap();
+        let movie = recorder.finish();
+        assert!(movie.fourscore);
+        assert_eq!(movie.frames[0].pads[3], Buttons::SELECT);
+
+        // a two player movie unplugs it again
+        Player::start(&mut cpu, Movie::default()).unwrap();
+        assert!(matches!(cpu.bus.ports[0], PortDevice::Joypad(_)));
+    }
+
+    #[test]
+    fn test_fm2_round_trip() {
+        let movie = record(12);
+        assert_eq!(movie.checksums.len(), 3);
+
+        let text = movie.to_fm2();
+        assert!(text.contains("|0|.......A|........||\n|0|........|........||\n"));
+        assert_eq!(Movie::parse_fm2(&text).unwrap(), movie);
+    }
+
+    #[test]
+    fn test_playback_reproduces_recording() {
+        let movie = record(12);
+        let mut cpu = nes();
+        let mut player = Player::start(&mut cpu, movie).unwrap();
+
+        assert_eq!(player.play_to_end(&mut cpu).unwrap(), Playback::Finished);
+        assert_eq!(player.position(), 12);
+        assert_eq!(cpu.bus.peek(0x11), 12);
+        assert_eq!(cpu.bus.peek(0x10), 4);
+    }
+
+    #[test]
+    fn test_playback_sets_up_console() {
+        // the movie's region and controllers win over the console's
+        let mut cpu = nes();
+        cpu.bus.set_region(Region::Dendy);
+        cpu.bus.ports = InputLayout::Zapper.devices();
+        Player::start(&mut cpu, Movie::default()).unwrap();
+        assert_eq!(cpu.bus.region(), Region::Ntsc);
+        assert!(matches!(cpu.bus.ports[1], PortDevice::Joypad(_)));
+
+        cpu.bus.ports = InputLayout::Hori.devices();
+        Player::start(&mut cpu, Movie { pal: true, ..Movie::default() }).unwrap();
+        assert_eq!(cpu.bus.region(), Region::Pal);
+        assert!(matches!(cpu.bus.ports, [PortDevice::Joypad(_), PortDevice::Joypad(_)]));
+    }
+
+    #[test]
+    fn test_rom_checksum() {
+        // FCEUX's: the MD5 of PRG-ROM and CHR-ROM in base64
+        let mut cartridge = Cartridge::new(&nrom_image("")).unwrap();
+        for (data, expected) in [(&b"abc"[..], "kAFQmDzST7DWlj99KOF/cg=="), (b"", "1B2M2Y8AsgTpgAmY7PhCfg==")] {
+            cartridge.prg_rom = data.to_vec();
+            cartridge.chr = Vec::new();
+            assert_eq!(rom_checksum(&cartridge), format!("base64:{}", expected));
+        }
+        cartridge.prg_rom = vec![b'a'; 60];
+        cartridge.chr = vec![b'a'; 40];
+        assert_eq!(rom_checksum(&cartridge), "base64:NqksyUqeD6IfYl+L+wB63w==");
+        cartridge.chr_is_ram = true;
+        assert_ne!(rom_checksum(&cartridge), "base64:NqksyUqeD6IfYl+L+wB63w==");
+
+        let movie = record(3);
+        let mut cpu = nes();
+        assert_eq!(movie.rom_checksum, rom_checksum(&cpu.bus.cartridge));
+        assert!(Movie::parse_fm2(&movie.to_fm2()).unwrap().rom_checksum.starts_with("base64:"));
+
+        // a movie made on another ROM is refused
+        cpu.bus.cartridge.prg_rom[0] ^= 1;
+        let actual = rom_checksum(&cpu.bus.cartridge);
+        assert_eq!(Player::start(&mut cpu, movie.clone()).err(),
+            Some(MovieError::RomMismatch { expected: movie.rom_checksum.clone(), actual }));
+        assert!(Player::start(&mut cpu, Movie { rom_checksum: String::new(), ..movie }).is_ok());
+    }
+
+    #[test]
+    fn test_desync_detected() {
+        let mut movie = record(12);
+        movie.frames[5].pads[0] = Buttons::A;
+
+        let mut cpu = nes();
+        let mut player = Player::start(&mut cpu, movie).unwrap();
+        assert!(matches!(player.play_to_end(&mut cpu).unwrap(), Playback::Desync { frame: 7, .. }));
+    }
+
+    #[test]
+    fn test_power_command() {
+        let mut cpu = nes();
+        let mut recorder = Recorder::start(&mut cpu, Movie::default(), 0);
+        for _ in 0..5 {
+            recorder.frame(&mut cpu, pad(Buttons::empty())).unwrap();
+        }
+        let power = MovieFrame { commands: MovieCommands::POWER, ..pad(Buttons::empty()) };
+        recorder.frame(&mut cpu, power).unwrap();
+
+        assert_eq!(cpu.bus.peek(0x11), 1);
+    }
+}
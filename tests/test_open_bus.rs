@@ -0,0 +1,58 @@
+mod common;
+
+mod test_open_bus {
+    use crate::common;
+    use nes_emulator::bus::{Bus, NesBus};
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::joypad::Buttons;
+
+    fn nes(program: &str) -> CPU<NesBus> {
+        common::nes(&format!(".org $C000\nreset: {}\nBRK\n.org $FFFA\n.word reset, reset, reset", program))
+    }
+
+    #[test]
+    fn test_unmapped_reads_return_last_bus_value() {
+        // the last byte on the bus is the high byte of the operand
+        let mut cpu = nes("LDA $4000");
+        cpu.run().unwrap();
+        assert_eq!(cpu.register_a, 0x40);
+
+        let mut cpu = nes("LDA $5123");
+        cpu.run().unwrap();
+        assert_eq!(cpu.register_a, 0x51);
+    }
+
+    #[test]
+    fn test_controller_ports_drive_low_bits_only() {
+        let mut cpu = nes("LDA $4016");
+        cpu.bus.set_buttons(0, Buttons::A);
+        cpu.run().unwrap();
+        assert_eq!(cpu.register_a, 0x41);
+    }
+
+    #[test]
+    fn test_ppu_io_latch() {
+        let mut cpu = nes("LDA #$1F\nSTA $2003\nLDX $2000\nLDY $2002");
+        cpu.bus.ppu.status = 0x80;
+        cpu.run().unwrap();
+        // a write-only register reads back the last value written, and
+        // PPUSTATUS fills its low bits from it
+        assert_eq!(cpu.register_x, 0x1F);
+        assert_eq!(cpu.register_y, 0x9F);
+
+        // without refreshes the latch fades
+        cpu.bus.ppu.tick(40 * 262 * 341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.peek(0x2000), 0x00);
+    }
+
+    #[test]
+    fn test_open_bus_survives_save_state() {
+        let mut cpu = nes("LDA $4000");
+        cpu.step().unwrap();
+        let state = cpu.save_state();
+        cpu.bus.open_bus = 0;
+
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.bus.peek(0x4000), 0x40);
+    }
+}
@@ -0,0 +1,118 @@
+mod common;
+
+mod test_palette {
+    use crate::common::nrom_image;
+    use nes_emulator::bus::NesBus;
+    use nes_emulator::cartridge::Cartridge;
+    use nes_emulator::error::PaletteError;
+    use nes_emulator::ntsc::{NtscFilter, NtscSetup, NTSC_OUT_WIDTH};
+    use nes_emulator::palette::Palette;
+    use nes_emulator::ppu::{PPU, SCREEN_HEIGHT, SCREEN_WIDTH};
+    use nes_emulator::region::Region;
+
+    fn cartridge() -> Cartridge {
+        Cartridge::new(&nrom_image("")).unwrap()
+    }
+
+    /// One frame with rendering off, so every pixel is the backdrop.
+    fn backdrop(ppu: &mut PPU, color: u8, mask: u8) -> u16 {
+        let mut cartridge = cartridge();
+        ppu.palette[0] = color;
+        ppu.mask = mask;
+        ppu.tick(262 * 341, &mut cartridge);
+        ppu.frame_buffer[0]
+    }
+
+    #[test]
+    fn test_grayscale_and_emphasis_in_framebuffer() {
+        let mut ppu = PPU::new();
+        assert_eq!(backdrop(&mut ppu, 0x16, 0x00), 0x16);
+        assert_eq!(backdrop(&mut ppu, 0x16, 0x01), 0x10);
+        assert_eq!(backdrop(&mut ppu, 0x16, 0x20), 0x16 | 0b001 << 6);
+        assert_eq!(backdrop(&mut ppu, 0x16, 0xC1), 0x10 | 0b110 << 6);
+
+        // PAL swaps red and green
+        let mut bus = NesBus::new(cartridge());
+        bus.set_region(Region::Pal);
+        assert_eq!(backdrop(&mut bus.ppu, 0x16, 0x20), 0x16 | 0b010 << 6);
+        assert_eq!(backdrop(&mut bus.ppu, 0x16, 0xC0), 0x16 | 0b101 << 6);
+    }
+
+    #[test]
+    fn test_default_palette() {
+        let palette = Palette::default();
+        assert_eq!(palette.rgb(0x0F), [0, 0, 0]);
+        assert_eq!(palette.rgb(0x30), [255, 255, 255]);
+        let [r, g, b] = palette.rgb(0x00);
+        assert!(r == g && g == b && r > 64 && r < 160);
+
+        // $16 is red, $1A green, $12 blue
+        let [r, g, b] = palette.rgb(0x16);
+        assert!(r > 2 * g && r > 2 * b);
+        let [r, g, b] = palette.rgb(0x1A);
+        assert!(g > 2 * r && g > 2 * b);
+        let [r, g, b] = palette.rgb(0x12);
+        assert!(b > 2 * r && b > 2 * g);
+
+        // red emphasis keeps red and dims the rest
+        let plain = palette.rgb(0x30);
+        let red = palette.rgb(0x30 | 0b001 << 6);
+        assert!(red[0] > red[1] && red[0] > red[2] && red[1] < plain[1]);
+        let all = palette.rgb(0x30 | 0b111 << 6);
+        assert!(all.iter().all(|&c| c < 255));
+    }
+
+    #[test]
+    fn test_pal_files() {
+        let mut data = vec![0; 192];
+        data[0x21 * 3..0x21 * 3 + 3].copy_from_slice(&[100, 150, 200]);
+        let palette = Palette::from_bytes(&data).unwrap();
+        assert_eq!(palette.rgb(0x21), [100, 150, 200]);
+        // green emphasis dims red and blue
+        assert_eq!(palette.rgb(0x21 | 0b010 << 6), [75, 150, 149]);
+        assert_eq!(palette.to_bytes().len(), 1536);
+
+        let full = Palette::default().to_bytes();
+        assert_eq!(Palette::from_bytes(&full).unwrap(), Palette::default());
+
+        assert_eq!(Palette::from_bytes(&[0; 100]), Err(PaletteError::InvalidSize(100)));
+    }
+
+    #[test]
+    fn test_render_rgb() {
+        let palette = Palette::from_bytes(&[0x12; 192]).unwrap();
+        assert_eq!(palette.render(&[0x05, 0x05]), vec![0x121212, 0x121212]);
+    }
+
+    #[test]
+    fn test_ntsc_filter() {
+        let filter = NtscFilter::default();
+
+        // a flat field decodes to the palette's color, away from the edges
+        let frame = vec![0x16; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let out = filter.render(&frame, 0);
+        assert_eq!(out.len(), NTSC_OUT_WIDTH * SCREEN_HEIGHT);
+        let [r, g, b] = Palette::default().rgb(0x16);
+        let expected = u32::from_be_bytes([0, r, g, b]);
+        assert_eq!(out[NTSC_OUT_WIDTH * 100 + 300], expected);
+
+        // thin vertical lines of white on black pick up color fringes
+        let frame: Vec<u16> = (0..SCREEN_WIDTH * SCREEN_HEIGHT)
+            .map(|i| if i % 2 == 0 { 0x30 } else { 0x0F })
+            .collect();
+        let out = filter.render(&frame, 0);
+        let [_, r, g, b] = out[NTSC_OUT_WIDTH * 100 + 301].to_be_bytes();
+        assert!(r != g || g != b);
+
+        // with no saturation there is no color at all
+        let gray = NtscFilter::new(NtscSetup { saturation: 0.0, ..NtscSetup::default() });
+        let [_, r, g, b] = gray.render(&frame, 0)[NTSC_OUT_WIDTH * 100 + 301].to_be_bytes();
+        assert!(r == g && g == b);
+
+        // the fringes move with the burst phase; merging fields blends two
+        let merged = NtscFilter::new(NtscSetup { merge_fields: true, ..NtscSetup::default() });
+        let pixel = |filter: &NtscFilter, phase| filter.render(&frame, phase)[NTSC_OUT_WIDTH * 100 + 301];
+        assert_ne!(pixel(&filter, 0), pixel(&filter, 1));
+        assert_ne!(pixel(&merged, 0), pixel(&filter, 0));
+    }
+}
@@ -0,0 +1,100 @@
+mod common;
+
+mod test_ppu {
+    use crate::common::nes;
+    use nes_emulator::bus::{Bus, NesBus};
+    use nes_emulator::cpu::CPU;
+
+    const NMI_COUNTER: &str = "
+        .org $C000
+        reset:  BIT $2002
+                LDA #$80
+                STA $2000
+        loop:   JMP loop
+        nmi:    INC $10
+                RTI
+        .org $FFFA
+        .word nmi, reset, reset
+    ";
+
+    #[test]
+    fn test_vblank_timing() {
+        let mut cpu = nes(NMI_COUNTER);
+        cpu.bus.ppu.power_on();
+        cpu.bus.ppu.status = 0;
+
+        // vblank begins at dot 1 of scanline 241
+        cpu.bus.ppu.tick(241 * 341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.status & 0x80, 0);
+        cpu.bus.ppu.tick(1, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.status & 0x80, 0x80);
+
+        // and ends on the pre-render line
+        cpu.bus.ppu.tick(20 * 341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.status & 0x80, 0);
+        assert_eq!(cpu.bus.ppu.frame, 0);
+        cpu.bus.ppu.tick(341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.frame, 1);
+    }
+
+    #[test]
+    fn test_nmi_once_per_frame() {
+        let mut cpu = nes(NMI_COUNTER);
+        for _ in 0..10 {
+            cpu.run_frame().unwrap();
+        }
+        assert_eq!(cpu.bus.peek(0x10), 10);
+        assert_eq!(cpu.stack_pointer, 0xFD);
+    }
+
+    /// Tile 1 is solid colour 1; nametable entry (0, 0) uses it.
+    fn solid_tile_screen() -> CPU<NesBus> {
+        let mut cpu = nes(NMI_COUNTER);
+        cpu.bus.ppu.power_on();
+        cpu.bus.cartridge.chr[0x10..0x18].fill(0xFF);
+        cpu.bus.ppu.vram[0] = 1;
+        cpu.bus.ppu.palette[0x00] = 0x0F;
+        cpu.bus.ppu.palette[0x01] = 0x16;
+        cpu.bus.ppu.palette[0x11] = 0x30;
+        cpu
+    }
+
+    #[test]
+    fn test_background_rendering() {
+        let mut cpu = solid_tile_screen();
+        cpu.bus.ppu.mask = 0x0A;
+        cpu.bus.ppu.tick(240 * 341, &mut cpu.bus.cartridge);
+
+        let screen = &cpu.bus.ppu.frame_buffer;
+        assert_eq!(screen[..8], [0x16; 8]);
+        assert_eq!(screen[8], 0x0F);
+        assert_eq!(screen[7 * 256 + 7], 0x16);
+        assert_eq!(screen[8 * 256], 0x0F);
+
+        // with the left column masked, the backdrop shows through
+        cpu.bus.ppu.mask = 0x08;
+        cpu.bus.ppu.tick(262 * 341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.frame_buffer[..8], [0x0F; 8]);
+    }
+
+    #[test]
+    fn test_sprite_0_hit() {
+        let mut cpu = solid_tile_screen();
+        cpu.bus.ppu.oam[..4].copy_from_slice(&[0, 1, 0, 0]);
+        cpu.bus.ppu.mask = 0x1E;
+
+        // a sprite with Y = 0 first appears on line 1
+        cpu.bus.ppu.tick(341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.status & 0x40, 0);
+        cpu.bus.ppu.tick(341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.status & 0x40, 0x40);
+        assert_eq!(cpu.bus.ppu.frame_buffer[256], 0x30);
+        assert_eq!(cpu.bus.ppu.frame_buffer[0], 0x16);
+
+        // behind the background, the sprite is hidden but still hits
+        cpu.bus.ppu.oam[2] = 0x20;
+        cpu.bus.ppu.tick(262 * 341, &mut cpu.bus.cartridge);
+        assert_eq!(cpu.bus.ppu.frame_buffer[256], 0x16);
+        assert_eq!(cpu.bus.ppu.status & 0x40, 0x40);
+    }
+}
@@ -0,0 +1,131 @@
+mod common;
+
+mod test_ramsearch {
+    use crate::common::nes;
+    use nes_emulator::bus::{Bus, FlatMemory};
+    use nes_emulator::cheat::CheatCode;
+    use nes_emulator::compare::Comparison;
+    use nes_emulator::debugger::{Debugger, Response};
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::ramsearch::{Filter, Operand, RamSearch};
+
+    /// A pretend game: a lives counter at $0075, a frame counter at $0300
+    /// and a score byte in PRG-RAM at $6010.
+    fn game() -> FlatMemory {
+        let mut memory = FlatMemory::new();
+        memory.mem_write(0x0075, 3);
+        memory.mem_write(0x0300, 0);
+        memory.mem_write(0x6010, 10);
+        memory
+    }
+
+    fn frame(memory: &mut FlatMemory) {
+        let counter = memory.peek(0x0300);
+        memory.mem_write(0x0300, counter.wrapping_add(1));
+    }
+
+    #[test]
+    fn test_find_lives_counter() {
+        let mut memory = game();
+        let mut search = RamSearch::new(&memory, false);
+        assert_eq!(search.len(), 0x800);
+
+        search.filter(&memory, Filter::equal_to(3));
+        frame(&mut memory);
+        assert!(search.filter(&memory, Filter::unchanged()) < 0x800);
+
+        // lose a life
+        memory.mem_write(0x0075, 2);
+        frame(&mut memory);
+        search.filter(&memory, Filter::decreased_by(1));
+
+        let addresses: Vec<u16> = search.candidates().iter().map(|c| c.address).collect();
+        assert_eq!(addresses, [0x0075]);
+        assert_eq!(search.candidates()[0].value, 2);
+    }
+
+    #[test]
+    fn test_relations_against_previous_values() {
+        let mut memory = game();
+        let mut search = RamSearch::new(&memory, true);
+        assert_eq!(search.len(), 0x800 + 0x2000);
+
+        frame(&mut memory);
+        memory.mem_write(0x6010, 15);
+        assert_eq!(search.filter(&memory, Filter::changed()), 2);
+        assert_eq!(search.filter(&memory, Filter::unchanged()), 2);
+
+        frame(&mut memory);
+        memory.mem_write(0x6010, 20);
+        search.filter(&memory, Filter::increased_by(5));
+        assert_eq!(search.candidates()[0].address, 0x6010);
+        assert_eq!(search.len(), 1);
+
+        // values wrap like the byte itself
+        memory.mem_write(0x6010, 0x01);
+        let mut wrapped = RamSearch::new(&memory, true);
+        memory.mem_write(0x6010, 0xFF);
+        wrapped.filter(&memory, Filter::new(Comparison::Equal, Operand::Delta(-2)));
+        assert_eq!(wrapped.candidates()[0].address, 0x6010);
+
+        let mut less = RamSearch::new(&memory, true);
+        memory.mem_write(0x6010, 0x80);
+        less.filter(&memory, Filter::decreased());
+        assert_eq!(less.len(), 1);
+
+        // update rebaselines without dropping anything
+        memory.mem_write(0x6010, 0x90);
+        less.update(&memory);
+        assert_eq!(less.candidates()[0].value, 0x90);
+    }
+
+    #[test]
+    fn test_search_sees_past_cheats() {
+        let mut cpu = nes("");
+        cpu.bus.mem_write(0x0075, 3);
+        cpu.bus.mem_write(0x6010, 10);
+        cpu.bus.cheats.add(CheatCode::Patch { address: 0x0075, value: 9, compare: None }, "lives");
+        cpu.bus.cheats.add(CheatCode::Patch { address: 0x6010, value: 99, compare: None }, "score");
+
+        let mut search = RamSearch::new(&cpu.bus, true);
+        search.filter(&cpu.bus, Filter::equal_to(9));
+        assert!(search.is_empty());
+
+        let mut search = RamSearch::new(&cpu.bus, true);
+        cpu.bus.mem_write(0x0075, 2);
+        cpu.bus.mem_write(0x6010, 20);
+        search.filter(&cpu.bus, Filter::changed());
+        let found: Vec<(u16, u8)> = search.candidates().iter().map(|c| (c.address, c.value)).collect();
+        assert_eq!(found, [(0x0075, 2), (0x6010, 20)]);
+    }
+
+    fn run(debugger: &mut Debugger, cpu: &mut CPU, line: &str) -> String {
+        match debugger.execute(cpu, line) {
+            Response::Output(text) => text,
+            Response::Quit => panic!("unexpected quit"),
+        }
+    }
+
+    #[test]
+    fn test_search_command() {
+        let mut cpu = CPU::new();
+        let mut debugger = Debugger::new();
+        assert!(run(&mut debugger, &mut cpu, "search == 3").starts_with("error: no search"));
+
+        cpu.bus.mem_write(0x0075, 3);
+        assert_eq!(run(&mut debugger, &mut cpu, "search new"), "2048 candidates");
+        assert_eq!(run(&mut debugger, &mut cpu, "search == $03"), "1 candidates\n  $0075  $03");
+
+        cpu.bus.mem_write(0x0075, 5);
+        assert_eq!(run(&mut debugger, &mut cpu, "search == prev+2"), "1 candidates\n  $0075  $05");
+        cpu.bus.mem_write(0x0075, 4);
+        assert_eq!(run(&mut debugger, &mut cpu, "search >= prev"), "0 candidates");
+
+        assert_eq!(run(&mut debugger, &mut cpu, "search new prg"), "10240 candidates");
+        let listing = run(&mut debugger, &mut cpu, "search list");
+        assert!(listing.ends_with("... 10224 more"));
+        assert!(run(&mut debugger, &mut cpu, "search ~ 3").starts_with("error: unknown operator"));
+        assert!(run(&mut debugger, &mut cpu, "search == prev*2").starts_with("error: cannot parse"));
+        assert!(run(&mut debugger, &mut cpu, "search == 300").starts_with("error"));
+    }
+}
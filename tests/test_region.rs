@@ -0,0 +1,139 @@
+mod common;
+
+mod test_region {
+    use crate::common::nrom_image;
+    use nes_emulator::bus::{Bus, NesBus, RamPattern};
+    use nes_emulator::cartridge::Cartridge;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::error::SaveStateError;
+    use nes_emulator::region::Region;
+    use nes_emulator::savestate::crc32;
+
+    /// iNES 1 header if `timing` is `None`.
+    fn cartridge(timing: Option<u8>) -> Cartridge {
+        let mut image = nrom_image("");
+        if let Some(timing) = timing {
+            image[7] = 0x08;
+            image[12] = timing;
+        }
+        Cartridge::new(&image).unwrap()
+    }
+
+    fn bus(region: Region) -> NesBus {
+        let mut bus = NesBus::new(cartridge(None));
+        bus.set_region(region);
+        bus.power_on(RamPattern::Zeros);
+        bus
+    }
+
+    /// CPU cycles taken by the next `frames` frames, from the start of one.
+    fn cycles_per_frames(bus: &mut NesBus, frames: u64) -> u64 {
+        let start = bus.frame();
+        while bus.frame() == start {
+            bus.tick(1);
+        }
+        let mut cycles = 0;
+        while bus.frame() < start + 1 + frames {
+            bus.tick(1);
+            cycles += 1;
+        }
+        cycles
+    }
+
+    #[test]
+    fn test_detect_from_header() {
+        assert_eq!(cartridge(None).region, None);
+        assert_eq!(cartridge(Some(0)).region, Some(Region::Ntsc));
+        assert_eq!(cartridge(Some(1)).region, Some(Region::Pal));
+        assert_eq!(cartridge(Some(2)).region, None);
+        assert_eq!(cartridge(Some(3)).region, Some(Region::Dendy));
+
+        assert_eq!(NesBus::new(cartridge(Some(1))).region(), Region::Pal);
+        assert_eq!(NesBus::new(cartridge(Some(2))).region(), Region::Ntsc);
+        assert_eq!(Region::parse("Dendy"), Some(Region::Dendy));
+        assert_eq!(Region::parse("secam"), None);
+    }
+
+    #[test]
+    fn test_frame_length() {
+        // rendering is off, so no dot is skipped: three frames of 341-dot
+        // lines at three dots per CPU cycle
+        assert_eq!(cycles_per_frames(&mut bus(Region::Ntsc), 3), 341 * 262);
+        assert_eq!(cycles_per_frames(&mut bus(Region::Dendy), 3), 341 * 312);
+        // PAL runs 3.2 dots per CPU cycle
+        assert_eq!(cycles_per_frames(&mut bus(Region::Pal), 16), 341 * 312 * 5);
+
+        assert!((Region::Ntsc.frame_rate() - 60.0988).abs() < 0.0001);
+        assert!((Region::Pal.frame_rate() - 50.0070).abs() < 0.0001);
+        assert!((Region::Pal.cpu_clock_hz() - 1_662_607.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_vblank_scanline() {
+        for (region, scanline) in [(Region::Ntsc, 241), (Region::Pal, 241), (Region::Dendy, 291)] {
+            let mut bus = bus(region);
+            bus.ppu.status = 0;
+            while bus.ppu.status & 0x80 == 0 {
+                bus.tick(1);
+            }
+            assert_eq!(bus.ppu.scanline, scanline, "{}", region);
+        }
+    }
+
+    #[test]
+    fn test_apu_frame_interrupt() {
+        for (region, cycles) in [(Region::Ntsc, 29829), (Region::Pal, 33253), (Region::Dendy, 29829)] {
+            let mut bus = bus(region);
+            bus.mem_write(0x4017, 0x00);
+            for _ in 0..cycles - 1 {
+                bus.tick(1);
+            }
+            assert_eq!(bus.peek(0x4015) & 0x40, 0, "{}", region);
+            bus.tick(1);
+            assert_eq!(bus.mem_read(0x4015) & 0x40, 0x40, "{}", region);
+            assert_eq!(bus.mem_read(0x4015) & 0x40, 0, "{}", region);
+        }
+
+        // inhibited
+        let mut bus = bus(Region::Ntsc);
+        bus.mem_write(0x4017, 0x40);
+        for _ in 0..30000 {
+            bus.tick(1);
+        }
+        assert_eq!(bus.mem_read(0x4015) & 0x40, 0);
+    }
+
+    #[test]
+    fn test_noise_and_dmc_tables() {
+        let mut ntsc = bus(Region::Ntsc);
+        let mut pal = bus(Region::Pal);
+        for bus in [&mut ntsc, &mut pal] {
+            bus.mem_write(0x400E, 0x02);
+            bus.mem_write(0x4010, 0x0F);
+        }
+        assert_eq!((ntsc.apu.noise_period(), ntsc.apu.dmc_rate()), (16, 54));
+        assert_eq!((pal.apu.noise_period(), pal.apu.dmc_rate()), (14, 50));
+    }
+
+    #[test]
+    fn test_region_survives_save_state() {
+        let mut cpu = CPU::with_bus(NesBus::new(cartridge(Some(3))));
+        cpu.power_on(RamPattern::Zeros);
+        let state = cpu.save_state();
+
+        cpu.bus.set_region(Region::Ntsc);
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.bus.region(), Region::Dendy);
+        assert_eq!(cpu.bus.ppu.region, Region::Dendy);
+
+        // a region byte no build writes is refused, not read as NTSC
+        let mut corrupt = state.clone();
+        let bus = corrupt.windows(4).position(|tag| tag == b"BUS ").unwrap();
+        corrupt[bus + 9] = 7;
+        let crc = crc32(&corrupt[16..]);
+        corrupt[12..16].copy_from_slice(&crc.to_le_bytes());
+        cpu.bus.set_region(Region::Pal);
+        assert_eq!(cpu.load_state(&corrupt), Err(SaveStateError::InvalidValue("region".to_string())));
+        assert_eq!(cpu.bus.region(), Region::Pal);
+    }
+}
@@ -0,0 +1,90 @@
+mod common;
+
+mod test_reset {
+    use crate::common::nes;
+    use nes_emulator::bus::RamPattern;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::flags::StatusFlags;
+
+    #[test]
+    fn test_power_on_state() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xFFFC, 0x00);
+        cpu.mem_write(0xFFFD, 0xC0);
+        cpu.register_y = 0x12;
+
+        cpu.power_on(RamPattern::Zeros);
+
+        assert_eq!(cpu.register_a, 0);
+        assert_eq!(cpu.register_x, 0);
+        assert_eq!(cpu.register_y, 0);
+        assert_eq!(cpu.stack_pointer, 0xFD);
+        assert_eq!(cpu.status.bits(), 0x34);
+        assert_eq!(cpu.program_counter, 0xC000);
+    }
+
+    #[test]
+    fn test_reset_keeps_registers() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0xFFFC, 0x34);
+        cpu.mem_write(0xFFFD, 0x12);
+        cpu.register_a = 0x01;
+        cpu.register_x = 0x02;
+        cpu.register_y = 0x03;
+        cpu.stack_pointer = 0xF0;
+        cpu.status = StatusFlags::CARRY;
+
+        cpu.reset();
+
+        assert_eq!((cpu.register_a, cpu.register_x, cpu.register_y), (0x01, 0x02, 0x03));
+        assert_eq!(cpu.stack_pointer, 0xED);
+        assert_eq!(cpu.status, StatusFlags::CARRY | StatusFlags::INTERRUPT_DISABLE);
+        assert_eq!(cpu.program_counter, 0x1234);
+    }
+
+    #[test]
+    fn test_load_uses_reset_vector() {
+        let mut cpu = CPU::new();
+        cpu.load(vec![0xE8, 0x00]);
+
+        assert_eq!(cpu.mem_read(0xFFFC), 0x00);
+        assert_eq!(cpu.mem_read(0xFFFD), 0x80);
+        assert_eq!(cpu.program_counter, 0x8000);
+    }
+
+    #[test]
+    fn test_ram_patterns() {
+        let mut cpu = CPU::new();
+        cpu.power_on(RamPattern::Ones);
+        assert_eq!(cpu.mem_read(0x0000), 0xFF);
+        assert_eq!(cpu.mem_read(0x07FF), 0xFF);
+
+        let mut first = [0u8; 64];
+        let mut second = [0u8; 64];
+        RamPattern::Random(42).fill(&mut first);
+        RamPattern::Random(42).fill(&mut second);
+        assert_eq!(first, second);
+
+        RamPattern::Random(43).fill(&mut second);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_nes_reset_clears_ppu_and_apu() {
+        let mut cpu = nes(".org $FFFC\n.word $C123");
+        assert_eq!(cpu.program_counter, 0xC123);
+
+        cpu.mem_write(0x2000, 0b1000_0000);
+        cpu.mem_write(0x2001, 0b0001_1110);
+        cpu.mem_write(0x2003, 0x40);
+        cpu.mem_write(0x4015, 0b0001_1111);
+
+        cpu.reset();
+
+        assert_eq!(cpu.bus.ppu.ctrl, 0);
+        assert_eq!(cpu.bus.ppu.mask, 0);
+        assert_eq!(cpu.bus.ppu.oam_addr, 0x40);
+        assert_eq!(cpu.bus.apu.channels_enabled, 0);
+        assert_eq!(cpu.program_counter, 0xC123);
+    }
+}
@@ -0,0 +1,135 @@
+mod common;
+
+mod test_rewind {
+    use crate::common::nes;
+    use nes_emulator::bus::{Bus, NesBus};
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::joypad::Buttons;
+    use nes_emulator::rewind::Rewind;
+
+    fn counting_nes() -> CPU<NesBus> {
+        nes("
+            .org $8000
+            loop:   INC $10
+                    BNE loop
+                    INC $11
+                    JMP loop
+            .org $FFFC
+            .word loop
+        ")
+    }
+
+    fn counter(cpu: &CPU<NesBus>) -> u16 {
+        u16::from_le_bytes([cpu.bus.peek(0x10), cpu.bus.peek(0x11)])
+    }
+
+    #[test]
+    fn test_frames_advance() {
+        let mut cpu = counting_nes();
+        for frame in 1..=3 {
+            assert!(cpu.run_frame().unwrap());
+            assert_eq!(cpu.bus.frame(), frame);
+        }
+        assert!(counter(&cpu) > 0);
+    }
+
+    #[test]
+    fn test_step_back_frame_by_frame() {
+        let mut cpu = counting_nes();
+        let mut rewind = Rewind::new(4, 1 << 20);
+        let mut counters = Vec::new();
+
+        for _ in 0..20 {
+            rewind.on_frame(&cpu);
+            counters.push((cpu.bus.frame(), counter(&cpu)));
+            cpu.run_frame().unwrap();
+        }
+        assert_eq!(rewind.len(), 5);
+        assert_eq!(cpu.bus.frame(), 20);
+
+        for expected in (10..20).rev() {
+            assert!(rewind.step_back(&mut cpu).unwrap());
+            assert_eq!(cpu.bus.frame(), expected);
+            let (frame, value) = counters[expected as usize];
+            assert_eq!(frame, expected);
+            assert_eq!(counter(&cpu), value);
+        }
+        assert_eq!(rewind.len(), 3);
+    }
+
+    #[test]
+    fn test_step_back_replays_recorded_input() {
+        // adds up the frames A was held on controller 1
+        let mut cpu = nes("
+            .org $C000
+            reset:  BIT $2002
+                    LDA #$80
+                    STA $2000
+            loop:   JMP loop
+            nmi:    LDA #1
+                    STA $4016
+                    LDA #0
+                    STA $4016
+                    LDA $4016
+                    AND #1
+                    CLC
+                    ADC $10
+                    STA $10
+                    RTI
+            .org $FFFA
+            .word nmi, reset, reset
+        ");
+        let mut rewind = Rewind::new(4, 1 << 20);
+        let mut states = Vec::new();
+
+        for frame in 0..20 {
+            let buttons = if frame % 3 == 0 { Buttons::A } else { Buttons::empty() };
+            cpu.bus.set_buttons(0, buttons);
+            rewind.on_frame(&cpu);
+            states.push(cpu.save_state());
+            cpu.run_frame().unwrap();
+        }
+        // whatever is held now must not leak into the replayed frames
+        cpu.bus.set_buttons(0, Buttons::A | Buttons::B);
+
+        for frame in (8..20).rev() {
+            assert!(rewind.step_back(&mut cpu).unwrap());
+            assert!(cpu.save_state() == states[frame], "frame {}", frame);
+        }
+    }
+
+    #[test]
+    fn test_resume_after_rewind() {
+        let mut cpu = counting_nes();
+        let mut rewind = Rewind::new(1, 1 << 20);
+        for _ in 0..6 {
+            rewind.on_frame(&cpu);
+            cpu.run_frame().unwrap();
+        }
+        let at_six = counter(&cpu);
+
+        assert!(rewind.rewind_to(&mut cpu, 2).unwrap());
+        assert_eq!(cpu.bus.frame(), 2);
+        for _ in 0..4 {
+            rewind.on_frame(&cpu);
+            cpu.run_frame().unwrap();
+        }
+        assert_eq!(counter(&cpu), at_six);
+        assert!(!rewind.rewind_to(&mut cpu, 100).unwrap());
+    }
+
+    #[test]
+    fn test_budget_drops_oldest() {
+        let mut cpu = counting_nes();
+        let snapshot = cpu.save_state().len();
+        let mut rewind = Rewind::new(1, snapshot + 64);
+
+        for _ in 0..30 {
+            rewind.on_frame(&cpu);
+            cpu.run_frame().unwrap();
+        }
+        assert!(rewind.memory_used() <= snapshot + 64);
+        assert!(rewind.oldest_frame().unwrap() > 0);
+        assert!(!rewind.rewind_to(&mut cpu, 0).unwrap());
+    }
+}
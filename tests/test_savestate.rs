@@ -0,0 +1,221 @@
+mod common;
+
+mod test_savestate {
+    use std::fs;
+    use crate::common::{self, console, nrom_image_chr_ram};
+    use nes_emulator::asm;
+    use nes_emulator::bus::{Bus, NesBus};
+    use nes_emulator::cartridge::Cartridge;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::error::SaveStateError;
+    use nes_emulator::input::PortDevice;
+    use nes_emulator::joypad::Buttons;
+    use nes_emulator::region::Region;
+
+    /// `program` at $8000, on CHR-RAM so that pattern data is saved too.
+    fn nes(program: &[&str]) -> CPU<NesBus> {
+        let source = format!(".org $8000\n{}\n.org $FFFC\n.word $8000", program.join("\n"));
+        console(Cartridge::new(&nrom_image_chr_ram(&source)).unwrap())
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let mut cpu = nes(&["LDA #$42", "STA $0300", "STA $6000", "INX", "BRK"]);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.bus.mem_write(0x2006, 0x00);
+        cpu.bus.mem_write(0x2006, 0x10);
+        cpu.bus.mem_write(0x2007, 0x99);
+
+        let state = cpu.save_state();
+        cpu.run().unwrap();
+        cpu.bus.mem_write(0x0300, 0);
+        cpu.bus.ppu.ctrl = 0x80;
+
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.program_counter, 0x8005);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.register_x, 0);
+        assert_eq!(cpu.cycles, 13);
+        assert_eq!(cpu.bus.peek(0x0300), 0x42);
+        assert_eq!(cpu.bus.peek(0x6000), 0);
+        assert_eq!(cpu.bus.ppu.ctrl, 0);
+        assert_eq!(cpu.bus.ppu.v, 0x0011);
+        assert_eq!(cpu.bus.cartridge.chr[0x0010], 0x99);
+
+        cpu.run().unwrap();
+        assert_eq!(cpu.bus.peek(0x6000), 0x42);
+        assert_eq!(cpu.register_x, 1);
+    }
+
+    #[test]
+    fn test_save_state_flat_memory() {
+        let mut cpu = CPU::new();
+        cpu.load(asm!("INX", "INX", "BRK"));
+        cpu.step().unwrap();
+        let state = cpu.save_state();
+
+        let mut restored = CPU::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.program_counter, 0x8001);
+        assert_eq!(restored.register_x, 1);
+        assert_eq!(restored.mem_read(0x8001), 0xE8);
+    }
+
+    #[test]
+    fn test_corrupt_state_is_rejected() {
+        let mut cpu = nes(&["BRK"]);
+        let mut state = cpu.save_state();
+
+        let last = state.len() - 1;
+        state[last] ^= 0xFF;
+        assert_eq!(cpu.load_state(&state), Err(SaveStateError::ChecksumMismatch));
+        assert_eq!(cpu.load_state(&state[..20]), Err(SaveStateError::Truncated));
+        assert_eq!(cpu.load_state(b"not a state at all"), Err(SaveStateError::BadMagic));
+
+        let mut future = cpu.save_state();
+        future[4] = 0xFF;
+        assert_eq!(cpu.load_state(&future), Err(SaveStateError::UnsupportedVersion(0x00FF)));
+    }
+
+    #[test]
+    fn test_state_from_another_rom_is_rejected() {
+        let mut first = nes(&["LDA #1", "BRK"]);
+        let mut second = nes(&["LDA #2", "BRK"]);
+        second.register_a = 0x55;
+
+        let state = first.save_state();
+        assert_eq!(second.load_state(&state), Err(SaveStateError::RomMismatch));
+        assert_eq!(second.register_a, 0x55);
+        first.load_state(&state).unwrap();
+    }
+
+    /// The program the states in tests/fixtures were saved from, each by
+    /// the build that introduced its format version, 20000 instructions
+    /// after power-on. It counts NMIs in $10 and reads one bit of the first
+    /// controller in each.
+    const FIXTURE_PROGRAM: &str = "
+        .org $C000
+        reset:  LDA #$42
+                STA $6000
+                LDA #$80
+                STA $2000
+        loop:   INX
+                JMP loop
+        nmi:    INC $10
+                LDA #1
+                STA $4016
+                LDA #0
+                STA $4016
+                LDA $4016
+                RTI
+        .org $FFFA
+        .word nmi, reset, reset
+    ";
+
+    /// Loads the fixture state of `version` into a console that has run on
+    /// a while with other settings, so that whatever the state leaves at
+    /// its defaults shows.
+    fn load_fixture(version: u16) -> CPU<NesBus> {
+        let mut cpu = common::nes(FIXTURE_PROGRAM);
+        cpu.bus.set_region(Region::Pal);
+        cpu.bus.set_buttons(0, Buttons::SELECT);
+        for _ in 0..3 {
+            cpu.run_frame().unwrap();
+        }
+        cpu.bus.open_bus = 0x55;
+
+        let path = format!("{}/tests/fixtures/savestate_v{}.state", env!("CARGO_MANIFEST_DIR"), version);
+        let state = fs::read(path).unwrap();
+        assert_eq!(u16::from_le_bytes([state[4], state[5]]), version);
+        cpu.load_state(&state).unwrap();
+
+        assert_eq!(cpu.program_counter, 0xC00A);
+        assert_eq!(cpu.bus.peek(0x6000), 0x42);
+        assert_eq!(cpu.bus.ppu.ctrl, 0x80);
+        cpu
+    }
+
+    fn ppu_position(cpu: &CPU<NesBus>) -> (u64, u16, u16) {
+        (cpu.bus.ppu.frame, cpu.bus.ppu.scanline, cpu.bus.ppu.dot)
+    }
+
+    /// The next `count` bits of the first controller port.
+    fn port_bits(cpu: &mut CPU<NesBus>, count: usize) -> Vec<u8> {
+        (0..count).map(|_| cpu.bus.mem_read(0x4016) & 1).collect()
+    }
+
+    #[test]
+    fn test_load_version_1() {
+        let mut cpu = load_fixture(1);
+        assert_eq!(cpu.cycles, 50009);
+        assert_eq!(cpu.register_x, 0x0E);
+        // no dot clock yet: the PPU starts at the top of a frame
+        assert_eq!(ppu_position(&cpu), (0, 0, 0));
+        assert_eq!(cpu.bus.buttons_mut(0).copied(), Some(Buttons::empty()));
+        assert_eq!(cpu.bus.open_bus, 0);
+        assert_eq!(cpu.bus.region(), Region::Ntsc);
+
+        // and the game carries on from there
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.bus.peek(0x10), 1);
+    }
+
+    #[test]
+    fn test_load_version_2() {
+        let mut cpu = load_fixture(2);
+        assert_eq!(cpu.cycles, 50037);
+        assert_eq!(cpu.bus.peek(0x10), 2);
+        assert_eq!(ppu_position(&cpu), (1, 178, 71));
+        // controllers were not saved yet, so they come back released
+        assert_eq!(cpu.bus.buttons_mut(0).copied(), Some(Buttons::empty()));
+    }
+
+    #[test]
+    fn test_load_version_3() {
+        let mut cpu = load_fixture(3);
+        assert_eq!(ppu_position(&cpu), (1, 178, 71));
+        assert_eq!(cpu.bus.open_bus, 0);
+        assert_eq!(cpu.bus.buttons_mut(0).copied(), Some(Buttons::START | Buttons::A));
+        // the NMI handler had read A; B, SELECT and START follow
+        assert_eq!(port_bits(&mut cpu, 3), [0, 0, 1]);
+    }
+
+    #[test]
+    fn test_load_version_4() {
+        let mut cpu = load_fixture(4);
+        assert_eq!(cpu.cycles, 50037);
+        assert_eq!(ppu_position(&cpu), (1, 178, 71));
+        assert_eq!(port_bits(&mut cpu, 3), [0, 0, 1]);
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.bus.peek(0x10), 3);
+    }
+
+    #[test]
+    fn test_load_version_5() {
+        let cpu = load_fixture(5);
+        // the high byte of JMP loop's operand was the last thing on the bus
+        assert_eq!(cpu.bus.open_bus, 0xC0);
+        assert_eq!(cpu.bus.region(), Region::Ntsc);
+    }
+
+    #[test]
+    fn test_load_version_6() {
+        let mut cpu = load_fixture(6);
+        assert_eq!(cpu.bus.region(), Region::Pal);
+        assert_eq!(ppu_position(&cpu), (1, 157, 189));
+        assert_eq!(cpu.bus.open_bus, 0xC0);
+        assert_eq!(port_bits(&mut cpu, 3), [0, 0, 1]);
+    }
+
+    #[test]
+    fn test_load_version_7() {
+        let mut cpu = load_fixture(7);
+        assert_eq!(cpu.bus.region(), Region::Dendy);
+        assert_eq!(ppu_position(&cpu), (1, 128, 71));
+        assert!(matches!(cpu.bus.ports[0], PortDevice::FourScore(_)));
+        assert_eq!(cpu.bus.buttons_mut(2).copied(), Some(Buttons::SELECT));
+        // player 1's B, SELECT and START
+        assert_eq!(port_bits(&mut cpu, 3), [0, 0, 1]);
+    }
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "singlestep")]
+
+mod test_singlestep {
+    use std::{env, path::Path};
+    use nes_emulator::bus::AccessKind;
+    use nes_emulator::error::SingleStepError;
+    use nes_emulator::opcode;
+    use nes_emulator::singlestep::{parse_tests, run_directory, run_test, run_tests, Outcome};
+
+    const TESTS: &str = r#"[
+        { "name": "a9 2c 7e",
+          "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                       "ram": [[512, 169], [513, 44]] },
+          "final":   { "pc": 514, "s": 253, "a": 44, "x": 0, "y": 0, "p": 36,
+                       "ram": [[512, 169], [513, 44]] },
+          "cycles":  [[512, 169, "read"], [513, 44, "read"]] },
+        { "name": "85 10 00",
+          "initial": { "pc": 512, "s": 253, "a": 7, "x": 0, "y": 0, "p": 36,
+                       "ram": [[512, 133], [513, 16]] },
+          "final":   { "pc": 514, "s": 253, "a": 7, "x": 0, "y": 0, "p": 36,
+                       "ram": [[16, 7], [512, 133], [513, 16]] },
+          "cycles":  [[512, 133, "read"], [513, 16, "read"], [16, 7, "write"]] },
+        { "name": "bd ff 10",
+          "initial": { "pc": 512, "s": 253, "a": 0, "x": 1, "y": 0, "p": 36,
+                       "ram": [[512, 189], [513, 255], [514, 16], [4096, 0], [4352, 128]] },
+          "final":   { "pc": 515, "s": 253, "a": 128, "x": 1, "y": 0, "p": 164,
+                       "ram": [[4352, 128]] },
+          "cycles":  [[512, 189, "read"], [513, 255, "read"], [514, 16, "read"],
+                      [4096, 0, "read"], [4352, 128, "read"]] },
+        { "name": "a9 2c 00",
+          "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                       "ram": [[512, 169], [513, 44]] },
+          "final":   { "pc": 514, "s": 253, "a": 45, "x": 0, "y": 0, "p": 36,
+                       "ram": [] },
+          "cycles":  [[512, 169, "read"], [513, 44, "read"]] }
+    ]"#;
+
+    #[test]
+    fn test_parse() {
+        let tests = parse_tests(TESTS).unwrap();
+        assert_eq!(tests.len(), 4);
+        assert_eq!(tests[0].name, "a9 2c 7e");
+        assert_eq!(tests[0].opcode(), 0xA9);
+        assert_eq!(tests[1].initial.a, 7);
+        assert_eq!(tests[1].expected.ram[0], (0x0010, 7));
+        assert_eq!(tests[1].cycles[2].kind, AccessKind::Write);
+
+        assert!(matches!(parse_tests("[1,"), Err(SingleStepError::Json(_))));
+        assert_eq!(parse_tests("{}"), Err(SingleStepError::Format("the top level must be an array".to_string())));
+        assert!(matches!(parse_tests(r#"[{"name": "x", "cycles": []}]"#), Err(SingleStepError::Format(_))));
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let tests = parse_tests(TESTS).unwrap();
+        assert_eq!(run_test(&tests[0]), Outcome::Passed);
+        assert_eq!(run_test(&tests[1]), Outcome::Passed);
+        assert_eq!(run_test(&tests[3]), Outcome::StateMismatch("A is $2C, expected $2D".to_string()));
+
+        // the dummy read from the wrong page is part of the bus activity
+        assert_eq!(run_test(&tests[2]), Outcome::Passed);
+    }
+
+    #[test]
+    fn test_report() {
+        let tests = parse_tests(TESTS).unwrap();
+        let report = run_tests(&tests);
+        assert_eq!(report.total(), 4);
+
+        let lda = &report.opcodes[&0xA9];
+        assert_eq!((lda.total, lda.passed, lda.state_passed), (2, 1, 1));
+        assert_eq!(lda.first_failure.as_ref().unwrap().0, "a9 2c 00");
+
+        let text = report.to_string();
+        assert!(text.starts_with("op   name   passed"));
+        assert!(text.contains("$85  STA    100.0%   100.0%       1"));
+        assert!(text.contains("$A9  LDA     50.0%    50.0%       2  a9 2c 00: state: A is $2C"));
+    }
+
+    /// Runs a checkout of the ProcessorTests `nes6502/v1` directory named by
+    /// `$PROCESSOR_TESTS`. Every official opcode the CPU implements must
+    /// pass all its tests; BRK stops the emulator instead of jumping
+    /// through $FFFE, and the unofficial opcodes are not implemented.
+    #[test]
+    #[ignore = "needs $PROCESSOR_TESTS"]
+    fn test_processor_tests() {
+        let dir = env::var_os("PROCESSOR_TESTS").expect("$PROCESSOR_TESTS names the nes6502/v1 directory");
+        let report = run_directory(Path::new(&dir)).unwrap();
+
+        let failing: Vec<String> = report.opcodes.iter()
+            .filter(|&(&code, _)| code != 0x00 && opcode::lookup(code).is_some())
+            .filter(|(_, result)| result.passed < result.total)
+            .map(|(code, result)| format!("${:02X}: {}/{} passed, first failure {:?}",
+                code, result.passed, result.total, result.first_failure))
+            .collect();
+        assert!(failing.is_empty(), "{} opcodes failed:\n{}", failing.len(), failing.join("\n"));
+    }
+}
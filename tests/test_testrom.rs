@@ -0,0 +1,114 @@
+mod common;
+
+mod test_testrom {
+    use crate::common::nrom_image;
+    use std::{env, fs, path::{Path, PathBuf}};
+    use nes_emulator::error::TestRomError;
+    use nes_emulator::testrom::run_test_rom;
+
+    /// A stand-in for a Blargg ROM: signs, prints `text` and reports `code`.
+    /// With `reset` it first asks to be reset, and only reports afterwards.
+    fn fake_test_rom(text: &str, code: u8, reset: bool) -> Vec<u8> {
+        nrom_image(&format!("
+            .org $C000
+            start:  LDA #$80
+                    STA $6000
+                    LDA #$DE
+                    STA $6001
+                    LDA #$B0
+                    STA $6002
+                    LDA #$61
+                    STA $6003
+                    LDA #{reset}
+                    CMP $7F00
+                    BEQ report
+                    INC $7F00
+                    LDA #$81
+                    STA $6000
+            wait:   JMP wait
+            report: LDA text,X
+                    STA $6004,X
+                    BEQ done
+                    INX
+                    JMP report
+            done:   LDA #{code}
+                    STA $6000
+            hang:   JMP hang
+            text:   .byte \"{text}\", 0
+            .org $FFFC
+            .word start
+        ", reset = reset as u8, code = code, text = text))
+    }
+
+    #[test]
+    fn test_passing_rom() {
+        let result = run_test_rom(&fake_test_rom("All tests passed", 0, false), 60).unwrap();
+        assert!(result.passed());
+        assert_eq!(result.message, "All tests passed");
+        assert_eq!(result.frames, 1);
+    }
+
+    #[test]
+    fn test_failing_rom() {
+        let result = run_test_rom(&fake_test_rom("3) BRK pushes wrong flags", 3, false), 60).unwrap();
+        assert!(!result.passed());
+        assert_eq!(result.code, 3);
+        assert_eq!(result.message, "3) BRK pushes wrong flags");
+    }
+
+    #[test]
+    fn test_reset_request() {
+        let result = run_test_rom(&fake_test_rom("Passed after reset", 0, true), 60).unwrap();
+        assert!(result.passed());
+        assert_eq!(result.message, "Passed after reset");
+        // the reset is held off for about 100 ms
+        assert!(result.frames > 6);
+    }
+
+    #[test]
+    fn test_timeout_and_halt() {
+        let silent = nrom_image(".org $C000\nstart: JMP start\n.org $FFFC\n.word start");
+        assert_eq!(run_test_rom(&silent, 5), Err(TestRomError::TimedOut { frames: 5, message: String::new() }));
+
+        let halts = nrom_image(".org $C000\nstart: BRK\n.org $FFFC\n.word start");
+        assert_eq!(run_test_rom(&halts, 5), Err(TestRomError::Halted { message: String::new() }));
+        assert!(matches!(run_test_rom(b"junk", 5), Err(TestRomError::Rom(_))));
+    }
+
+    fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                find_roms(&path, roms);
+            } else if path.extension().is_some_and(|ext| ext == "nes") {
+                roms.push(path);
+            }
+        }
+    }
+
+    /// Runs every ROM under `$NES_TEST_ROMS` (e.g. a checkout of instr_test-v5,
+    /// cpu_timing_test, ppu_vbl_nmi and apu_test). The ROMs are not
+    /// redistributable, so this only runs when asked for with `--ignored`.
+    #[test]
+    #[ignore = "needs $NES_TEST_ROMS"]
+    fn test_rom_suites() {
+        let dir = env::var_os("NES_TEST_ROMS").expect("$NES_TEST_ROMS names a directory of test ROMs");
+        let mut roms = Vec::new();
+        find_roms(Path::new(&dir), &mut roms);
+        roms.sort();
+
+        let mut failures = Vec::new();
+        for path in &roms {
+            let rom = fs::read(path).unwrap();
+            let outcome = match run_test_rom(&rom, 60 * 60) {
+                Ok(result) if result.passed() => continue,
+                Ok(result) => format!("failed with code {}: {}", result.code, result.message.trim()),
+                Err(err) => err.to_string(),
+            };
+            failures.push(format!("{}: {}", path.display(), outcome));
+        }
+        assert!(failures.is_empty(), "{} of {} ROMs failed:\n{}", failures.len(), roms.len(), failures.join("\n"));
+    }
+}
@@ -0,0 +1,124 @@
+mod test_video {
+    use nes_emulator::ntsc::{NtscSetup, NTSC_OUT_WIDTH};
+    use nes_emulator::palette::Palette;
+    use nes_emulator::ppu::PPU;
+    use nes_emulator::video::{scanlines, Image, Scaler, VideoConfig};
+
+    const W: u32 = 0xFFFFFF;
+    const K: u32 = 0x000000;
+
+    fn image(width: usize, pixels: &[u32]) -> Image {
+        Image { width, height: pixels.len() / width, pixels: pixels.to_vec() }
+    }
+
+    /// A 3x3 picture with a white diagonal from the top right.
+    fn diagonal() -> Image {
+        image(3, &[
+            K, W, W,
+            K, K, W,
+            K, K, K,
+        ])
+    }
+
+    #[test]
+    fn test_scale2x() {
+        let out = Scaler::Scale2x.apply(&diagonal());
+        assert_eq!((out.width, out.height), (6, 6));
+        // the centre pixel's top right corner fills in along the edge
+        assert_eq!(out.at(2, 2), K);
+        assert_eq!(out.at(3, 2), W);
+        assert_eq!(out.at(3, 3), K);
+
+        // flat areas are left alone
+        let flat = image(2, &[W; 4]);
+        assert_eq!(Scaler::Scale2x.apply(&flat).pixels, vec![W; 16]);
+    }
+
+    #[test]
+    fn test_scale3x() {
+        let out = Scaler::Scale3x.apply(&diagonal());
+        assert_eq!((out.width, out.height), (9, 9));
+        assert_eq!(out.at(5, 3), W);
+        assert_eq!(out.at(4, 3), K);
+        assert_eq!(out.at(5, 4), K);
+        assert_eq!(out.at(4, 4), K);
+    }
+
+    #[test]
+    fn test_hq2x() {
+        let out = Scaler::Hq2x.apply(&diagonal());
+        assert_eq!((out.width, out.height), (6, 6));
+        // pattern 22: the centre's top right corner blends the edge in
+        assert_eq!([out.at(2, 2), out.at(3, 2), out.at(2, 3), out.at(3, 3)], [K, 0x808080, K, K]);
+        // pattern 105: the top middle pixel sits on an edge running down
+        // and left, so its left quarters take the 61 and 90 blends
+        assert_eq!([out.at(2, 0), out.at(2, 1)], [0xBFBFBF, 0x404040]);
+
+        let flat = image(2, &[W; 4]);
+        assert_eq!(Scaler::Hq2x.apply(&flat).pixels, vec![W; 16]);
+    }
+
+    #[test]
+    fn test_xbrz_smooths_staircase() {
+        for scaler in [Scaler::Xbrz2, Scaler::Xbrz3, Scaler::Xbrz4] {
+            let out = scaler.apply(&diagonal());
+            let factor = scaler.factor() as isize;
+            assert_eq!(out.width, 3 * factor as usize);
+
+            // the outermost subpixel of the centre's top right corner is
+            // at least half white; the far corner stays black
+            let [_, r, _, _] = out.at(2 * factor - 1, factor).to_be_bytes();
+            assert!(r >= 0x7F, "factor {}: {:02X}", factor, r);
+            assert_eq!(out.at(factor, 2 * factor - 1), K);
+        }
+    }
+
+    #[test]
+    fn test_scanlines() {
+        let out = scanlines(&image(1, &[0x804020, 0x804020]), 2, 50);
+        assert_eq!(out.pixels, vec![0x804020, 0x402010, 0x804020, 0x402010]);
+
+        // already doubled pictures darken the last row of each line
+        let out = scanlines(&image(1, &[W; 6]), 2, 100);
+        assert_eq!(out.pixels, vec![W, W, K, W, W, K]);
+    }
+
+    #[test]
+    fn test_render_from_config() {
+        let mut ppu = PPU::new();
+        ppu.frame_buffer.fill(0x30);
+
+        let image = VideoConfig::default().render(&ppu);
+        assert_eq!((image.width, image.height), (256, 240));
+        assert_eq!(image.pixels[0], W);
+
+        let config = VideoConfig {
+            palette: Palette::from_bytes(&[0x40; 192]).unwrap(),
+            scaler: Scaler::Scale3x,
+            scanlines: 25,
+            ..VideoConfig::default()
+        };
+        let image = config.render(&ppu);
+        assert_eq!((image.width, image.height), (768, 720));
+        assert_eq!(image.pixels[..2], [0x404040, 0x404040]);
+        assert_eq!(image.pixels[2 * 768], 0x303030);
+
+        let config = VideoConfig { ntsc: Some(NtscSetup::default()), scanlines: 50, ..VideoConfig::default() };
+        let image = config.render(&ppu);
+        assert_eq!((image.width, image.height), (NTSC_OUT_WIDTH, 480));
+    }
+
+    #[test]
+    fn test_ppm() {
+        let ppm = image(2, &[0x123456, 0xABCDEF]).to_ppm();
+        assert_eq!(ppm, b"P6\n2 1\n255\n\x12\x34\x56\xAB\xCD\xEF");
+    }
+
+    #[test]
+    fn test_parse_scaler() {
+        assert_eq!(Scaler::parse("HQ2x"), Some(Scaler::Hq2x));
+        assert_eq!(Scaler::parse("blend2x"), None);
+        assert_eq!(Scaler::parse("xbrz4").map(|scaler| scaler.factor()), Some(4));
+        assert_eq!(Scaler::parse("xbrz6"), None);
+    }
+}
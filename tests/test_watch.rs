@@ -0,0 +1,63 @@
+mod test_watch {
+    use std::sync::{Arc, Mutex};
+    use nes_emulator::asm;
+    use nes_emulator::cpu::CPU;
+    use nes_emulator::watch::{WatchEvent, WatchKind};
+
+    fn recorder() -> (Arc<Mutex<Vec<WatchEvent>>>, impl FnMut(&WatchEvent) + Send + 'static) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        (events, move |event: &WatchEvent| sink.lock().unwrap().push(*event))
+    }
+
+    fn cpu_with(program: Vec<u8>) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.load(program);
+        cpu.stack_pointer = 0xFF;
+        cpu
+    }
+
+    #[test]
+    fn test_write_watch_reports_writer() {
+        let mut cpu = cpu_with(asm!("LDA #$11", "STA $20", "INX", "LDA #$22", "STA $20", "STA $21", "BRK"));
+        let (events, callback) = recorder();
+        cpu.watches.add(WatchKind::WRITE, 0x20..=0x20, callback);
+        cpu.run().unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(*events, vec![
+            WatchEvent { kind: WatchKind::WRITE, address: 0x20, value: 0x11, pc: 0x8002, cycle: 4 },
+            WatchEvent { kind: WatchKind::WRITE, address: 0x20, value: 0x22, pc: 0x8007, cycle: 11 },
+        ]);
+    }
+
+    #[test]
+    fn test_read_and_execute_watches() {
+        let mut cpu = cpu_with(asm!("LDA $30", "ADC $30", "BRK"));
+        cpu.mem_write(0x30, 5);
+        let (reads, on_read) = recorder();
+        let (fetches, on_fetch) = recorder();
+        cpu.watches.add(WatchKind::READ, 0x30..=0x3F, on_read);
+        cpu.watches.add(WatchKind::EXECUTE, 0x8000..=0xFFFF, on_fetch);
+        cpu.run().unwrap();
+
+        let reads = reads.lock().unwrap();
+        assert_eq!(reads.iter().map(|e| (e.pc, e.value)).collect::<Vec<_>>(), vec![(0x8000, 5), (0x8002, 5)]);
+
+        let fetches = fetches.lock().unwrap();
+        assert_eq!(fetches.iter().map(|e| (e.address, e.value)).collect::<Vec<_>>(),
+            vec![(0x8000, 0xA5), (0x8002, 0x65), (0x8004, 0x00)]);
+    }
+
+    #[test]
+    fn test_remove_watch() {
+        let mut cpu = cpu_with(asm!("STA $20", "BRK"));
+        let (events, callback) = recorder();
+        let id = cpu.watches.add(WatchKind::READ | WatchKind::WRITE, 0x0000..=0xFFFF, callback);
+
+        assert!(cpu.watches.remove(id));
+        assert!(!cpu.watches.remove(id));
+        cpu.run().unwrap();
+        assert!(events.lock().unwrap().is_empty());
+    }
+}
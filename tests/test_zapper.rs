@@ -0,0 +1,82 @@
+mod common;
+
+mod test_zapper {
+    use crate::common;
+    use nes_emulator::bus::{Bus, NesBus};
+    use nes_emulator::input::InputLayout;
+    use nes_emulator::zapper::{Zapper, ZapperInput};
+
+    /// A console with rendering off, a white backdrop and a Zapper in port 2.
+    fn nes() -> NesBus {
+        let mut bus = common::nes("").bus;
+        bus.ppu.palette[0] = 0x30;
+        bus.ports = InputLayout::Zapper.devices();
+        bus
+    }
+
+    /// Runs the PPU to `dot` of `scanline` in the current frame.
+    fn run_to(bus: &mut NesBus, scanline: u16, dot: u16) {
+        while (bus.ppu.scanline, bus.ppu.dot) != (scanline, dot) {
+            bus.ppu.tick(1, &mut bus.cartridge);
+        }
+    }
+
+    fn aim(bus: &mut NesBus, x: usize, y: usize) {
+        bus.zapper_mut().unwrap().aim_at(x, y);
+    }
+
+    #[test]
+    fn test_light_follows_the_beam() {
+        let mut bus = nes();
+        aim(&mut bus, 100, 100);
+
+        // the lines around the aim point have not been drawn yet
+        run_to(&mut bus, 90, 0);
+        assert_eq!(bus.mem_read(0x4017) & 0x08, 0x08);
+
+        run_to(&mut bus, 104, 300);
+        assert_eq!(bus.mem_read(0x4017) & 0x08, 0x00);
+
+        // the sensor goes dark again once the beam has moved on
+        run_to(&mut bus, 130, 0);
+        assert_eq!(bus.mem_read(0x4017) & 0x08, 0x08);
+
+        // a black screen never trips it
+        let mut bus = nes();
+        bus.ppu.palette[0] = 0x0F;
+        aim(&mut bus, 100, 100);
+        run_to(&mut bus, 104, 300);
+        assert_eq!(bus.mem_read(0x4017) & 0x08, 0x08);
+
+        // nor does aiming away from the screen
+        let mut bus = nes();
+        run_to(&mut bus, 104, 300);
+        assert_eq!(bus.mem_read(0x4017) & 0x08, 0x08);
+    }
+
+    #[test]
+    fn test_trigger_and_open_bus() {
+        let mut bus = nes();
+        bus.zapper_mut().unwrap().input = ZapperInput { aim: None, trigger: true };
+        bus.open_bus = 0x40;
+        assert_eq!(bus.peek(0x4017), 0x40 | 0x10 | 0x08);
+        assert_eq!(bus.mem_read(0x4017), 0x40 | 0x10 | 0x08);
+
+        // unplugged, the controller answers again
+        bus.ports = InputLayout::Standard.devices();
+        assert_eq!(bus.mem_read(0x4017) & 0x1F, 0);
+    }
+
+    #[test]
+    fn test_aim_from_view() {
+        let mut zapper = Zapper::new();
+        // the middle of a 3x-scaled window
+        zapper.aim_from_view(384.0, 360.0, 768.0, 720.0);
+        assert_eq!(zapper.input.aim, Some((128, 120)));
+
+        zapper.aim_from_view(-1.0, 10.0, 768.0, 720.0);
+        assert_eq!(zapper.input.aim, None);
+        zapper.aim_from_view(10.0, 720.0, 768.0, 720.0);
+        assert_eq!(zapper.input.aim, None);
+    }
+}